@@ -0,0 +1,298 @@
+//! Generators for property tests that check printed asts parse back to the same ast
+//!
+//! Only asts the parser can produce are generated, e.g. identifiers never look like registers and
+//! arrays always have at least two elements.
+
+use proptest::{collection::vec, option, prelude::*};
+
+use crate::{
+    literal::{self, Array, Byte, NotSet, Repetition},
+    Address, CodeSegment, DataSegment, Expr, Ident, Instruction, Label, Literal, Oper, ParseNom,
+    Placement, Pointer, Register, Root, Span, Variable,
+};
+
+/// Parse the whole of `input` panicking if anything is left over
+pub(crate) fn reparse<T: ParseNom>(input: &str) -> T {
+    let (rest, value) = T::parse(Span::new_extra(input, input))
+        .unwrap_or_else(|err| panic!("failed to parse {input:?}: {err:?}"));
+    assert!(
+        rest.trim().is_empty(),
+        "{:?} was not parsed",
+        rest.fragment()
+    );
+    value
+}
+
+impl Arbitrary for Ident {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        "[a-zA-Z_][a-zA-Z0-9_]{0,8}"
+            .prop_filter("identifiers can not be register names", |s| {
+                !Register::ALL.iter().any(|r| r.eq_ignore_ascii_case(s))
+            })
+            .prop_map(Ident::new)
+            .boxed()
+    }
+}
+
+impl Arbitrary for Register {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        prop_oneof![Just(Self::A), Just(Self::B), Just(Self::C), Just(Self::D)].boxed()
+    }
+}
+
+impl Arbitrary for Oper {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        prop_oneof![
+            Just(Self::Add),
+            Just(Self::Sub),
+            Just(Self::Mul),
+            Just(Self::Div),
+            Just(Self::Shl),
+            Just(Self::Shr),
+            Just(Self::And),
+            Just(Self::Or),
+        ]
+        .boxed()
+    }
+}
+
+impl Arbitrary for Byte {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        (-128i16..=255).prop_map(Self).boxed()
+    }
+}
+
+fn element() -> impl Strategy<Value = Literal> {
+    let leaf = prop_oneof![
+        any::<Byte>().prop_map(Literal::Byte),
+        Just(Literal::NotSet(NotSet)),
+        // an expression that is only a literal is parsed as a byte
+        any::<Expr>()
+            .prop_filter("literals are bytes", |e| !matches!(e, Expr::Lit(..)))
+            .prop_map(|e| Literal::Expr(literal::Expr(e))),
+    ];
+    leaf.prop_recursive(2, 8, 3, |inner| {
+        (1usize..5, vec(inner, 1..4))
+            .prop_map(|(count, values)| Literal::Dup(literal::Dup(Repetition { count, values })))
+    })
+}
+
+impl Arbitrary for Literal {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        prop_oneof![
+            element(),
+            vec(element(), 2..8).prop_map(|v| Self::Array(Array(v))),
+        ]
+        .boxed()
+    }
+}
+
+impl Arbitrary for Expr {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        prop_oneof![
+            any::<Byte>().prop_map(Self::Lit),
+            any::<Ident>().prop_map(Self::Ident),
+            any::<Register>().prop_map(Self::Reg),
+        ]
+        .prop_recursive(3, 16, 2, |inner| {
+            prop_oneof![
+                inner.clone().prop_map(|e| Self::Neg(Box::new(e))),
+                (inner.clone(), any::<Oper>(), inner).prop_map(|(left, oper, right)| {
+                    Self::Binary {
+                        left: Box::new(left),
+                        oper,
+                        right: Box::new(right),
+                    }
+                }),
+            ]
+        })
+        .boxed()
+    }
+}
+
+impl Arbitrary for Address {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        any::<Expr>().prop_map(|to| Self { to }).boxed()
+    }
+}
+
+impl Arbitrary for Pointer {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        (any::<Ident>(), option::of((any::<Oper>(), any::<Byte>())))
+            .prop_map(|(var, offset)| Self { var, offset })
+            .boxed()
+    }
+}
+
+impl Arbitrary for Instruction {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        let reg = any::<Register>;
+        let addr = any::<Address>;
+        let expr = any::<Expr>;
+        let target = || {
+            prop_oneof![
+                any::<Ident>(),
+                any::<Ident>().prop_map(|i| format!(".{i}").into()),
+                (any::<Ident>(), any::<Ident>()).prop_map(|(g, l)| format!("{g}.{l}").into()),
+                "[0-9]{1,2}[bf]".prop_map(Ident::new),
+            ]
+        };
+        prop_oneof![
+            Just(Self::NoOp),
+            addr().prop_map(Self::InputC),
+            addr().prop_map(Self::InputCF),
+            addr().prop_map(Self::InputD),
+            addr().prop_map(Self::InputDF),
+            (reg(), reg()).prop_map(|(rx, ry)| Self::Move(rx, ry)),
+            (reg(), expr()).prop_map(|(rx, e)| Self::LoadI(rx, e)),
+            (reg(), any::<Pointer>()).prop_map(|(rx, p)| Self::LoadP(rx, p)),
+            (reg(), reg()).prop_map(|(rx, ry)| Self::Add(rx, ry)),
+            (reg(), expr()).prop_map(|(rx, e)| Self::AddI(rx, e)),
+            (reg(), reg()).prop_map(|(rx, ry)| Self::Sub(rx, ry)),
+            (reg(), expr()).prop_map(|(rx, e)| Self::SubI(rx, e)),
+            (reg(), addr()).prop_map(|(rx, a)| Self::Load(rx, a)),
+            (reg(), addr()).prop_map(|(rx, a)| Self::LoadF(rx, a)),
+            (addr(), reg()).prop_map(|(a, rx)| Self::Store(a, rx)),
+            (addr(), reg()).prop_map(|(a, rx)| Self::StoreF(a, rx)),
+            reg().prop_map(Self::ShiftL),
+            reg().prop_map(Self::ShiftR),
+            (reg(), reg()).prop_map(|(rx, ry)| Self::Cmp(rx, ry)),
+            target().prop_map(Self::Jump),
+            target().prop_map(Self::BrE),
+            target().prop_map(Self::BrNE),
+            target().prop_map(Self::BrG),
+            target().prop_map(Self::BrGE),
+        ]
+        .boxed()
+    }
+}
+
+impl Arbitrary for Label {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        prop_oneof![
+            any::<Ident>(),
+            any::<Ident>().prop_map(|i| format!(".{i}").into()),
+            "[0-9]{1,2}".prop_map(Ident::new),
+        ]
+        .prop_map(|ident| Self {
+            ident,
+            code_addr: 0,
+            span: None,
+        })
+        .boxed()
+    }
+}
+
+impl Arbitrary for Variable {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        // `.org` can only be written in a data segment
+        let at = option::of((0i16..16).prop_map(|addr| Placement::At(Byte(addr))));
+        (any::<Ident>(), any::<Literal>(), at)
+            .prop_map(|(ident, value, placement)| Self {
+                ident,
+                value,
+                placement,
+                data_addr: 0,
+                span: None,
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for DataSegment {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        vec((any::<Variable>(), option::of(0i16..16)), 1..8)
+            .prop_map(|variables| {
+                let variables = variables
+                    .into_iter()
+                    .map(|(mut var, org)| {
+                        if var.placement.is_none() {
+                            var.placement = org.map(|addr| Placement::Org(Byte(addr)));
+                        }
+                        var
+                    })
+                    .collect();
+                let mut data = Self { variables };
+                data.assign_addresses();
+                data
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for CodeSegment {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        // any number of labels can be in front of every instruction or after the last one
+        let labels = || vec(any::<Label>(), 0..3);
+        (vec((labels(), any::<Instruction>()), 1..12), labels())
+            .prop_map(|(code, end_labels)| {
+                let (labels, instructions): (Vec<_>, Vec<_>) = code.into_iter().unzip();
+                let labels = labels
+                    .into_iter()
+                    .chain(std::iter::once(end_labels))
+                    .enumerate()
+                    .flat_map(|(code_addr, labels)| {
+                        labels
+                            .into_iter()
+                            .map(move |label| Label { code_addr, ..label })
+                    })
+                    .collect();
+                Self {
+                    labels,
+                    instructions,
+                    instruction_spans: Vec::new(),
+                }
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for Root {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        (option::of(any::<DataSegment>()), any::<CodeSegment>())
+            .prop_map(|(data, code)| Self { data, code })
+            .boxed()
+    }
+}
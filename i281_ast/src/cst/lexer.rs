@@ -0,0 +1,165 @@
+use std::sync::Arc;
+
+use super::{GreenElement, GreenNode, GreenToken, SyntaxKind};
+use crate::keyword;
+
+/// Split the source into tokens, every byte ends up in exactly one token
+pub(super) fn lex(text: &str) -> Vec<(SyntaxKind, &str)> {
+    let mut tokens = Vec::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let len = match c {
+            '\n' => 1,
+            '\r' if rest.starts_with("\r\n") => 2,
+            ';' => rest.find(['\r', '\n']).unwrap_or(rest.len()),
+            c if c.is_whitespace() => {
+                let first = c.len_utf8();
+                rest[first..]
+                    .find(|c: char| !c.is_whitespace() || c == '\n' || c == '\r')
+                    .map_or(rest.len(), |len| first + len)
+            }
+            c if c.is_ascii_alphanumeric() || c == '_' => rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len()),
+            _ if rest.starts_with("<<") || rest.starts_with(">>") => 2,
+            c => c.len_utf8(),
+        };
+        let (token, after) = rest.split_at(len);
+        tokens.push((kind_of(token), token));
+        rest = after;
+    }
+    tokens
+}
+
+fn kind_of(token: &str) -> SyntaxKind {
+    use SyntaxKind::*;
+    match token {
+        "\n" | "\r\n" => Newline,
+        "." => Dot,
+        ":" => Colon,
+        "," => Comma,
+        "@" => At,
+        "?" => Question,
+        "[" => LBracket,
+        "]" => RBracket,
+        "(" => LParen,
+        ")" => RParen,
+        "+" | "-" | "*" | "/" | "&" | "|" | "<<" | ">>" => Operator,
+        _ if token.starts_with(';') => Comment,
+        _ if token.starts_with(char::is_whitespace) => Whitespace,
+        _ if token.starts_with(|c: char| c.is_ascii_digit()) => Number,
+        _ if token.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') => Ident,
+        _ => Unknown,
+    }
+}
+
+/// Group the tokens into lines, a line ends after its newline
+pub(super) fn lines<'a>(tokens: &[(SyntaxKind, &'a str)]) -> Vec<Vec<(SyntaxKind, &'a str)>> {
+    let mut lines = Vec::new();
+    let mut line = Vec::new();
+    for &token in tokens {
+        line.push(token);
+        if token.0 == SyntaxKind::Newline {
+            lines.push(std::mem::take(&mut line));
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Build the node of a single line from its tokens
+///
+/// The kind of the line is decided by its first few tokens only, the rest of the line is
+/// checked when the `Root` is derived from the tree.
+pub(super) fn line(tokens: &[(SyntaxKind, &str)]) -> Arc<GreenNode> {
+    use SyntaxKind::*;
+    let significant: Vec<_> = tokens
+        .iter()
+        .enumerate()
+        .filter(|(_, (kind, _))| !kind.is_trivia())
+        .map(|(i, &(kind, text))| (i, kind, text))
+        .collect();
+    let is = |n: usize, expected: SyntaxKind| significant.get(n).is_some_and(|t| t.1 == expected);
+    let text = |n: usize| significant.get(n).map_or("", |t| t.2);
+
+    let kind = if significant.is_empty() {
+        Blank
+    } else if is(0, Dot)
+        && is(1, Ident)
+        && [keyword::Data.to_string(), keyword::Code.to_string()]
+            .iter()
+            .any(|kw| kw.eq_ignore_ascii_case(text(1)))
+        && !is(2, Colon)
+    {
+        Header
+    } else if is(0, Dot) && is(1, Ident) && text(1).eq_ignore_ascii_case(&keyword::Org.to_string())
+    {
+        Org
+    } else if is(0, Ident)
+        && is(1, Ident)
+        && text(1).eq_ignore_ascii_case(&keyword::Byte.to_string())
+    {
+        Variable
+    } else {
+        return statement(tokens, &significant);
+    };
+    node(kind, tokens.iter().map(|&t| token(t)).collect())
+}
+
+/// A line with labels followed by an optional instruction
+fn statement(
+    tokens: &[(SyntaxKind, &str)],
+    significant: &[(usize, SyntaxKind, &str)],
+) -> Arc<GreenNode> {
+    use SyntaxKind::*;
+    let kinds: Vec<_> = significant.iter().map(|t| t.1).collect();
+    // the index into `tokens` where every label starts and ends
+    let mut labels = Vec::new();
+    let mut n = 0;
+    loop {
+        let len = match kinds[n..] {
+            [Dot, Ident, Colon, ..] => 3,
+            [Ident | Number, Colon, ..] => 2,
+            _ => break,
+        };
+        labels.push((significant[n].0, significant[n + len - 1].0 + 1));
+        n += len;
+    }
+    let instruction = match kinds.get(n) {
+        None => None,
+        Some(Ident) => Some((significant[n].0, significant[significant.len() - 1].0 + 1)),
+        Some(_) => return node(Error, tokens.iter().map(|&t| token(t)).collect()),
+    };
+
+    let mut children = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        let group = labels
+            .iter()
+            .map(|&range| (Label, range))
+            .chain(instruction.map(|range| (Instruction, range)))
+            .find(|(_, (start, _))| *start == i);
+        match group {
+            Some((kind, (start, end))) => {
+                let inner = tokens[start..end].iter().map(|&t| token(t)).collect();
+                children.push(GreenElement::Node(node(kind, inner)));
+                i = end;
+            }
+            None => {
+                children.push(token(tokens[i]));
+                i += 1;
+            }
+        }
+    }
+    node(Statement, children)
+}
+
+fn token((kind, text): (SyntaxKind, &str)) -> GreenElement {
+    GreenElement::Token(Arc::new(GreenToken::new(kind, text)))
+}
+
+pub(super) fn node(kind: SyntaxKind, children: Vec<GreenElement>) -> Arc<GreenNode> {
+    Arc::new(GreenNode::new(kind, children))
+}
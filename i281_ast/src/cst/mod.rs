@@ -0,0 +1,593 @@
+//! A lossless concrete syntax tree of the source
+//!
+//! Unlike the [`Root`] which only keeps what the program means, the syntax tree keeps every byte
+//! of the source including comments, blank lines and how things were spelled. It is split into
+//! an immutable green tree that only knows the kind and length of everything and can be shared
+//! between versions of the tree, and borrowed [`SyntaxNode`]s and [`SyntaxToken`]s that also know
+//! where they are in the source.
+//!
+//! The tree only goes as deep as lines and the tokens on them. Every line is parsed on its own by
+//! the nom parsers of the [`Root`] when it is lexed and the result is kept with the tree, so an
+//! edit only parses the lines it touches again. The [`Root`] is put together from the parsed
+//! lines of each segment.
+
+mod lexer;
+mod parse;
+
+use std::{ops::Range, sync::Arc};
+
+use nom::Slice;
+
+use crate::{keyword, ParseError, Root, Span};
+use parse::{error, Line};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SyntaxKind {
+    // tokens
+    /// Spaces and tabs, newlines are their own token
+    Whitespace,
+    Newline,
+    /// `;` up to the end of the line
+    Comment,
+    Ident,
+    /// Anything starting with a digit, e.g. `12`, `0xFF`, `200u8` or `1b`
+    Number,
+    Dot,
+    Colon,
+    Comma,
+    At,
+    Question,
+    LBracket,
+    RBracket,
+    LParen,
+    RParen,
+    Operator,
+    /// A character that is not part of the language
+    Unknown,
+
+    // nodes
+    Root,
+    /// The `.data` line and every line up to the next segment
+    DataSegment,
+    /// The `.code` line and every line up to the next segment
+    CodeSegment,
+    /// A line that only has whitespace or a comment
+    Blank,
+    /// A `.data` or `.code` line
+    Header,
+    /// A `.org N` line
+    Org,
+    /// A line that declares a variable
+    Variable,
+    /// A line with any number of labels and an optional instruction
+    Statement,
+    Label,
+    /// The opcode as it was written followed by its operands
+    Instruction,
+    /// A line that could not be recognized
+    Error,
+}
+
+impl SyntaxKind {
+    /// Returns true for tokens that do not change the meaning of the program
+    pub fn is_trivia(self) -> bool {
+        matches!(self, Self::Whitespace | Self::Newline | Self::Comment)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GreenToken {
+    kind: SyntaxKind,
+    text: Box<str>,
+}
+
+impl GreenToken {
+    pub fn new(kind: SyntaxKind, text: &str) -> Self {
+        Self {
+            kind,
+            text: text.into(),
+        }
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.kind
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum GreenElement {
+    Node(Arc<GreenNode>),
+    Token(Arc<GreenToken>),
+}
+
+impl GreenElement {
+    pub fn text_len(&self) -> usize {
+        match self {
+            Self::Node(node) => node.text_len(),
+            Self::Token(token) => token.text().len(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GreenNode {
+    kind: SyntaxKind,
+    text_len: usize,
+    children: Vec<GreenElement>,
+}
+
+impl GreenNode {
+    pub fn new(kind: SyntaxKind, children: Vec<GreenElement>) -> Self {
+        Self {
+            kind,
+            text_len: children.iter().map(GreenElement::text_len).sum(),
+            children,
+        }
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.kind
+    }
+
+    pub fn text_len(&self) -> usize {
+        self.text_len
+    }
+
+    pub fn children(&self) -> &[GreenElement] {
+        &self.children
+    }
+}
+
+/// A node of the tree together with where it starts in the source
+#[derive(Clone, Copy, Debug)]
+pub struct SyntaxNode<'a> {
+    green: &'a Arc<GreenNode>,
+    offset: usize,
+}
+
+/// A token of the tree together with where it starts in the source
+#[derive(Clone, Copy, Debug)]
+pub struct SyntaxToken<'a> {
+    green: &'a GreenToken,
+    offset: usize,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum SyntaxElement<'a> {
+    Node(SyntaxNode<'a>),
+    Token(SyntaxToken<'a>),
+}
+
+impl<'a> SyntaxNode<'a> {
+    pub fn kind(&self) -> SyntaxKind {
+        self.green.kind()
+    }
+
+    pub fn green(&self) -> &'a Arc<GreenNode> {
+        self.green
+    }
+
+    pub fn text_range(&self) -> Range<usize> {
+        self.offset..self.offset + self.green.text_len()
+    }
+
+    pub fn children(&self) -> impl Iterator<Item = SyntaxElement<'a>> {
+        let mut offset = self.offset;
+        self.green.children().iter().map(move |child| {
+            let start = offset;
+            offset += child.text_len();
+            match child {
+                GreenElement::Node(green) => SyntaxElement::Node(SyntaxNode {
+                    green,
+                    offset: start,
+                }),
+                GreenElement::Token(green) => SyntaxElement::Token(SyntaxToken {
+                    green,
+                    offset: start,
+                }),
+            }
+        })
+    }
+
+    pub fn child_nodes(&self) -> impl Iterator<Item = SyntaxNode<'a>> {
+        self.children().filter_map(|child| match child {
+            SyntaxElement::Node(node) => Some(node),
+            SyntaxElement::Token(..) => None,
+        })
+    }
+
+    /// Every token within the node in source order
+    pub fn tokens(&self) -> Vec<SyntaxToken<'a>> {
+        let mut tokens = Vec::new();
+        for child in self.children() {
+            match child {
+                SyntaxElement::Node(node) => tokens.extend(node.tokens()),
+                SyntaxElement::Token(token) => tokens.push(token),
+            }
+        }
+        tokens
+    }
+
+    /// The first token that is not trivia, for an instruction this is the opcode as written
+    pub fn first_significant_token(&self) -> Option<SyntaxToken<'a>> {
+        self.tokens().into_iter().find(|t| !t.kind().is_trivia())
+    }
+
+    pub fn text(&self) -> String {
+        self.tokens().iter().map(SyntaxToken::text).collect()
+    }
+}
+
+impl<'a> SyntaxToken<'a> {
+    pub fn kind(&self) -> SyntaxKind {
+        self.green.kind()
+    }
+
+    pub fn text(&self) -> &'a str {
+        self.green.text()
+    }
+
+    pub fn text_range(&self) -> Range<usize> {
+        self.offset..self.offset + self.green.text().len()
+    }
+}
+
+/// The syntax tree of a whole source file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SyntaxTree {
+    text: String,
+    green: Arc<GreenNode>,
+    /// What each of the [`Self::lines`] means, `None` for a line with an error
+    parsed: Vec<Option<Arc<Line>>>,
+}
+
+impl SyntaxTree {
+    /// Build the tree, this never fails as anything that is not understood becomes an error node
+    pub fn parse(text: &str) -> Self {
+        let tokens = lexer::lex(text);
+        let lines: Vec<_> = lexer::lines(&tokens)
+            .iter()
+            .map(|l| lexer::line(l))
+            .collect();
+        Self {
+            text: text.to_owned(),
+            parsed: lines.iter().map(parse_line).collect(),
+            green: build(lines),
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn root(&self) -> SyntaxNode<'_> {
+        SyntaxNode {
+            green: &self.green,
+            offset: 0,
+        }
+    }
+
+    /// Every line of the source in order, segments are looked into
+    pub fn lines(&self) -> Vec<SyntaxNode<'_>> {
+        self.root()
+            .child_nodes()
+            .flat_map(|node| match node.kind() {
+                SyntaxKind::DataSegment | SyntaxKind::CodeSegment => node.child_nodes().collect(),
+                _ => vec![node],
+            })
+            .collect()
+    }
+
+    /// The token that contains the byte at `offset`
+    pub fn token_at(&self, offset: usize) -> Option<SyntaxToken<'_>> {
+        self.root()
+            .tokens()
+            .into_iter()
+            .find(|t| t.text_range().contains(&offset))
+    }
+
+    /// Replace `range` of the source with `replacement` and update the tree
+    ///
+    /// Only the lines the edit touches are lexed and parsed again, the green nodes of every other
+    /// line and what they mean are reused as they are.
+    ///
+    /// # Panics
+    /// If the range is out of bounds or does not fall on character boundaries.
+    pub fn edit(&mut self, range: Range<usize>, replacement: &str) {
+        let lines: Vec<(usize, Arc<GreenNode>)> = self
+            .lines()
+            .iter()
+            .map(|line| (line.text_range().start, line.green().clone()))
+            .collect();
+        self.text.replace_range(range.clone(), replacement);
+        if lines.is_empty() {
+            *self = Self::parse(&self.text);
+            return;
+        }
+
+        // the lines that contain the start and the end of the edit, an edit that ends right
+        // after a newline also touches the line after it as they may be joined
+        let containing = |offset: usize| {
+            lines
+                .iter()
+                .position(|(start, line)| offset < start + line.text_len())
+                .unwrap_or(lines.len() - 1)
+        };
+        let first = containing(range.start);
+        let last = containing(range.end).max(first);
+        let start = lines[first].0;
+        let end = lines[last].0 + lines[last].1.text_len() + replacement.len() - range.len();
+
+        let tokens = lexer::lex(&self.text[start..end]);
+        let relexed: Vec<_> = lexer::lines(&tokens)
+            .into_iter()
+            .map(|l| lexer::line(&l))
+            .collect();
+        self.parsed = self.parsed[..first]
+            .iter()
+            .cloned()
+            .chain(relexed.iter().map(parse_line))
+            .chain(self.parsed[last + 1..].iter().cloned())
+            .collect();
+        let lines = lines[..first]
+            .iter()
+            .map(|(_, line)| line.clone())
+            .chain(relexed)
+            .chain(lines[last + 1..].iter().map(|(_, line)| line.clone()))
+            .collect();
+        self.green = build(lines);
+    }
+
+    /// Derive the abstract syntax tree of the program
+    ///
+    /// The segments are put together from the lines that were parsed when the tree was built or
+    /// edited. A line with an error is parsed again where it is in the source so the error points
+    /// into the whole file.
+    pub fn to_root(&self) -> Result<Root, ParseError<'static>> {
+        let full = Span::new_extra(&self.text, &self.text);
+        let mut parsed = self.parsed.iter();
+        let mut line = |node: SyntaxNode| {
+            let range = node.text_range();
+            let input = full.slice(range.clone());
+            let line = match parsed.next().cloned().flatten() {
+                Some(line) => Line::clone(&line).shift(range.start),
+                None => parse::line(node, input)?,
+            };
+            Ok::<_, ParseError<'static>>((input, line))
+        };
+
+        let mut data = None;
+        let mut code = None;
+        for node in self.root().child_nodes() {
+            let input = full.slice(node.text_range());
+            let is_data = match node.kind() {
+                SyntaxKind::DataSegment if data.is_none() => true,
+                SyntaxKind::CodeSegment if code.is_none() => false,
+                SyntaxKind::DataSegment | SyntaxKind::CodeSegment => {
+                    return Err(error(
+                        input,
+                        "a program can only have one `.data` and one `.code` segment",
+                    ));
+                }
+                SyntaxKind::Blank => {
+                    line(node)?;
+                    continue;
+                }
+                _ => return Err(error(input, "expected a `.data` or `.code` segment")),
+            };
+            let mut lines = node
+                .child_nodes()
+                .map(&mut line)
+                .collect::<Result<Vec<_>, _>>()?
+                .into_iter();
+            // a segment always starts with its header
+            let header = lines.next().map_or(input, |(header, _)| header);
+            match is_data {
+                true => data = Some(parse::data_segment(header, lines.collect())?),
+                false => code = Some(parse::code_segment(header, lines.collect())?),
+            }
+        }
+        let Some(code) = code else {
+            let end = full.slice(self.text.len()..);
+            return Err(error(
+                end,
+                "add a `.code` segment with the instructions to run",
+            ));
+        };
+        Ok(Root { data, code })
+    }
+}
+
+/// Group the lines into segments, each segment starts at its header
+fn build(lines: Vec<Arc<GreenNode>>) -> Arc<GreenNode> {
+    let mut children = Vec::new();
+    let mut segment: Option<(SyntaxKind, Vec<GreenElement>)> = None;
+    for line in lines {
+        if line.kind() == SyntaxKind::Header {
+            if let Some((kind, lines)) = segment.take() {
+                children.push(GreenElement::Node(lexer::node(kind, lines)));
+            }
+            let is_data = line
+                .children()
+                .iter()
+                .filter_map(|child| match child {
+                    GreenElement::Token(token) if token.kind() == SyntaxKind::Ident => Some(token),
+                    _ => None,
+                })
+                .next()
+                .is_some_and(|token| {
+                    token
+                        .text()
+                        .eq_ignore_ascii_case(&keyword::Data.to_string())
+                });
+            let kind = match is_data {
+                true => SyntaxKind::DataSegment,
+                false => SyntaxKind::CodeSegment,
+            };
+            segment = Some((kind, vec![GreenElement::Node(line)]));
+        } else if let Some((_, lines)) = &mut segment {
+            lines.push(GreenElement::Node(line));
+        } else {
+            children.push(GreenElement::Node(line));
+        }
+    }
+    if let Some((kind, lines)) = segment {
+        children.push(GreenElement::Node(lexer::node(kind, lines)));
+    }
+    lexer::node(SyntaxKind::Root, children)
+}
+
+/// Parse a line from its own text, `None` if it has an error which [`SyntaxTree::to_root`] finds
+/// again where the line is in the source
+fn parse_line(green: &Arc<GreenNode>) -> Option<Arc<Line>> {
+    let node = SyntaxNode { green, offset: 0 };
+    let text = node.text();
+    parse::line(node, Span::new_extra(&text, &text))
+        .ok()
+        .map(Arc::new)
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use proptest::prelude::*;
+
+    use super::{SyntaxKind, SyntaxTree};
+    use crate::Root;
+
+    const SOURCE: &str = "; sum an array
+.data
+arr BYTE 1, 2 ; the input
+
+.code
+start: LOADI A, 0
+1:  brz  1f   ; spelled as written
+    JUMP 1b
+1:
+";
+
+    #[test]
+    fn cst1() {
+        let tree = SyntaxTree::parse(SOURCE);
+        assert_eq!(tree.root().text(), SOURCE);
+        let kinds: Vec<_> = tree.lines().iter().map(|l| l.kind()).collect();
+        {
+            use SyntaxKind::*;
+            assert_eq!(
+                kinds,
+                [
+                    Blank, Header, Variable, Blank, Header, Statement, Statement, Statement,
+                    Statement
+                ]
+            );
+        }
+        let branch = tree.lines()[6].child_nodes().last().unwrap();
+        assert_eq!(branch.kind(), SyntaxKind::Instruction);
+        assert_eq!(branch.first_significant_token().unwrap().text(), "brz");
+        assert_eq!(branch.text(), "brz  1f");
+        let comment = tree.token_at(SOURCE.find("; the").unwrap()).unwrap();
+        assert_eq!(comment.kind(), SyntaxKind::Comment);
+        let root = tree.to_root().unwrap();
+        assert_eq!(root.data.unwrap().variables.len(), 1);
+        assert_eq!(root.code.instructions.len(), 3);
+        assert_eq!(root.code.labels.len(), 3);
+    }
+
+    #[test]
+    fn cst2() {
+        let mut tree = SyntaxTree::parse(SOURCE);
+        let before = tree.clone();
+        let at = SOURCE.find("JUMP").unwrap();
+        tree.edit(at..at + 4, "BRNZ");
+        assert_eq!(tree, SyntaxTree::parse(&SOURCE.replace("JUMP", "BRNZ")));
+        let (old, new) = (before.lines(), tree.lines());
+        assert!(Arc::ptr_eq(old[5].green(), new[5].green()));
+        assert!(!Arc::ptr_eq(old[7].green(), new[7].green()));
+
+        // breaking a line and fixing it again
+        let at = SOURCE.find("A, 0").unwrap() + 3;
+        tree.edit(at..at + 1, "");
+        assert!(tree.to_root().is_err());
+        tree.edit(at..at, "0");
+        let edited = SOURCE.replace("JUMP", "BRNZ");
+        assert_eq!(tree, SyntaxTree::parse(&edited));
+        assert_eq!(tree.to_root().unwrap(), Root::parse(&edited).unwrap());
+    }
+
+    #[test]
+    fn cst3() {
+        let err = SyntaxTree::parse(".code\nNOOP\ngarbage here\n")
+            .to_root()
+            .unwrap_err();
+        assert!(err.to_string().contains("line: 3"));
+        assert!(SyntaxTree::parse(".data\nx BYTE 1\n").to_root().is_err());
+        let root = SyntaxTree::parse(".code\nNOOP\n.data\nx BYTE 1\n")
+            .to_root()
+            .unwrap();
+        assert!(root.data.is_some());
+        assert!(SyntaxTree::parse(".data\n.org 1\n.code\nNOOP\n")
+            .to_root()
+            .is_err());
+        assert!(SyntaxTree::parse(".code\nNOOP extra\n").to_root().is_err());
+    }
+
+    #[test]
+    fn cst4() {
+        // only the edited line is parsed again and the spans after it still point at the source
+        let mut tree = SyntaxTree::parse(SOURCE);
+        let before = tree.clone();
+        let at = SOURCE.find("A, 0").unwrap() + 3;
+        tree.edit(at..at + 1, "100");
+        for (i, (old, new)) in before.parsed.iter().zip(&tree.parsed).enumerate() {
+            let (old, new) = (old.as_ref().unwrap(), new.as_ref().unwrap());
+            assert_eq!(Arc::ptr_eq(old, new), i != 5, "line {i}");
+        }
+
+        let root = tree.to_root().unwrap();
+        let edited = SOURCE.replace("A, 0", "A, 100");
+        assert_eq!(root, Root::parse(&edited).unwrap());
+        let span = root.code.instruction_span(1).unwrap();
+        assert_eq!(
+            &edited[span.offset()..span.offset() + span.len()],
+            "brz  1f"
+        );
+        let label = &root.code.labels[2];
+        let span = label.span.unwrap();
+        assert_eq!(&edited[span.offset()..span.offset() + span.len()], "1");
+    }
+
+    proptest! {
+        #[test]
+        fn cst_lossless(text in "[ -~\t\n]{0,64}") {
+            let tree = SyntaxTree::parse(&text);
+            prop_assert_eq!(tree.root().text(), text);
+        }
+
+        #[test]
+        fn cst_edit(
+            text in "[ -~\t\n]{0,48}",
+            start in 0usize..48,
+            len in 0usize..8,
+            replacement in "[ -~\t\n]{0,8}",
+        ) {
+            let start = start.min(text.len());
+            let end = (start + len).min(text.len());
+            let mut tree = SyntaxTree::parse(&text);
+            tree.edit(start..end, &replacement);
+            let mut edited = text.clone();
+            edited.replace_range(start..end, &replacement);
+            prop_assert_eq!(tree, SyntaxTree::parse(&edited));
+        }
+
+        #[test]
+        fn cst_root(root in any::<Root>()) {
+            let text = root.to_string();
+            prop_assert_eq!(SyntaxTree::parse(&text).to_root().unwrap(), root);
+        }
+    }
+}
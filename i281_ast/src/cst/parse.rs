@@ -0,0 +1,214 @@
+use miette::SourceSpan;
+use nom::Slice;
+
+use super::{lexer, SyntaxKind, SyntaxNode};
+use crate::{
+    literal::Byte,
+    segment::org,
+    util::{spanned, ws_start0},
+    CodeSegment, DataSegment, IResult, Instruction, Label, ParseError, ParseNom, Placement, Span,
+    Variable,
+};
+
+/// What a single line of the source means, spans are relative to the start of the line
+///
+/// Lines are parsed on their own so the result only has to change when the line does, the
+/// segments of the [`crate::Root`] are put together from them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) enum Line {
+    Blank,
+    Header,
+    Org(Byte),
+    Variable(Variable),
+    Statement {
+        labels: Vec<Label>,
+        instruction: Option<(Instruction, SourceSpan)>,
+    },
+}
+
+impl Line {
+    /// Move every span by `offset`, for a line that starts `offset` bytes into the source
+    pub(super) fn shift(mut self, offset: usize) -> Self {
+        let shift =
+            |span: SourceSpan| SourceSpan::new((span.offset() + offset).into(), span.len().into());
+        match &mut self {
+            Self::Variable(var) => var.span = var.span.map(shift),
+            Self::Statement {
+                labels,
+                instruction,
+            } => {
+                for label in labels.iter_mut() {
+                    label.span = label.span.map(shift);
+                }
+                if let Some((_, span)) = instruction {
+                    *span = shift(*span);
+                }
+            }
+            Self::Blank | Self::Header | Self::Org(_) => {}
+        }
+        self
+    }
+}
+
+/// Parse a line of the tree, `input` is exactly the text of the line
+///
+/// Spans are relative to wherever `input` starts, so a line parsed from its own text can be kept
+/// while the lines around it are edited.
+pub(super) fn line(node: SyntaxNode, input: Span) -> Result<Line, ParseError<'static>> {
+    let start = node.text_range().start;
+    let part = |node: SyntaxNode| {
+        let range = node.text_range();
+        input.slice(range.start - start..range.end - start)
+    };
+    match node.kind() {
+        SyntaxKind::Blank => Ok(Line::Blank),
+        SyntaxKind::Header => {
+            // the lexer only makes a header of a `.` followed by the name of a segment
+            let name = node
+                .tokens()
+                .into_iter()
+                .filter(|t| !t.kind().is_trivia())
+                .nth(1)
+                .map_or(start, |t| t.text_range().end);
+            trailing(input.slice(name - start..))?;
+            Ok(Line::Header)
+        }
+        SyntaxKind::Org => {
+            let (rest, addr) = finish(ws_start0(org)(input))?;
+            trailing(rest)?;
+            Ok(Line::Org(addr))
+        }
+        SyntaxKind::Variable => {
+            let (rest, var) = finish(ws_start0(Variable::parse)(input))?;
+            trailing(rest)?;
+            Ok(Line::Variable(var))
+        }
+        SyntaxKind::Statement => {
+            let mut labels = Vec::new();
+            let mut instruction = None;
+            for child in node.child_nodes() {
+                match child.kind() {
+                    SyntaxKind::Label => {
+                        let (rest, label) = finish(Label::parse(part(child)))?;
+                        trailing(rest)?;
+                        labels.push(label);
+                    }
+                    SyntaxKind::Instruction => {
+                        let (rest, ins) = finish(spanned(Instruction::parse)(part(child)))?;
+                        trailing(rest)?;
+                        instruction = Some(ins);
+                    }
+                    _ => {}
+                }
+            }
+            Ok(Line::Statement {
+                labels,
+                instruction,
+            })
+        }
+        _ => Err(error(
+            input,
+            "expected a label, an instruction or a variable",
+        )),
+    }
+}
+
+/// Put the data segment together from its lines, the first line is the header
+pub(super) fn data_segment(
+    header: Span,
+    lines: Vec<(Span, Line)>,
+) -> Result<DataSegment, ParseError<'static>> {
+    let mut variables = Vec::new();
+    let mut org: Option<(Span, Byte)> = None;
+    for (input, line) in lines {
+        match line {
+            Line::Blank => {}
+            Line::Org(addr) if org.is_none() => org = Some((input, addr)),
+            Line::Variable(mut var) => {
+                if let Some((_, addr)) = org.take() {
+                    if var.placement.is_some() {
+                        return Err(error(input, "a variable can't be placed twice"));
+                    }
+                    var.placement = Some(Placement::Org(addr));
+                }
+                variables.push(var);
+            }
+            _ => return Err(error(input, "expected a variable")),
+        }
+    }
+    if let Some((input, _)) = org {
+        return Err(error(input, "`.org` has to be followed by a variable"));
+    }
+    if variables.is_empty() {
+        return Err(error(header, "add a variable to the `.data` segment"));
+    }
+
+    let mut data = DataSegment { variables };
+    data.assign_addresses();
+    Ok(data)
+}
+
+/// Put the code segment together from its lines, the first line is the header
+///
+/// Labels point at the next instruction, labels after the last instruction point just past the
+/// end of the code.
+pub(super) fn code_segment(
+    header: Span,
+    lines: Vec<(Span, Line)>,
+) -> Result<CodeSegment, ParseError<'static>> {
+    let mut code = CodeSegment {
+        labels: Vec::new(),
+        instructions: Vec::new(),
+        instruction_spans: Vec::new(),
+    };
+    for (input, line) in lines {
+        match line {
+            Line::Blank => {}
+            Line::Statement {
+                labels,
+                instruction,
+            } => {
+                let code_addr = code.instructions.len();
+                code.labels
+                    .extend(labels.into_iter().map(|label| Label { code_addr, ..label }));
+                if let Some((ins, span)) = instruction {
+                    code.instructions.push(ins);
+                    code.instruction_spans.push(span);
+                }
+            }
+            _ => return Err(error(input, "expected a label or an instruction")),
+        }
+    }
+    if code.instructions.is_empty() {
+        return Err(error(header, "add an instruction to the `.code` segment"));
+    }
+    Ok(code)
+}
+
+fn finish<T>(result: IResult<T>) -> Result<(Span, T), ParseError<'static>> {
+    result.map_err(|err| match err {
+        nom::Err::Failure(e) | nom::Err::Error(e) => e.into_static(),
+        nom::Err::Incomplete(_) => unreachable!(), // we use complete in all parsers
+    })
+}
+
+/// Anything but trivia left on a line is an error
+fn trailing(rest: Span) -> Result<(), ParseError<'static>> {
+    let mut offset = 0;
+    for (kind, text) in lexer::lex(rest.fragment()) {
+        if !kind.is_trivia() {
+            return Err(error(
+                rest.slice(offset..),
+                "expected a label, an instruction or a variable",
+            ));
+        }
+        offset += text.len();
+    }
+    Ok(())
+}
+
+pub(super) fn error(input: Span, help: &'static str) -> ParseError<'static> {
+    ParseError::new(input, nom::error::ErrorKind::Verify)
+        .with_help(help)
+        .into_static()
+}
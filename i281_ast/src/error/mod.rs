@@ -8,7 +8,7 @@ use crate::Span;
 
 #[derive(Clone, Error, Debug, Diagnostic)]
 #[error("Failure to parse input on line: {line_number}")]
-#[diagnostic(code(ast::parse_error), help("double check your syntax"))]
+#[diagnostic(code(ast::parse_error))]
 pub struct ParseError<'b> {
     #[source_code]
     input: Cow<'b, str>,
@@ -16,6 +16,8 @@ pub struct ParseError<'b> {
     #[label("While parsing this")]
     err_span: SourceSpan,
     kind: ErrorKind,
+    #[help]
+    help: Cow<'static, str>,
 }
 
 impl<'a> ParseError<'a> {
@@ -23,6 +25,12 @@ impl<'a> ParseError<'a> {
         <Self as nom::error::ParseError<Span>>::from_error_kind(input, kind)
     }
 
+    /// Replace the generic help with one that explains what went wrong
+    pub fn with_help(mut self, help: impl Into<Cow<'static, str>>) -> Self {
+        self.help = help.into();
+        self
+    }
+
     pub fn into_static(self) -> ParseError<'static> {
         let input = Cow::Owned(self.input.into_owned());
         let line_number = self.line_number;
@@ -33,6 +41,7 @@ impl<'a> ParseError<'a> {
             line_number,
             err_span,
             kind,
+            help: self.help,
         }
     }
 }
@@ -49,6 +58,7 @@ impl<'a> nom::error::ParseError<Span<'a>> for ParseError<'a> {
             line_number,
             err_span: SourceSpan::new(start.into(), end.into()),
             kind,
+            help: Cow::Borrowed("double check your syntax"),
         }
     }
 
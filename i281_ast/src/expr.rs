@@ -0,0 +1,232 @@
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    combinator::{map, verify},
+    sequence::{delimited, pair, preceded},
+};
+
+use crate::{
+    label,
+    literal::Byte,
+    util::{ws0, ws_end0, ws_start0},
+    IResult, Ident, Oper, ParseNom, Register, Span,
+};
+
+/// A constant expression, used for addresses, immediates and data
+///
+/// Identifiers refer to the address of a variable or a label, registers are only allowed in the
+/// addresses of instructions that take an offset. The parser never produces parentheses, the
+/// grouping is kept in the shape of the tree and they are added back when printing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "kind", content = "value", rename_all = "snake_case")
+)]
+pub enum Expr {
+    Lit(Byte),
+    Ident(Ident),
+    Reg(Register),
+    Neg(Box<Expr>),
+    Binary {
+        left: Box<Expr>,
+        oper: Oper,
+        right: Box<Expr>,
+    },
+}
+
+/// Operators with the lowest and highest precedence, see [`Oper::precedence`]
+const LOWEST: u8 = 1;
+const HIGHEST: u8 = 5;
+
+impl Expr {
+    /// Returns true if the expression can be evaluated at compile time
+    pub fn is_const(&self) -> bool {
+        self.registers().next().is_none()
+    }
+
+    /// Get an iterator over all identifiers within the expression in the order they are written
+    pub fn idents(&self) -> impl Iterator<Item = &Ident> {
+        self.leaves().into_iter().filter_map(|leaf| match leaf {
+            Self::Ident(ident) => Some(ident),
+            _ => None,
+        })
+    }
+
+    /// Get an iterator over all registers in the expression
+    ///
+    /// The parser will parse any number of registers however usually only one is allowed
+    pub fn registers(&self) -> impl Iterator<Item = Register> + '_ {
+        self.leaves().into_iter().filter_map(|leaf| match leaf {
+            Self::Reg(reg) => Some(*reg),
+            _ => None,
+        })
+    }
+
+    fn leaves(&self) -> Vec<&Self> {
+        match self {
+            Self::Lit(..) | Self::Ident(..) | Self::Reg(..) => vec![self],
+            Self::Neg(expr) => expr.leaves(),
+            Self::Binary { left, right, .. } => {
+                let mut leaves = left.leaves();
+                leaves.extend(right.leaves());
+                leaves
+            }
+        }
+    }
+
+    /// The precedence of the outermost operator, everything except a binary expression is atomic
+    fn precedence(&self) -> u8 {
+        match self {
+            Self::Binary { oper, .. } => oper.precedence(),
+            _ => u8::MAX,
+        }
+    }
+}
+
+/// Parse a chain of operators with at least the given precedence, they associate to the left
+fn binary(precedence: u8, input: Span) -> IResult<Expr> {
+    if precedence > HIGHEST {
+        return unary(input);
+    }
+
+    let (mut input, mut left) = binary(precedence + 1, input)?;
+    loop {
+        let oper = verify(ws0(Oper::parse), |oper: &Oper| {
+            oper.precedence() == precedence
+        });
+        match pair(oper, |input| binary(precedence + 1, input))(input) {
+            Ok((rest, (oper, right))) => {
+                input = rest;
+                left = Expr::Binary {
+                    left: Box::new(left),
+                    oper,
+                    right: Box::new(right),
+                };
+            }
+            Err(nom::Err::Error(_)) => return Ok((input, left)),
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+fn unary(input: Span) -> IResult<Expr> {
+    alt((
+        // a literal is tried first so `-128` is a single byte and not the negation of `128`
+        map(Byte::parse, Expr::Lit),
+        map(preceded(ws_end0(tag("-")), unary), |expr| {
+            Expr::Neg(Box::new(expr))
+        }),
+        delimited(ws_end0(tag("(")), Expr::parse, ws_start0(tag(")"))),
+        map(Register::parse, Expr::Reg),
+        map(label::symbol, Expr::Ident),
+    ))(input)
+}
+
+impl ParseNom for Expr {
+    fn parse(input: Span) -> IResult<Self> {
+        binary(LOWEST, input)
+    }
+}
+
+impl std::fmt::Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Lit(lit) => lit.fmt(f),
+            Self::Ident(ident) => ident.fmt(f),
+            Self::Reg(reg) => reg.fmt(f),
+            // `-(5)` keeps the negation from turning into the literal `-5`
+            Self::Neg(expr) => match **expr {
+                Self::Ident(..) | Self::Reg(..) => write!(f, "-{expr}"),
+                _ => write!(f, "-({expr})"),
+            },
+            Self::Binary { left, oper, right } => {
+                let precedence = oper.precedence();
+                match left.precedence() < precedence {
+                    true => write!(f, "({left})")?,
+                    false => write!(f, "{left}")?,
+                }
+                write!(f, "{oper}")?;
+                match right.precedence() <= precedence {
+                    true => write!(f, "({right})"),
+                    false => write!(f, "{right}"),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use proptest::prelude::*;
+
+    use super::Expr;
+    use crate::{arbitrary::reparse, literal::Byte, Oper, Register};
+
+    fn lit(value: i16) -> Box<Expr> {
+        Box::new(Expr::Lit(Byte(value)))
+    }
+
+    #[test]
+    fn expr1() {
+        let expected = Expr::Binary {
+            left: lit(1),
+            oper: Oper::Add,
+            right: Box::new(Expr::Binary {
+                left: lit(2),
+                oper: Oper::Mul,
+                right: lit(3),
+            }),
+        };
+        assert_eq!(reparse::<Expr>("1 + 2 * 3"), expected);
+    }
+
+    #[test]
+    fn expr2() {
+        let expected = Expr::Binary {
+            left: Box::new(Expr::Binary {
+                left: lit(10),
+                oper: Oper::Sub,
+                right: lit(2),
+            }),
+            oper: Oper::Sub,
+            right: lit(-3),
+        };
+        assert_eq!(reparse::<Expr>("10-2--3"), expected);
+    }
+
+    #[test]
+    fn expr3() {
+        let expected = Expr::Binary {
+            left: Box::new(Expr::Neg(Box::new(Expr::Binary {
+                left: Box::new(Expr::Ident("x".into())),
+                oper: Oper::Or,
+                right: Box::new(Expr::Reg(Register::B)),
+            }))),
+            oper: Oper::Shl,
+            right: lit(2),
+        };
+        let expr = reparse::<Expr>("-( x | B ) << 2");
+        assert_eq!(expr, expected);
+        assert_eq!(expr.to_string(), "-(x|B)<<2");
+        assert_eq!(expr.registers().collect::<Vec<_>>(), [Register::B]);
+        assert!(!expr.is_const());
+    }
+
+    #[test]
+    fn expr4() {
+        assert_eq!(
+            reparse::<Expr>("(1+2)*(3-(4-5))").to_string(),
+            "(1+2)*(3-(4-5))"
+        );
+        assert_eq!(reparse::<Expr>("((1*2))+3").to_string(), "1*2+3");
+        assert_eq!(reparse::<Expr>("-(5)"), Expr::Neg(lit(5)));
+    }
+
+    proptest! {
+        #[test]
+        fn expr_roundtrip(expr in any::<Expr>()) {
+            prop_assert_eq!(reparse::<Expr>(&expr.to_string()), expr);
+        }
+    }
+}
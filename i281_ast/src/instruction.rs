@@ -1,9 +1,15 @@
-use crate::util::{always_fails, ws0, ws_end1};
-use crate::{opcode, ParseNom, Span};
+use crate::util::{always_fails, eof, ws0};
+use crate::{label, opcode, ParseNom, Span};
 
-use nom::{bytes::complete::tag, sequence::separated_pair};
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::multispace1,
+    combinator::value,
+    sequence::{separated_pair, terminated},
+};
 
-use crate::{literal, Address, IResult, Ident, OpCode, Pointer, Register};
+use crate::{Address, Expr, IResult, Ident, OpCode, Pointer, Register};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
@@ -18,12 +24,12 @@ pub enum Instruction {
     InputD(Address),
     InputDF(Address),
     Move(Register, Register),
-    LoadI(Register, literal::Byte),
+    LoadI(Register, Expr),
     LoadP(Register, Pointer),
     Add(Register, Register),
-    AddI(Register, literal::Byte),
+    AddI(Register, Expr),
     Sub(Register, Register),
-    SubI(Register, literal::Byte),
+    SubI(Register, Expr),
     Load(Register, Address),
     LoadF(Register, Address),
     Store(Address, Register),
@@ -39,6 +45,74 @@ pub enum Instruction {
 }
 
 impl Instruction {
+    /// The opcode the instruction is written with, aliases like `BRZ` are never returned
+    pub fn opcode(&self) -> OpCode {
+        match self {
+            Self::NoOp => opcode::NoOp.into(),
+            Self::InputC(..) => opcode::InputC.into(),
+            Self::InputCF(..) => opcode::InputCF.into(),
+            Self::InputD(..) => opcode::InputD.into(),
+            Self::InputDF(..) => opcode::InputDF.into(),
+            Self::Move(..) => opcode::Move.into(),
+            Self::LoadI(..) => opcode::LoadI.into(),
+            Self::LoadP(..) => opcode::LoadP.into(),
+            Self::Add(..) => opcode::Add.into(),
+            Self::AddI(..) => opcode::AddI.into(),
+            Self::Sub(..) => opcode::Sub.into(),
+            Self::SubI(..) => opcode::SubI.into(),
+            Self::Load(..) => opcode::Load.into(),
+            Self::LoadF(..) => opcode::LoadF.into(),
+            Self::Store(..) => opcode::Store.into(),
+            Self::StoreF(..) => opcode::StoreF.into(),
+            Self::ShiftL(..) => opcode::ShiftL.into(),
+            Self::ShiftR(..) => opcode::ShiftR.into(),
+            Self::Cmp(..) => opcode::Cmp.into(),
+            Self::Jump(..) => opcode::Jump.into(),
+            Self::BrE(..) => opcode::BrE.into(),
+            Self::BrNE(..) => opcode::BrNE.into(),
+            Self::BrG(..) => opcode::BrG.into(),
+            Self::BrGE(..) => opcode::BrGE.into(),
+        }
+    }
+
+    /// Get every register the instruction uses, including registers within its address
+    pub fn registers(&self) -> Vec<Register> {
+        match self {
+            Self::InputC(addr) | Self::InputCF(addr) | Self::InputD(addr) | Self::InputDF(addr) => {
+                addr.to.registers().collect()
+            }
+            Self::Move(rx, ry) | Self::Add(rx, ry) | Self::Sub(rx, ry) | Self::Cmp(rx, ry) => {
+                vec![*rx, *ry]
+            }
+            Self::LoadI(rx, value) | Self::AddI(rx, value) | Self::SubI(rx, value) => {
+                std::iter::once(*rx).chain(value.registers()).collect()
+            }
+            Self::LoadP(rx, _) | Self::ShiftL(rx) | Self::ShiftR(rx) => vec![*rx],
+            Self::Load(rx, addr)
+            | Self::LoadF(rx, addr)
+            | Self::Store(addr, rx)
+            | Self::StoreF(addr, rx) => std::iter::once(*rx).chain(addr.to.registers()).collect(),
+            Self::NoOp
+            | Self::Jump(..)
+            | Self::BrE(..)
+            | Self::BrNE(..)
+            | Self::BrG(..)
+            | Self::BrGE(..) => Vec::new(),
+        }
+    }
+
+    /// The label the instruction jumps to if it is a jump or branch
+    pub fn target(&self) -> Option<&Ident> {
+        match self {
+            Self::Jump(label)
+            | Self::BrE(label)
+            | Self::BrNE(label)
+            | Self::BrG(label)
+            | Self::BrGE(label) => Some(label),
+            _ => None,
+        }
+    }
+
     pub(crate) fn parse_after_opcode(opcode: OpCode, input: Span) -> IResult<Self> {
         match opcode {
             OpCode::NoOp(..) => Ok((input, Self::NoOp)),
@@ -65,7 +139,7 @@ impl Instruction {
             }
             OpCode::LoadI(..) => {
                 let (input, (rx, value)) =
-                    separated_pair(Register::parse, ws0(tag(",")), literal::Byte::parse)(input)?;
+                    separated_pair(Register::parse, ws0(tag(",")), Expr::parse)(input)?;
                 Ok((input, Self::LoadI(rx, value)))
             }
             OpCode::LoadP(..) => {
@@ -80,7 +154,7 @@ impl Instruction {
             }
             OpCode::AddI(..) => {
                 let (input, (rx, value)) =
-                    separated_pair(Register::parse, ws0(tag(",")), literal::Byte::parse)(input)?;
+                    separated_pair(Register::parse, ws0(tag(",")), Expr::parse)(input)?;
                 Ok((input, Self::AddI(rx, value)))
             }
             OpCode::Sub(..) => {
@@ -90,7 +164,7 @@ impl Instruction {
             }
             OpCode::SubI(..) => {
                 let (input, (rx, value)) =
-                    separated_pair(Register::parse, ws0(tag(",")), literal::Byte::parse)(input)?;
+                    separated_pair(Register::parse, ws0(tag(",")), Expr::parse)(input)?;
                 Ok((input, Self::SubI(rx, value)))
             }
             OpCode::Load(..) => {
@@ -127,23 +201,23 @@ impl Instruction {
                 Ok((input, Self::Cmp(rx, ry)))
             }
             OpCode::Jump(..) => {
-                let (input, label) = Ident::parse(input)?;
+                let (input, label) = label::target(input)?;
                 Ok((input, Self::Jump(label)))
             }
             OpCode::BrE(..) | OpCode::BrZ(..) => {
-                let (input, label) = Ident::parse(input)?;
+                let (input, label) = label::target(input)?;
                 Ok((input, Self::BrE(label)))
             }
             OpCode::BrNE(..) | OpCode::BrNZ(..) => {
-                let (input, label) = Ident::parse(input)?;
+                let (input, label) = label::target(input)?;
                 Ok((input, Self::BrNE(label)))
             }
             OpCode::BrG(..) => {
-                let (input, label) = Ident::parse(input)?;
+                let (input, label) = label::target(input)?;
                 Ok((input, Self::BrG(label)))
             }
             OpCode::BrGE(..) => {
-                let (input, label) = Ident::parse(input)?;
+                let (input, label) = label::target(input)?;
                 Ok((input, Self::BrGE(label)))
             }
         }
@@ -152,9 +226,11 @@ impl Instruction {
 
 impl ParseNom for Instruction {
     fn parse(input: Span) -> IResult<Self> {
-        let (input, opcode) = ws_end1(OpCode::parse)(input)?;
-        let (input, ins) = always_fails(move |input| Self::parse_after_opcode(opcode.clone(), input))(input)?;
-        return Ok((input, ins));
+        // the opcode is followed by whitespace unless it is a `NOOP` at the very end of the input
+        let (input, opcode) = terminated(OpCode::parse, alt((value((), multispace1), eof)))(input)?;
+        let (input, ins) =
+            always_fails(move |input| Self::parse_after_opcode(opcode.clone(), input))(input)?;
+        Ok((input, ins))
     }
 }
 
@@ -189,4 +265,28 @@ impl std::fmt::Display for Instruction {
     }
 }
 
-// TODO implement tests for all instructions
+#[cfg(test)]
+mod test {
+    use proptest::prelude::*;
+
+    use super::Instruction;
+    use crate::{arbitrary::reparse, Register};
+
+    #[test]
+    fn instruction1() {
+        let ins = reparse::<Instruction>("STOREF [x+C], A");
+        assert_eq!(ins.opcode().to_string(), "STOREF");
+        assert_eq!(ins.registers(), [Register::A, Register::C]);
+        assert_eq!(
+            reparse::<Instruction>("BRZ end").opcode().to_string(),
+            "BRE"
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn instruction_roundtrip(ins in any::<Instruction>()) {
+            prop_assert_eq!(reparse::<Instruction>(&ins.to_string()), ins);
+        }
+    }
+}
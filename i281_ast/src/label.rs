@@ -1,25 +1,210 @@
-use nom::bytes::complete::tag;
+use miette::SourceSpan;
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::{alphanumeric1, digit1, one_of},
+    combinator::{map, not, recognize},
+    sequence::{pair, terminated, tuple},
+};
 
-use crate::ParseNom;
+use crate::{util::spanned, ParseNom, Span};
 
 use super::Ident;
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 pub struct Label {
     pub ident: Ident,
     pub code_addr: usize,
+    /// Where the label was defined in the source, this is `None` if it was not parsed
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub span: Option<SourceSpan>,
 }
 
+/// How a label is named, which decides how jumps can refer to it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LabelKind {
+    /// `name:`, referred to by `name`
+    Global,
+    /// `.name:`, scoped to the previous global label and referred to by `.name` inside that
+    /// scope or `global.name` from anywhere
+    Local,
+    /// `1:`, may be defined many times and is referred to by `1b` or `1f` for the closest
+    /// definition before or after the jump
+    Numeric,
+}
+
+impl LabelKind {
+    pub fn of(ident: &Ident) -> Self {
+        let name = ident.as_str();
+        if name.starts_with('.') {
+            Self::Local
+        } else if !name.is_empty() && name.bytes().all(|b| b.is_ascii_digit()) {
+            Self::Numeric
+        } else {
+            Self::Global
+        }
+    }
+}
+
+impl Label {
+    pub fn kind(&self) -> LabelKind {
+        LabelKind::of(&self.ident)
+    }
+
+    /// Returns true if the name of the label could have been produced by the parser
+    pub fn has_valid_name(&self) -> bool {
+        match self.kind() {
+            LabelKind::Global => self.ident.is_valid(),
+            LabelKind::Local => Ident::from(&self.ident.as_str()[1..]).is_valid(),
+            LabelKind::Numeric => true,
+        }
+    }
+
+    /// The name of every label with local labels qualified by their scope, e.g. `sort.inner`
+    ///
+    /// Local labels defined before any global label have an empty scope and keep their name.
+    pub fn qualified_names(labels: &[Label]) -> Vec<Ident> {
+        let mut scope = "";
+        labels
+            .iter()
+            .map(|label| match label.kind() {
+                LabelKind::Global => {
+                    scope = label.ident.as_str();
+                    label.ident.clone()
+                }
+                LabelKind::Local => format!("{scope}{}", label.ident).into(),
+                LabelKind::Numeric => label.ident.clone(),
+            })
+            .collect()
+    }
+
+    /// Find the label a jump in the instruction at `from` refers to with `target`
+    ///
+    /// When a name is defined more than once the first definition wins, except for numeric
+    /// labels where the closest one in the direction of the suffix is used.
+    pub fn resolve<'a>(labels: &'a [Label], from: usize, target: &Ident) -> Option<&'a Label> {
+        let name = target.as_str();
+        if let Some(num) = name.strip_suffix('b').filter(|n| is_numeric(n)) {
+            return labels
+                .iter()
+                .rev()
+                .find(|l| l.ident.as_str() == num && l.code_addr <= from);
+        }
+        if let Some(num) = name.strip_suffix('f').filter(|n| is_numeric(n)) {
+            return labels
+                .iter()
+                .find(|l| l.ident.as_str() == num && l.code_addr > from);
+        }
+        if !name.contains('.') {
+            return labels
+                .iter()
+                .find(|l| l.kind() == LabelKind::Global && &l.ident == target);
+        }
+        let qualified = match name.starts_with('.') {
+            true => format!("{}{name}", Self::scope(labels, from)),
+            false => name.to_owned(),
+        };
+        labels
+            .iter()
+            .zip(Self::qualified_names(labels))
+            .find(|(l, q)| l.kind() == LabelKind::Local && q.as_str() == qualified)
+            .map(|(l, _)| l)
+    }
+
+    /// Every name a jump in the instruction at `from` can use to refer to a label
+    pub fn references(labels: &[Label], from: usize) -> Vec<Ident> {
+        let scope = Self::scope(labels, from);
+        let mut names: Vec<Ident> = Vec::new();
+        for (label, qualified) in labels.iter().zip(Self::qualified_names(labels)) {
+            let name = match label.kind() {
+                LabelKind::Global => label.ident.clone(),
+                LabelKind::Local => {
+                    if qualified.as_str() == format!("{scope}{}", label.ident) {
+                        names.push(label.ident.clone());
+                    }
+                    qualified
+                }
+                LabelKind::Numeric if label.code_addr <= from => format!("{}b", label.ident).into(),
+                LabelKind::Numeric => format!("{}f", label.ident).into(),
+            };
+            names.push(name);
+        }
+        let mut seen = std::collections::HashSet::new();
+        names.retain(|name| seen.insert(name.clone()));
+        names
+    }
+
+    /// The last global label at or before the instruction at `from`
+    fn scope(labels: &[Label], from: usize) -> &str {
+        labels
+            .iter()
+            .rev()
+            .find(|l| l.kind() == LabelKind::Global && l.code_addr <= from)
+            .map_or("", |l| l.ident.as_str())
+    }
+}
+
+fn is_numeric(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// The name of a label where it is defined: `name`, `.name` or `1`
+fn name(input: Span) -> crate::IResult<Ident> {
+    map(
+        alt((
+            recognize(pair(tag("."), Ident::parse)),
+            digit1,
+            recognize(Ident::parse),
+        )),
+        |s: Span| Ident::from(*s.fragment()),
+    )(input)
+}
+
+/// The label a jump or branch goes to: `name`, `.name`, `global.name`, `1b` or `1f`
+pub(crate) fn target(input: Span) -> crate::IResult<Ident> {
+    alt((
+        map(
+            recognize(terminated(
+                pair(digit1, one_of("bf")),
+                not(alt((alphanumeric1, tag("_")))),
+            )),
+            |s: Span| Ident::from(*s.fragment()),
+        ),
+        symbol,
+    ))(input)
+}
+
+/// A name in an expression, a variable or a label: `name`, `.name` or `global.name`
+pub(crate) fn symbol(input: Span) -> crate::IResult<Ident> {
+    map(
+        alt((
+            recognize(pair(tag("."), Ident::parse)),
+            recognize(tuple((Ident::parse, tag("."), Ident::parse))),
+            recognize(Ident::parse),
+        )),
+        |s: Span| Ident::from(*s.fragment()),
+    )(input)
+}
+
+// the span is ignored as it only describes where the label came from
+impl PartialEq for Label {
+    fn eq(&self, other: &Self) -> bool {
+        self.ident == other.ident && self.code_addr == other.code_addr
+    }
+}
+impl Eq for Label {}
+
 impl ParseNom for Label {
     fn parse(input: crate::Span) -> crate::IResult<Self> {
-        let (input, ident) = Ident::parse(input)?;
+        let (input, (ident, span)) = spanned(name)(input)?;
         let (input, _) = tag(":")(input)?;
         Ok((
             input,
             Self {
                 ident,
                 code_addr: 0,
+                span: Some(span),
             },
         ))
     }
@@ -33,14 +218,17 @@ impl std::fmt::Display for Label {
 
 #[cfg(test)]
 mod test {
-    use super::Label;
-    use crate::Parse;
+    use proptest::prelude::*;
+
+    use super::{Label, LabelKind};
+    use crate::{arbitrary::reparse, Ident, Parse};
 
     #[test]
     fn label1() {
         let expected = Label {
             ident: "lab".into(),
             code_addr: 0,
+            span: None,
         };
         assert_eq!(Label::parse("lab:").unwrap().1, expected);
     }
@@ -50,4 +238,55 @@ mod test {
     fn label2() {
         Label::parse("lab").unwrap();
     }
+
+    #[test]
+    fn label3() {
+        let label = |name: &str, code_addr| Label {
+            ident: name.into(),
+            code_addr,
+            span: None,
+        };
+        assert_eq!(Label::parse(".inner:").unwrap().1.kind(), LabelKind::Local);
+        assert_eq!(Label::parse("1:").unwrap().1.kind(), LabelKind::Numeric);
+        let labels = [
+            label("1", 0),
+            label("sort", 1),
+            label(".loop", 2),
+            label("1", 3),
+            label("fill", 4),
+            label(".loop", 5),
+        ];
+        let resolve = |from, target: &str| {
+            Label::resolve(&labels, from, &Ident::from(target)).map(|l| l.code_addr)
+        };
+        assert_eq!(resolve(3, "1b"), Some(3));
+        assert_eq!(resolve(2, "1b"), Some(0));
+        assert_eq!(resolve(2, "1f"), Some(3));
+        assert_eq!(resolve(3, "1f"), None);
+        assert_eq!(resolve(3, ".loop"), Some(2));
+        assert_eq!(resolve(6, ".loop"), Some(5));
+        assert_eq!(resolve(6, "sort.loop"), Some(2));
+        assert_eq!(resolve(0, ".loop"), None);
+        assert_eq!(resolve(0, "fill"), Some(4));
+        assert_eq!(
+            Label::references(&labels, 2),
+            [
+                "1b",
+                "sort",
+                ".loop",
+                "sort.loop",
+                "1f",
+                "fill",
+                "fill.loop"
+            ]
+            .map(Ident::from)
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn label_roundtrip(label in any::<Label>()) {
+            prop_assert_eq!(reparse::<Label>(&label.to_string()), label);
+        }
+    }
 }
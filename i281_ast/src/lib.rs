@@ -4,6 +4,8 @@
 mod primitive;
 
 mod address;
+pub mod cst;
+mod expr;
 mod instruction;
 mod label;
 mod pointer;
@@ -14,16 +16,23 @@ mod variable;
 mod error;
 mod util;
 
+#[cfg(test)]
+mod arbitrary;
+
 pub(crate) use util::type_enum;
 
-pub use address::{Address, AddressExpr, AddressItem};
+pub use address::Address;
+pub use cst::SyntaxTree;
+pub use expr::Expr;
 pub use instruction::Instruction;
-pub use label::Label;
+pub use label::{Label, LabelKind};
 pub use pointer::Pointer;
-pub use primitive::{keyword, literal, opcode, Ident, Literal, OpCode, Oper, Register};
+pub use primitive::{
+    keyword, literal, opcode, Ident, Literal, OpCode, Oper, Register, ReservedWord,
+};
 pub use root::Root;
 pub use segment::{CodeSegment, DataSegment};
-pub use variable::Variable;
+pub use variable::{Placement, Variable};
 
 pub use error::ParseError;
 // The custom error span this will contain the input and extra will be the full input
@@ -40,7 +49,7 @@ pub(crate) trait ParseNom: Sized {
 
 // this is a public facing trait that is automatically implemented for all ParseNom implementors
 pub trait Parse {
-    fn parse(input: &str) -> IResult<Self>
+    fn parse(input: &str) -> IResult<'_, Self>
     where
         Self: Sized;
 }
@@ -49,7 +58,7 @@ impl<T> Parse for T
 where
     T: ParseNom,
 {
-    fn parse(input: &str) -> IResult<Self> {
+    fn parse(input: &str) -> IResult<'_, Self> {
         <T as ParseNom>::parse(Span::new_extra(input, input))
     }
 }
@@ -35,3 +35,17 @@ impl std::fmt::Display for Pointer {
 }
 
 // TODO: implement tests for pointers if they are going to stay in the assembly language
+#[cfg(test)]
+mod test {
+    use proptest::prelude::*;
+
+    use super::Pointer;
+    use crate::arbitrary::reparse;
+
+    proptest! {
+        #[test]
+        fn pointer_roundtrip(pointer in any::<Pointer>()) {
+            prop_assert_eq!(reparse::<Pointer>(&pointer.to_string()), pointer);
+        }
+    }
+}
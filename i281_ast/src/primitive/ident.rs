@@ -7,7 +7,32 @@ use nom::{
     sequence::pair,
 };
 
-use crate::ParseNom;
+use crate::{keyword::Keyword, OpCode, ParseNom, Register};
+
+/// A word of the language that an identifier can be mistaken for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReservedWord {
+    Register(&'static str),
+    OpCode(&'static str),
+    Keyword(&'static str),
+}
+
+impl ReservedWord {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Register(s) | Self::OpCode(s) | Self::Keyword(s) => s,
+        }
+    }
+
+    /// What kind of word it is, e.g. `"register"`
+    pub fn kind(self) -> &'static str {
+        match self {
+            Self::Register(..) => "register",
+            Self::OpCode(..) => "opcode",
+            Self::Keyword(..) => "keyword",
+        }
+    }
+}
 
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
@@ -22,6 +47,28 @@ impl Ident {
     pub fn as_str(&self) -> &str {
         self.0.as_str()
     }
+
+    /// Returns true if the identifier could have been produced by the parser
+    ///
+    /// Identifiers constructed by hand or deserialized are not checked so this can be used to
+    /// validate them.
+    pub fn is_valid(&self) -> bool {
+        matches!(<Self as crate::Parse>::parse(self.as_str()), Ok((rest, _)) if rest.is_empty())
+    }
+
+    /// The register, opcode or keyword this identifier is spelled the same as ignoring case
+    pub fn reserved_word(&self) -> Option<ReservedWord> {
+        let find = |words: &'static [&'static str]| {
+            words
+                .iter()
+                .copied()
+                .find(|word| word.eq_ignore_ascii_case(self.as_str()))
+        };
+        find(Register::ALL)
+            .map(ReservedWord::Register)
+            .or_else(|| find(OpCode::ALL).map(ReservedWord::OpCode))
+            .or_else(|| find(Keyword::ALL).map(ReservedWord::Keyword))
+    }
 }
 
 impl From<String> for Ident {
@@ -60,8 +107,10 @@ impl std::fmt::Display for Ident {
 
 #[cfg(test)]
 mod test {
-    use super::Ident;
-    use crate::Parse;
+    use proptest::prelude::*;
+
+    use super::{Ident, ReservedWord};
+    use crate::{arbitrary::reparse, Parse};
 
     #[test]
     fn ident1() {
@@ -84,4 +133,28 @@ mod test {
     fn ident4() {
         Ident::parse("ab   ").unwrap();
     }
+
+    #[test]
+    fn ident5() {
+        assert!(Ident::from("_ab1").is_valid());
+        assert!(!Ident::from("1ab").is_valid());
+        assert!(!Ident::from("a b").is_valid());
+        assert!(!Ident::from("").is_valid());
+    }
+
+    #[test]
+    fn ident6() {
+        let reserved = |s: &str| Ident::from(s).reserved_word();
+        assert_eq!(reserved("a"), Some(ReservedWord::Register("A")));
+        assert_eq!(reserved("LoadI"), Some(ReservedWord::OpCode("LOADI")));
+        assert_eq!(reserved("data"), Some(ReservedWord::Keyword("data")));
+        assert_eq!(reserved("loadix"), None);
+    }
+
+    proptest! {
+        #[test]
+        fn ident_roundtrip(ident in any::<Ident>()) {
+            prop_assert_eq!(reparse::<Ident>(ident.as_ref()), ident);
+        }
+    }
 }
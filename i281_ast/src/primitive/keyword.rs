@@ -47,6 +47,8 @@ keyword! {
     Byte == "BYTE",
     Code == "code",
     Data == "data",
+    Dup == "DUP",
+    Org == "org",
 }
 
 #[cfg(test)]
@@ -1,89 +1,212 @@
 use nom::{
     branch::alt,
     bytes::complete::tag,
-    character::complete::{digit1, hex_digit1, one_of},
-    combinator::{map, opt, recognize},
+    character::complete::{digit1, one_of},
+    combinator::{map, opt, recognize, verify},
     multi::{many1, separated_list1},
-    sequence::{pair, preceded, separated_pair},
+    sequence::{delimited, pair, preceded, separated_pair, tuple},
 };
 
-use crate::{type_enum, util::ws0, IResult, ParseError, ParseNom, Span};
+use crate::{
+    keyword, type_enum,
+    util::{ws0, ws_end0, ws_end1},
+    IResult, ParseError, ParseNom, Span,
+};
 
 type_enum!(Literal {
-    Byte(i8),
+    Byte(i16),
     Array(Vec<Literal>),
     NotSet,
+    Expr(crate::Expr),
+    Dup(Repetition),
 });
 
+impl Byte {
+    /// The byte stored in memory, values above 127 are stored in two's complement
+    pub fn as_i8(&self) -> i8 {
+        self.0 as i8
+    }
+
+    /// Returns true if the value fits in a byte, either signed or unsigned
+    pub fn in_range(&self) -> bool {
+        (i8::MIN as i16..=u8::MAX as i16).contains(&self.0)
+    }
+}
+
+/// `count DUP(values)`, the values repeated `count` times
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct Repetition {
+    pub count: usize,
+    /// The parser never puts an array in here, a list of values is stored directly
+    pub values: Vec<Literal>,
+}
+
 impl Literal {
     /// Get the size of the literal in bytes
     pub fn size_of(&self) -> usize {
         match self {
-            Self::Byte(..) | Self::NotSet(..) => 1,
-            Self::Array(arr) => arr.0.len(),
+            Self::Byte(..) | Self::NotSet(..) | Self::Expr(..) => 1,
+            Self::Array(arr) => arr.0.iter().map(Self::size_of).sum(),
+            Self::Dup(Dup(rep)) => rep
+                .count
+                .saturating_mul(rep.values.iter().map(Self::size_of).sum()),
         }
     }
-}
 
-fn parse_int_err<'a>(
-    input: Span<'a>,
-) -> impl FnOnce(std::num::ParseIntError) -> nom::Err<ParseError<'a>> {
-    move |_| nom::Err::Failure(ParseError::new(input, nom::error::ErrorKind::TooLarge))
+    /// Returns true if the literal is an array that contains another array
+    ///
+    /// The parser never produces these but they can be constructed or deserialized
+    pub fn is_nested(&self) -> bool {
+        let values = match self {
+            Self::Array(arr) => &arr.0,
+            Self::Dup(Dup(rep)) => &rep.values,
+            _ => return false,
+        };
+        values
+            .iter()
+            .any(|v| matches!(v, Self::Array(..)) || v.is_nested())
+    }
+
+    /// Get every value in the order it is placed in memory with repetitions written out
+    ///
+    /// Only bytes, expressions and unset values are returned, arrays inside of arrays are
+    /// flattened.
+    pub fn elements(&self) -> Vec<&Self> {
+        match self {
+            Self::Byte(..) | Self::NotSet(..) | Self::Expr(..) => vec![self],
+            Self::Array(arr) => arr.0.iter().flat_map(Self::elements).collect(),
+            Self::Dup(Dup(rep)) => {
+                let once: Vec<_> = rep.values.iter().flat_map(Self::elements).collect();
+                once.repeat(rep.count)
+            }
+        }
+    }
 }
 
-fn binary(input: Span) -> IResult<i8> {
-    let inp = input.clone();
+/// Any byte can be written without a suffix, values above 127 are stored in two's complement
+const BYTE_RANGE: std::ops::RangeInclusive<i64> = -128..=255;
 
-    let (input, num) =
-        preceded(alt((tag("0b"), tag("0B"))), recognize(many1(one_of("01"))))(input)?;
-    let num: i8 = i8::from_str_radix(&num, 2).map_err(parse_int_err(inp))?;
-    Ok((input, num))
+/// Digits with `_` separators, at least one digit is required
+fn digits<'a>(allowed: &'static str) -> impl FnMut(Span<'a>) -> IResult<'a, Span<'a>> {
+    verify(recognize(many1(one_of(allowed))), |digits: &Span| {
+        digits.chars().any(|c| c != '_')
+    })
 }
 
-fn hexadecimal(input: Span) -> IResult<i8> {
-    let inp = input.clone();
+/// A binary, hexadecimal or decimal number with an optional `u8` or `i8` suffix
+fn number(input: Span) -> IResult<i16> {
+    let start = input;
+    let (input, (sign, (radix, digits), suffix)) = tuple((
+        opt(one_of("+-")),
+        alt((
+            map(preceded(alt((tag("0b"), tag("0B"))), digits("01_")), |d| {
+                (2, d)
+            }),
+            map(
+                preceded(
+                    alt((tag("0x"), tag("0X"))),
+                    digits("0123456789abcdefABCDEF_"),
+                ),
+                |d| (16, d),
+            ),
+            map(recognize(pair(digit1, opt(digits("0123456789_")))), |d| {
+                (10, d)
+            }),
+        )),
+        opt(alt((tag("u8"), tag("i8")))),
+    ))(input)?;
 
-    let (input, num) = preceded(alt((tag("0x"), tag("0X"))), hex_digit1)(input)?;
-    let num: i8 = i8::from_str_radix(&num, 16).map_err(parse_int_err(inp))?;
-    Ok((input, num))
+    let written = &start.fragment()[..input.location_offset() - start.location_offset()];
+    let digits: String = digits.chars().filter(|&c| c != '_').collect();
+    let value = i64::from_str_radix(&digits, radix).ok().map(|value| {
+        if sign == Some('-') {
+            -value
+        } else {
+            value
+        }
+    });
+    let range = match suffix.map(|s| *s.fragment()) {
+        Some("u8") => 0..=u8::MAX as i64,
+        Some(_) => i8::MIN as i64..=i8::MAX as i64,
+        None => BYTE_RANGE,
+    };
+    match value.filter(|value| range.contains(value)) {
+        Some(value) => Ok((input, value as i16)),
+        None => {
+            let err = ParseError::new(start, nom::error::ErrorKind::TooLarge);
+            Err(nom::Err::Failure(
+                err.with_help(out_of_range(written, value)),
+            ))
+        }
+    }
 }
 
-fn decimal(input: Span) -> IResult<i8> {
-    let inp = input.clone();
+/// Explain why a number does not fit, a byte that only has the wrong suffix is shown both ways
+fn out_of_range(written: &str, value: Option<i64>) -> String {
+    match value.filter(|value| BYTE_RANGE.contains(value)) {
+        Some(value) => {
+            let byte = value as u8;
+            format!(
+                "`{written}` is out of range for its suffix, the byte {byte:#04x} is `{byte}u8` and `{}i8`",
+                byte as i8
+            )
+        }
+        None => format!(
+            "`{written}` does not fit in a byte, signed bytes are -128..=127 and unsigned bytes are 0..=255"
+        ),
+    }
+}
 
-    let (input, num) = recognize(pair(opt(alt((tag("-"), tag("+")))), digit1))(input)?;
-    let num: i8 = i8::from_str_radix(&num, 10).map_err(parse_int_err(inp))?;
-    Ok((input, num))
+/// A single value of a variable, an expression that is only a literal is kept as a byte
+fn element(input: Span) -> IResult<Literal> {
+    alt((
+        // tried first as the count would otherwise be parsed as an expression
+        map(Dup::parse, Literal::Dup),
+        map(NotSet::parse, Literal::NotSet),
+        map(crate::Expr::parse, |expr| match expr {
+            crate::Expr::Lit(byte) => Literal::Byte(byte),
+            expr => Literal::Expr(Expr(expr)),
+        }),
+    ))(input)
 }
 
 impl ParseNom for Byte {
     fn parse(input: Span) -> IResult<Self> {
-        let (input, num) = alt((binary, hexadecimal, decimal))(input)?;
-        Ok((input, Self(num)))
+        map(number, Self)(input)
     }
 }
 
 impl ParseNom for Array {
     fn parse(input: Span) -> IResult<Self> {
         let (input, (first, mut vals)) = separated_pair(
-            alt((
-                map(Byte::parse, Literal::Byte),
-                map(NotSet::parse, Literal::NotSet),
-            )),
+            element,
             ws0(tag(",")),
-            separated_list1(
-                ws0(tag(",")),
-                alt((
-                    map(Byte::parse, Literal::Byte),
-                    map(NotSet::parse, Literal::NotSet),
-                )),
-            ),
+            separated_list1(ws0(tag(",")), element),
         )(input)?;
         vals.insert(0, first);
         Ok((input, Self(vals)))
     }
 }
 
+impl ParseNom for Dup {
+    fn parse(input: Span) -> IResult<Self> {
+        let (input, (count, values)) = pair(
+            ws_end1(verify(Byte::parse, |count: &Byte| count.0 > 0)),
+            preceded(
+                ws_end0(keyword::Dup::parse),
+                delimited(ws_end0(tag("(")), Literal::parse, ws0(tag(")"))),
+            ),
+        )(input)?;
+        let values = match values {
+            Literal::Array(Array(values)) => values,
+            value => vec![value],
+        };
+        let count = count.0 as usize;
+        Ok((input, Self(Repetition { count, values })))
+    }
+}
+
 impl ParseNom for NotSet {
     fn parse(input: Span) -> IResult<Self> {
         tag("?")(input).map(|(input, _)| (input, Self))
@@ -92,11 +215,7 @@ impl ParseNom for NotSet {
 
 impl ParseNom for Literal {
     fn parse(input: Span) -> IResult<Self> {
-        alt((
-            map(Array::parse, Literal::Array),
-            map(Byte::parse, Literal::Byte),
-            map(NotSet::parse, Literal::NotSet),
-        ))(input)
+        alt((map(Array::parse, Literal::Array), element))(input)
     }
 }
 
@@ -119,6 +238,13 @@ impl std::fmt::Display for Array {
     }
 }
 
+impl std::fmt::Display for Dup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Repetition { count, values } = &self.0;
+        write!(f, "{count} {}({})", keyword::Dup, Array(values.clone()))
+    }
+}
+
 impl std::fmt::Display for NotSet {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "?")
@@ -131,14 +257,18 @@ impl std::fmt::Display for Literal {
             Self::Byte(b) => b.fmt(f),
             Self::Array(a) => a.fmt(f),
             Self::NotSet(n) => n.fmt(f),
+            Self::Expr(e) => e.0.fmt(f),
+            Self::Dup(d) => d.fmt(f),
         }
     }
 }
 
 #[cfg(test)]
 mod test {
+    use proptest::prelude::*;
+
     use super::{Array, Byte, Literal, NotSet};
-    use crate::Parse;
+    use crate::{arbitrary::reparse, Parse};
 
     #[test]
     fn literal1() {
@@ -174,4 +304,66 @@ mod test {
         ]));
         assert_eq!(Literal::parse("20,?").unwrap().1, expected);
     }
+
+    #[test]
+    fn literal6() {
+        let Literal::Array(Array(values)) = Literal::parse("arr+1, 2*3, -4").unwrap().1 else {
+            panic!("expected an array");
+        };
+        assert!(matches!(values[0], Literal::Expr(..)));
+        assert_eq!(values[0].to_string(), "arr+1");
+        assert!(matches!(values[1], Literal::Expr(..)));
+        assert_eq!(values[2], Literal::Byte(Byte(-4)));
+    }
+
+    #[test]
+    fn literal7() {
+        let literal = Literal::parse("1, 2 dup (?, 3 DUP(0)), -1").unwrap().1;
+        assert_eq!(literal.size_of(), 10);
+        assert_eq!(literal.to_string(), "1, 2 DUP(?, 3 DUP(0)), -1");
+        let values: Vec<_> = literal.elements().iter().map(|v| v.to_string()).collect();
+        assert_eq!(values, ["1", "?", "0", "0", "0", "?", "0", "0", "0", "-1"]);
+        assert!(!literal.is_nested());
+        // a count of zero is not a repetition
+        assert_eq!(*Literal::parse("0 DUP(1)").unwrap().0.fragment(), " DUP(1)");
+    }
+
+    #[test]
+    fn literal8() {
+        for (input, value) in [
+            ("200", 200),
+            ("0xFF", 255),
+            ("0b1111_0000", 240),
+            ("1_2_7i8", 127),
+            ("-128i8", -128),
+            ("0x_80u8", 128),
+        ] {
+            assert_eq!(reparse::<Byte>(input), Byte(value));
+        }
+        assert_eq!(Byte(200).as_i8(), -56);
+    }
+
+    #[test]
+    fn literal9() {
+        let help = |input| match Byte::parse(input) {
+            Err(nom::Err::Failure(err)) => miette::Diagnostic::help(&err).unwrap().to_string(),
+            other => panic!("expected a failure for {input}, found {other:?}"),
+        };
+        assert_eq!(
+            help("200i8"),
+            "`200i8` is out of range for its suffix, the byte 0xc8 is `200u8` and `-56i8`"
+        );
+        assert_eq!(
+            help("-1u8"),
+            "`-1u8` is out of range for its suffix, the byte 0xff is `255u8` and `-1i8`"
+        );
+        assert!(help("0x1_00").starts_with("`0x1_00` does not fit in a byte"));
+    }
+
+    proptest! {
+        #[test]
+        fn literal_roundtrip(literal in any::<Literal>()) {
+            prop_assert_eq!(reparse::<Literal>(&literal.to_string()), literal);
+        }
+    }
 }
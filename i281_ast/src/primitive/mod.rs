@@ -5,7 +5,7 @@ pub mod opcode;
 mod oper;
 mod register;
 
-pub use ident::Ident;
+pub use ident::{Ident, ReservedWord};
 pub use literal::Literal;
 pub use opcode::OpCode;
 pub use oper::Oper;
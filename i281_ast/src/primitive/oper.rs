@@ -8,11 +8,52 @@ use crate::ParseNom;
 pub enum Oper {
     Add,
     Sub,
+    Mul,
+    Div,
+    Shl,
+    Shr,
+    And,
+    Or,
+}
+
+impl Oper {
+    pub const ALL: &'static [Oper] = &[
+        Self::Add,
+        Self::Sub,
+        Self::Mul,
+        Self::Div,
+        Self::Shl,
+        Self::Shr,
+        Self::And,
+        Self::Or,
+    ];
+
+    /// How tightly the operator binds, operators with a higher precedence are evaluated first
+    ///
+    /// From lowest to highest: `|`, `&`, `<<` `>>`, `+` `-`, `*` `/`
+    pub fn precedence(self) -> u8 {
+        match self {
+            Self::Or => 1,
+            Self::And => 2,
+            Self::Shl | Self::Shr => 3,
+            Self::Add | Self::Sub => 4,
+            Self::Mul | Self::Div => 5,
+        }
+    }
 }
 
 impl ParseNom for Oper {
     fn parse(input: crate::Span) -> crate::IResult<Self> {
-        alt((map(tag("+"), |_| Self::Add), map(tag("-"), |_| Self::Sub)))(input)
+        alt((
+            map(tag("+"), |_| Self::Add),
+            map(tag("-"), |_| Self::Sub),
+            map(tag("*"), |_| Self::Mul),
+            map(tag("/"), |_| Self::Div),
+            map(tag("<<"), |_| Self::Shl),
+            map(tag(">>"), |_| Self::Shr),
+            map(tag("&"), |_| Self::And),
+            map(tag("|"), |_| Self::Or),
+        ))(input)
     }
 }
 
@@ -21,6 +62,12 @@ impl std::fmt::Display for Oper {
         match self {
             Oper::Add => write!(f, "+"),
             Oper::Sub => write!(f, "-"),
+            Oper::Mul => write!(f, "*"),
+            Oper::Div => write!(f, "/"),
+            Oper::Shl => write!(f, "<<"),
+            Oper::Shr => write!(f, ">>"),
+            Oper::And => write!(f, "&"),
+            Oper::Or => write!(f, "|"),
         }
     }
 }
@@ -39,4 +86,18 @@ mod test {
     fn oper2() {
         assert_eq!(Oper::parse("-").unwrap().1, Oper::Sub);
     }
+
+    #[test]
+    fn oper3() {
+        assert_eq!(Oper::parse("<<").unwrap().1, Oper::Shl);
+        assert_eq!(Oper::parse(">>").unwrap().1, Oper::Shr);
+        assert!(Oper::parse("<").is_err());
+    }
+
+    #[test]
+    fn oper4() {
+        assert!(Oper::Mul.precedence() > Oper::Add.precedence());
+        assert!(Oper::Add.precedence() > Oper::Shl.precedence());
+        assert!(Oper::And.precedence() > Oper::Or.precedence());
+    }
 }
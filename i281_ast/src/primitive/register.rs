@@ -15,9 +15,10 @@ impl Register {
     pub const ALL: &'static [&'static str] = &["A", "B", "C", "D"];
 }
 
-fn non_alphanum(c: char) -> bool {
+// underscores are included so a register is never parsed from the start of an identifier
+fn non_ident_char(c: char) -> bool {
     if c.is_ascii() {
-        !is_alphanumeric(c as u8)
+        !is_alphanumeric(c as u8) && c != '_'
     } else {
         false
     }
@@ -25,7 +26,7 @@ fn non_alphanum(c: char) -> bool {
 
 impl ParseNom for Register {
     fn parse(input: crate::Span) -> crate::IResult<Self> {
-        let (input, c) = take_till1(non_alphanum)(input)?;
+        let (input, c) = take_till1(non_ident_char)(input)?;
         match c.to_uppercase().as_str() {
             "A" => Ok((input, Self::A)),
             "B" => Ok((input, Self::B)),
@@ -84,4 +85,10 @@ mod test {
     fn register5() {
         Register::parse("domeasolid").unwrap();
     }
+
+    #[test]
+    #[should_panic]
+    fn register6() {
+        Register::parse("a_1").unwrap();
+    }
 }
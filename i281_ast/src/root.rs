@@ -1,8 +1,6 @@
-use nom::{branch::permutation, combinator::opt};
+use crate::{CodeSegment, DataSegment, ParseError, SyntaxTree};
 
-use crate::{CodeSegment, DataSegment, ParseError, ParseNom, Span};
-
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 pub struct Root {
     pub data: Option<DataSegment>,
@@ -10,21 +8,38 @@ pub struct Root {
 }
 
 impl Root {
+    /// Parse a whole program, this goes through the lossless [`SyntaxTree`]
     pub fn parse(input: &str) -> Result<Self, ParseError<'static>> {
-        <Self as ParseNom>::parse(Span::new_extra(input, input))
-            .map(|(_, out)| out)
-            .map_err(|err| match err {
-                nom::Err::Failure(e) | nom::Err::Error(e) => e,
-                nom::Err::Incomplete(_) => unreachable!(), // we use complete in all parsers
-            })
-            .map_err(ParseError::into_static)
+        SyntaxTree::parse(input).to_root()
+    }
+}
+
+impl std::fmt::Display for Root {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(data) = &self.data {
+            writeln!(f, "{data}")?;
+        }
+        write!(f, "{}", self.code)
     }
 }
 
-impl ParseNom for Root {
-    fn parse(input: crate::Span) -> crate::IResult<Self> {
-        let (input, (data, code)) =
-            permutation((opt(DataSegment::parse), CodeSegment::parse))(input)?;
-        Ok((input, Self { data, code }))
+#[cfg(test)]
+mod test {
+    use proptest::prelude::*;
+
+    use super::Root;
+
+    #[test]
+    fn root1() {
+        let source = ".data\nx BYTE 1, ?\n.code\nloop: LOADF A, [x+B]\n    JUMP loop\n";
+        let root = Root::parse(source).unwrap();
+        assert_eq!(Root::parse(&root.to_string()).unwrap(), root);
+    }
+
+    proptest! {
+        #[test]
+        fn root_roundtrip(root in any::<Root>()) {
+            prop_assert_eq!(Root::parse(&root.to_string()).unwrap(), root);
+        }
     }
 }
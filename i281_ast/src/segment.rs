@@ -1,14 +1,19 @@
 use nom::{
+    branch::alt,
     bytes::complete::tag,
-    combinator::opt,
-    multi::many1,
-    sequence::{delimited, pair, preceded, terminated}, branch::alt,
+    combinator::{opt, verify},
+    multi::{many0, many1},
+    sequence::{delimited, pair, preceded, terminated},
 };
 
+use miette::SourceSpan;
+
 use crate::{
     keyword,
-    util::{always_fails, many0_endings, ws_start0, eof},
-    IResult, Instruction, Label, ParseNom, Span, Variable,
+    literal::Byte,
+    util::{always_fails, eof, many0_endings, spanned, ws_end1, ws_start0},
+    variable::data_addr,
+    IResult, Instruction, Label, ParseNom, Placement, Span, Variable,
 };
 
 #[derive(Clone, Debug)]
@@ -17,85 +22,155 @@ pub struct DataSegment {
     pub variables: Vec<Variable>,
 }
 
+// spans only describe where the segment came from so they are ignored
 impl PartialEq for DataSegment {
     fn eq(&self, other: &Self) -> bool {
-        self.variables
-            .iter()
-            .zip(other.variables.iter())
-            .all(|(a, b)| a == b)
+        self.variables == other.variables
     }
 }
 impl Eq for DataSegment {}
 
+impl DataSegment {
+    /// Lay out the variables in memory in the order they were declared
+    ///
+    /// Each variable follows on from the one before it, `.org` moves where this happens and
+    /// variables placed with `@` are skipped over. Variables can still overlap as `.org` and `@`
+    /// are taken as written, the analyzer reports this. A negative placement, which only a hand
+    /// written ast can have, is ignored and also reported by the analyzer. The parser calls this
+    /// automatically, it only needs to be called after a data segment is constructed or
+    /// deserialized by hand.
+    pub fn assign_addresses(&mut self) {
+        let pinned: Vec<_> = self
+            .variables
+            .iter()
+            .filter_map(|var| match &var.placement {
+                Some(placement @ Placement::At(_)) => {
+                    let start = placement.data_addr()?;
+                    Some((start, start.checked_add(var.value.size_of())?))
+                }
+                _ => None,
+            })
+            .collect();
+
+        let mut data_addr: usize = 0;
+        for var in self.variables.iter_mut() {
+            let size = var.value.size_of();
+            let placed = var.placement.as_ref().and_then(Placement::data_addr);
+            match (&var.placement, placed) {
+                (Some(Placement::At(_)), Some(addr)) => {
+                    var.data_addr = addr;
+                    continue;
+                }
+                (Some(Placement::Org(_)), Some(addr)) => data_addr = addr,
+                _ => {
+                    while let Some(&(_, end)) = pinned.iter().find(|&&(start, end)| {
+                        start < data_addr.saturating_add(size) && data_addr < end
+                    }) {
+                        data_addr = end;
+                    }
+                }
+            }
+            var.data_addr = data_addr;
+            data_addr = data_addr.saturating_add(size);
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 pub struct CodeSegment {
     pub labels: Vec<Label>,
     pub instructions: Vec<Instruction>,
+    /// Where each instruction was written in the source, empty if the segment was not parsed
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub instruction_spans: Vec<SourceSpan>,
 }
 
 impl PartialEq for CodeSegment {
     fn eq(&self, other: &Self) -> bool {
-        let labels = self
-            .labels
-            .iter()
-            .zip(other.labels.iter())
-            .all(|(a, b)| a == b);
-        let instructions = self
-            .instructions
-            .iter()
-            .zip(other.instructions.iter())
-            .all(|(a, b)| a == b);
-        labels && instructions
+        self.labels == other.labels && self.instructions == other.instructions
     }
 }
 impl Eq for CodeSegment {}
 
+impl CodeSegment {
+    /// Get where the instruction at `index` was written in the source if it is known
+    pub fn instruction_span(&self, index: usize) -> Option<SourceSpan> {
+        self.instruction_spans.get(index).copied()
+    }
+}
+
 impl ParseNom for DataSegment {
     fn parse(input: crate::Span) -> crate::IResult<Self> {
-        let (input, mut variables) = preceded(
+        let (input, variables) = preceded(
             delimited(
                 many0_endings,
                 preceded(tag("."), keyword::Data::parse),
                 many0_endings,
             ),
-            always_fails(many1(terminated(ws_start0(Variable::parse), alt((many0_endings, eof))))),
+            always_fails(many1(verify(
+                pair(
+                    opt(terminated(ws_start0(org), many0_endings)),
+                    terminated(ws_start0(Variable::parse), alt((many0_endings, eof))),
+                ),
+                // a variable can't be placed twice
+                |(org, var): &(Option<Byte>, Variable)| org.is_none() || var.placement.is_none(),
+            ))),
         )(input)?;
-        let mut data_addr: usize = 0;
-        for var in variables.iter_mut() {
-            var.data_addr = data_addr;
-            data_addr += var.value.size_of();
-        }
-        Ok((input, Self { variables }))
+
+        let variables = variables
+            .into_iter()
+            .map(|(org, mut var)| {
+                if let Some(addr) = org {
+                    var.placement = Some(Placement::Org(addr));
+                }
+                var
+            })
+            .collect();
+        let mut data = Self { variables };
+        data.assign_addresses();
+        Ok((input, data))
     }
 }
 
+/// `.org N` moves where the next variable is placed
+pub(crate) fn org(input: Span) -> IResult<Byte> {
+    preceded(preceded(tag("."), ws_end1(keyword::Org::parse)), data_addr)(input)
+}
+
 impl ParseNom for CodeSegment {
     fn parse(input: Span) -> IResult<Self> {
-        let (input, code) = preceded(
+        let label = || ws_start0(Label::parse);
+        let (input, (code, end_labels)) = preceded(
             delimited(
                 many0_endings,
                 preceded(tag("."), keyword::Code::parse),
                 always_fails(many0_endings),
             ),
-            always_fails(many1(pair(
-                opt(terminated(ws_start0(Label::parse), many0_endings)),
-                terminated(ws_start0(Instruction::parse), alt((many0_endings, eof))),
-            ))),
+            pair(
+                always_fails(many1(pair(
+                    many0(terminated(label(), many0_endings)),
+                    terminated(
+                        ws_start0(spanned(Instruction::parse)),
+                        alt((many0_endings, eof)),
+                    ),
+                ))),
+                // labels after the last instruction point just past the end of the code
+                many0(terminated(label(), alt((many0_endings, eof)))),
+            ),
         )(input)?;
 
         let (labels, instructions): (Vec<_>, Vec<_>) = code.into_iter().unzip();
+        let (instructions, instruction_spans): (Vec<_>, _) = instructions.into_iter().unzip();
 
-        let labels: Vec<_> = labels
+        let labels = labels
             .into_iter()
+            .chain(std::iter::once(end_labels))
             .enumerate()
-            .filter_map(|(i, label)| {
-                if let Some(mut label) = label {
-                    label.code_addr = i;
-                    Some(label)
-                } else {
-                    None
-                }
+            .flat_map(|(code_addr, labels)| {
+                labels
+                    .into_iter()
+                    .map(move |label| Label { code_addr, ..label })
             })
             .collect();
 
@@ -104,9 +179,133 @@ impl ParseNom for CodeSegment {
             Self {
                 labels,
                 instructions,
+                instruction_spans,
             },
         ))
     }
 }
 
-// TODO: implement comprehensive tests for both segments
+impl std::fmt::Display for DataSegment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, ".{}", keyword::Data)?;
+        for var in self.variables.iter() {
+            if let Some(Placement::Org(addr)) = &var.placement {
+                writeln!(f, ".{} {addr}", keyword::Org)?;
+            }
+            writeln!(f, "{var}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for CodeSegment {
+    /// Labels are written in front of the instruction at their `code_addr` and labels right
+    /// after the last instruction at the end, labels that point further have nowhere to go and
+    /// are not written
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, ".{}", keyword::Code)?;
+        for (code_addr, ins) in self.instructions.iter().enumerate() {
+            for label in self.labels.iter().filter(|l| l.code_addr == code_addr) {
+                writeln!(f, "{label}")?;
+            }
+            writeln!(f, "    {ins}")?;
+        }
+        let end = self.instructions.len();
+        for label in self.labels.iter().filter(|l| l.code_addr == end) {
+            writeln!(f, "{label}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use proptest::prelude::*;
+
+    use super::{CodeSegment, DataSegment};
+    use crate::{arbitrary::reparse, literal::Byte, Label, Parse, Placement};
+
+    #[test]
+    fn segment1() {
+        let code = CodeSegment::parse(".code\nstart: NOOP\n    JUMP start\n")
+            .unwrap()
+            .1;
+        assert_eq!(
+            code.to_string(),
+            ".code\nstart:\n    NOOP\n    JUMP start\n"
+        );
+    }
+
+    #[test]
+    fn segment2() {
+        let a = CodeSegment::parse(".code\nNOOP\nNOOP\n").unwrap().1;
+        let mut b = a.clone();
+        b.instructions.pop();
+        assert_ne!(a, b);
+        b = a.clone();
+        b.labels.push(Label {
+            ident: "extra".into(),
+            code_addr: 0,
+            span: None,
+        });
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn segment3() {
+        let source = ".data\na BYTE 1, 2\nb BYTE 3 @ 1\n.org 10\nc BYTE ?\nd BYTE 4\ne BYTE 5\n";
+        let data = DataSegment::parse(source).unwrap().1;
+        let addrs: Vec<_> = data.variables.iter().map(|v| v.data_addr).collect();
+        assert_eq!(addrs, [2, 1, 10, 11, 12]);
+        assert_eq!(data.to_string(), source);
+        assert!(DataSegment::parse(".data\n.org 1\na BYTE 1 @ 2\n").is_err());
+
+        // only a hand written ast can have these, they are ignored and the analyzer reports them
+        let mut data = DataSegment::parse(".data\na BYTE 1\nb BYTE 2\nc BYTE 3\n")
+            .unwrap()
+            .1;
+        data.variables[0].placement = Some(Placement::At(Byte(-1)));
+        data.variables[1].placement = Some(Placement::Org(Byte(i16::MIN)));
+        data.assign_addresses();
+        let addrs: Vec<_> = data.variables.iter().map(|v| v.data_addr).collect();
+        assert_eq!(addrs, [0, 1, 2]);
+    }
+
+    #[test]
+    fn segment4() {
+        let source = ".code\nstart: main:\n    NOOP\nloop: JUMP loop\nend:\ndone:";
+        let code = CodeSegment::parse(source).unwrap().1;
+        let addrs: Vec<_> = code
+            .labels
+            .iter()
+            .map(|l| (l.ident.as_str(), l.code_addr))
+            .collect();
+        assert_eq!(
+            addrs,
+            [
+                ("start", 0),
+                ("main", 0),
+                ("loop", 1),
+                ("end", 2),
+                ("done", 2)
+            ]
+        );
+        assert_eq!(
+            code.to_string(),
+            ".code\nstart:\nmain:\n    NOOP\nloop:\n    JUMP loop\nend:\ndone:\n"
+        );
+        assert!(CodeSegment::parse(".code\nend:\n").is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn data_segment_roundtrip(data in any::<DataSegment>()) {
+            prop_assert_eq!(reparse::<DataSegment>(&data.to_string()), data);
+        }
+
+        #[test]
+        fn code_segment_roundtrip(code in any::<CodeSegment>()) {
+            prop_assert_eq!(reparse::<CodeSegment>(&code.to_string()), code);
+        }
+    }
+}
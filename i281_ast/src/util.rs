@@ -1,3 +1,4 @@
+use miette::SourceSpan;
 use nom::{
     bytes::complete::{is_not, tag},
     character::complete::{line_ending, multispace0, multispace1},
@@ -6,7 +7,7 @@ use nom::{
     sequence::{delimited, pair, preceded, terminated},
 };
 
-use crate::Span;
+use crate::{IResult, Span};
 
 pub(crate) fn ws0<I, O, F, E>(f: F) -> impl FnMut(I) -> nom::IResult<I, O, E>
 where
@@ -59,10 +60,7 @@ pub(crate) fn ending1<'a, E>(input: Span<'a>) -> nom::IResult<Span<'a>, (), E>
 where
     E: nom::error::ParseError<Span<'a>>,
 {
-    value(
-        (),
-        terminated(opt(comma_eol_comment), line_ending),
-    )(input)
+    value((), terminated(opt(comma_eol_comment), line_ending))(input)
 }
 
 pub(crate) fn many0_endings<'a, E>(input: Span<'a>) -> nom::IResult<Span<'a>, (), E>
@@ -94,6 +92,19 @@ where
     value((), nom::combinator::eof)(input)
 }
 
+/// Run a parser and also return the span of the input it consumed
+pub(crate) fn spanned<'a, O, F>(mut f: F) -> impl FnMut(Span<'a>) -> IResult<'a, (O, SourceSpan)>
+where
+    F: nom::Parser<Span<'a>, O, crate::ParseError<'a>>,
+{
+    move |input: Span<'a>| {
+        let start = input.location_offset();
+        let (rest, out) = f.parse(input)?;
+        let span = SourceSpan::new(start.into(), (rest.location_offset() - start).into());
+        Ok((rest, (out, span)))
+    }
+}
+
 macro_rules! type_enum {
     (@base $name:ident $(<$($lif:tt),+>)? {$($variant:ident $(<$($varlif:tt),+>)?),*}) => {
         #[derive(Clone, Debug, PartialEq, Eq)]
@@ -1,28 +1,86 @@
-use nom::sequence::separated_pair;
+use miette::SourceSpan;
+use nom::{
+    bytes::complete::tag,
+    combinator::{map, opt, verify},
+    sequence::{pair, preceded, separated_pair},
+};
 
-use crate::{keyword, util::ws_end1, Ident, Literal, ParseNom};
+use crate::{
+    keyword,
+    literal::Byte,
+    util::{spanned, ws0, ws_end1},
+    IResult, Ident, Literal, ParseNom, Span,
+};
 
+/// Where a variable is placed when it should not simply follow the variable before it
 #[derive(Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "kind", content = "data_addr", rename_all = "snake_case")
+)]
+pub enum Placement {
+    /// `.org N` on the line before the variable, the variables after it follow on from it
+    Org(Byte),
+    /// `name BYTE value @ N`, only this variable is moved and the others are laid out around it
+    At(Byte),
+}
+
+impl Placement {
+    /// The address this places at, `None` if it is negative and can't be an address
+    pub fn data_addr(&self) -> Option<usize> {
+        let (Self::Org(addr) | Self::At(addr)) = self;
+        usize::try_from(addr.0).ok()
+    }
+}
+
+/// A data address written in a placement, these can not be negative
+pub(crate) fn data_addr(input: Span) -> IResult<Byte> {
+    verify(Byte::parse, |addr: &Byte| addr.0 >= 0)(input)
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 pub struct Variable {
     pub ident: Ident,
     pub value: Literal,
+    /// Set if the address was given with `.org` or `@`
+    pub placement: Option<Placement>,
     pub data_addr: usize,
+    /// Where the variable was declared in the source, this is `None` if it was not parsed
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub span: Option<SourceSpan>,
 }
 
+// the span is ignored as it only describes where the variable came from
+impl PartialEq for Variable {
+    fn eq(&self, other: &Self) -> bool {
+        self.ident == other.ident
+            && self.value == other.value
+            && self.placement == other.placement
+            && self.data_addr == other.data_addr
+    }
+}
+impl Eq for Variable {}
+
 impl ParseNom for Variable {
     fn parse(input: crate::Span) -> crate::IResult<Self> {
-        let (input, (ident, value)) = separated_pair(
-            ws_end1(Ident::parse),
-            ws_end1(keyword::Byte::parse),
-            Literal::parse,
-        )(input)?;
+        let (input, (((ident, value), at), span)) = spanned(pair(
+            separated_pair(
+                ws_end1(Ident::parse),
+                ws_end1(keyword::Byte::parse),
+                Literal::parse,
+            ),
+            opt(map(preceded(ws0(tag("@")), data_addr), Placement::At)),
+        ))(input)?;
         Ok((
             input,
             Variable {
                 ident,
                 value,
+                placement: at,
                 data_addr: 0,
+                span: Some(span),
             },
         ))
     }
@@ -30,22 +88,36 @@ impl ParseNom for Variable {
 
 impl std::fmt::Display for Variable {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let Self { ident, value, .. } = self;
-        write!(f, "{ident} {} {value}", keyword::Byte)
+        let Self {
+            ident,
+            value,
+            placement,
+            ..
+        } = self;
+        write!(f, "{ident} {} {value}", keyword::Byte)?;
+        // `.org` is on its own line so it is written by the data segment
+        match placement {
+            Some(Placement::At(addr)) => write!(f, " @ {addr}"),
+            _ => Ok(()),
+        }
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::Variable;
-    use crate::{literal::Byte, Parse};
+    use proptest::prelude::*;
+
+    use super::{Placement, Variable};
+    use crate::{arbitrary::reparse, literal::Byte, Parse};
 
     #[test]
     fn variable1() {
         let expected = Variable {
             ident: "a".into(),
             value: Byte(10).into(),
+            placement: None,
             data_addr: 0,
+            span: None,
         };
         assert_eq!(Variable::parse("a BYTE 10").unwrap().1, expected);
     }
@@ -55,4 +127,20 @@ mod test {
     fn variable2() {
         Variable::parse("a 10").unwrap(); // missing keyword
     }
+
+    #[test]
+    fn variable3() {
+        let var = Variable::parse("a BYTE 1, 2 @ 0x0C").unwrap().1;
+        assert_eq!(var.placement, Some(Placement::At(Byte(12))));
+        assert_eq!(var.to_string(), "a BYTE 1, 2 @ 12");
+        let (rest, _) = Variable::parse("a BYTE 1 @ -1").unwrap();
+        assert_eq!(*rest.fragment(), " @ -1"); // addresses can't be negative
+    }
+
+    proptest! {
+        #[test]
+        fn variable_roundtrip(var in any::<Variable>()) {
+            prop_assert_eq!(reparse::<Variable>(&var.to_string()), var);
+        }
+    }
 }
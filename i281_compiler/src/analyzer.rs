@@ -1,46 +1,205 @@
-use std::collections::HashSet;
+use std::collections::HashMap;
 
 use i281_ast::{
-    literal::Byte, Address, AddressItem, Ident, Instruction, Label, Oper, Register, Root, Variable,
+    literal, Address, Expr, Ident, Instruction, Label, LabelKind, Literal, Placement, Pointer,
+    Register, ReservedWord, Root, Variable,
 };
-use i281_ir::{BranchCondition, Instruction::*, Ir, ShiftDirection};
+use i281_ir::{
+    BranchCondition, Instruction::*, ShiftDirection, Symbol, SymbolicIr, SymbolicLabel,
+    SymbolicVariable, Value,
+};
+use miette::SourceSpan;
 
 use crate::{
-    diagnostics::{Diagnostic, Error, Failure, Result, Warning},
-    CODE_ADDR_MAX, DATA_ADDR_MAX, DATA_ADDR_MIN,
+    diagnostics::{did_you_mean, Diagnostic, Error, Failure, Result, Warning},
+    eval::{self, symbol_value, Linear},
+    CODE_ADDR_MAX, CODE_ADDR_MIN, DATA_ADDR_MAX, DATA_ADDR_MIN,
 };
 
+/// Memory that is filled beyond this percentage produces a warning
+const NEARLY_FULL_PERCENT: usize = 90;
+
+fn nearly_full(used: usize, max: usize) -> bool {
+    used * 100 > max * NEARLY_FULL_PERCENT && used <= max
+}
+
+/// A variable placed with `@`, or with or after a `.org`, is at an address linking does not change
+fn is_fixed(variables: &[Variable], index: usize) -> bool {
+    variables[index].placement.is_some()
+        || variables[..index]
+            .iter()
+            .any(|var| matches!(var.placement, Some(Placement::Org(_))))
+}
+
+/// Report a declared name that is spelled the same as a register, opcode or keyword
+///
+/// Register names are an error as addresses and expressions always read them as the register,
+/// the others only make the program harder to read. `suffix` is added to suggest a new name.
+fn reserved_name(
+    name: &Ident,
+    suffix: &str,
+    span: Option<SourceSpan>,
+    variables: &[Variable],
+    labels: &[Label],
+) -> Option<Diagnostic> {
+    let word = name.reserved_word()?;
+    let base = format!("{}_{suffix}", name.as_str().to_lowercase());
+    let taken = |candidate: &str| {
+        variables.iter().any(|v| v.ident.as_str() == candidate)
+            || labels.iter().any(|l| l.ident.as_str() == candidate)
+    };
+    let rename = std::iter::once(base.clone())
+        .chain((2..).map(|n| format!("{base}{n}")))
+        .find(|candidate| !taken(candidate))
+        .expect("there are only finitely many names taken")
+        .into();
+    Some(match word {
+        ReservedWord::Register(register) => Error::RegisterName {
+            name: name.clone(),
+            register,
+            rename,
+            span,
+        }
+        .into(),
+        _ => Warning::ReservedName {
+            name: name.clone(),
+            kind: word.kind(),
+            word: word.as_str(),
+            rename,
+            span,
+        }
+        .into(),
+    })
+}
+
+/// An expression that folds to a value above 127 is a different number when read as a signed byte
+///
+/// Literals are left alone as writing a literal above 127 already says it is unsigned.
+fn ambiguous_byte(expr: &Expr, folded: &Linear, span: Option<SourceSpan>) -> Option<Diagnostic> {
+    if matches!(expr, Expr::Lit(_)) || folded.value <= i8::MAX as i64 {
+        return None;
+    }
+    Some(
+        Warning::AmbiguousByte {
+            expr: expr.clone(),
+            value: folded.value,
+            signed: folded.value as i8,
+            span,
+        }
+        .into(),
+    )
+}
+
+/// A unique name for every label, local labels are qualified with their scope and numeric labels
+/// are numbered in the order they are defined, e.g. `1#0` and `1#1`
+fn label_names(labels: &[Label]) -> Vec<String> {
+    let mut defined = HashMap::new();
+    Label::qualified_names(labels)
+        .into_iter()
+        .zip(labels)
+        .map(|(name, label)| match label.kind() {
+            LabelKind::Numeric => {
+                let count = defined.entry(name.clone()).or_insert(0);
+                *count += 1;
+                format!("{name}#{}", *count - 1)
+            }
+            _ => name.to_string(),
+        })
+        .collect()
+}
+
+/// The memory an address points into
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemorySpace {
+    /// Instructions, labels are addresses in code memory
+    Code,
+    /// User data, variables are addresses in data memory
+    Data,
+}
+
+impl MemorySpace {
+    /// Returns true if the address is within this memory
+    pub fn contains(self, addr: i64) -> bool {
+        match self {
+            Self::Code => (CODE_ADDR_MIN as i64..CODE_ADDR_MAX as i64).contains(&addr),
+            Self::Data => (DATA_ADDR_MIN as i64..DATA_ADDR_MAX as i64).contains(&addr),
+        }
+    }
+}
+
+impl std::fmt::Display for MemorySpace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Code => write!(f, "code"),
+            Self::Data => write!(f, "data"),
+        }
+    }
+}
+
 pub struct Analyzer {
     variables: Vec<Variable>,
     labels: Vec<Label>,
+    /// The name of every label in the symbolic ir, in the same order as `labels`
+    label_names: Vec<String>,
     instructions: Vec<Instruction>,
+    instruction_spans: Vec<SourceSpan>,
+    /// The span of the instruction currently being built
+    current_span: Option<SourceSpan>,
+    /// The index of the instruction currently being built, local labels are in its scope
+    current_index: Option<usize>,
+    /// Names that are not defined are imported from another module instead of being an error
+    imports: bool,
     diagnostics: Vec<Diagnostic>,
 }
 
 impl Analyzer {
     pub fn new(ast: Root) -> Self {
-        let variables = ast.data.map(|data| data.variables).unwrap_or_else(Vec::new);
+        let variables = ast.data.map(|data| data.variables).unwrap_or_default();
 
         let labels = ast.code.labels;
+        let label_names = label_names(&labels);
         let instructions = ast.code.instructions;
+        let instruction_spans = ast.code.instruction_spans;
 
         Self {
             variables,
             labels,
+            label_names,
             instructions,
+            instruction_spans,
+            current_span: None,
+            current_index: None,
+            imports: false,
             diagnostics: Vec::new(),
         }
     }
 
-    pub fn validate(mut self) -> Result<(Ir, Vec<Diagnostic>)> {
-        self.validate_labels()?;
-        let variables = self.validate_variables()?;
+    /// Import the names that are not defined from another module, for assembling an object that
+    /// is linked with other modules later
+    pub fn allow_imports(mut self, allow: bool) -> Self {
+        self.imports = allow;
+        self
+    }
+
+    pub fn validate(mut self) -> Result<(SymbolicIr, Vec<Diagnostic>)> {
+        self.validate_labels();
+        let variables = self.validate_variables();
 
         if self.instructions.len() > CODE_ADDR_MAX as usize {
             self.diagnostics.push(
                 Error::TooManyInstructions {
                     found: self.instructions.len(),
                     max: CODE_ADDR_MAX as usize,
+                    span: self.instruction_spans.get(CODE_ADDR_MAX as usize).copied(),
+                }
+                .into(),
+            );
+        } else if nearly_full(self.instructions.len(), CODE_ADDR_MAX as usize) {
+            self.diagnostics.push(
+                Warning::MemoryNearlyFull {
+                    memory: "code",
+                    used: self.instructions.len(),
+                    max: CODE_ADDR_MAX as usize,
                 }
                 .into(),
             );
@@ -48,246 +207,574 @@ impl Analyzer {
 
         let mut instructions = Vec::with_capacity(self.instructions.len());
         for (index, ins) in self.instructions.clone().iter().enumerate() {
-            match self.build_instruction(index, &ins) {
+            self.current_span = self.instruction_spans.get(index).copied();
+            self.current_index = Some(index);
+            match self.build_instruction(index, ins) {
                 Ok(ins) => instructions.push(ins),
                 Err(Failure::Skip) => continue,
                 Err(other) => return Err(other),
             }
         }
 
+        let labels = self
+            .labels
+            .iter()
+            .zip(self.label_names)
+            .map(|(label, name)| SymbolicLabel {
+                name,
+                code_addr: label.code_addr,
+            })
+            .collect();
         Ok((
-            Ir {
+            SymbolicIr {
                 variables,
+                labels,
                 instructions,
             },
             self.diagnostics,
         ))
     }
 
-    fn get_label_offset(
+    /// The label a jump or branch goes to, it is only turned into an offset when resolving
+    fn get_label_target(
         &mut self,
         current_index: usize,
         ident: &Ident,
         ins: &Instruction,
-    ) -> Result<i8> {
-        let current_index: i8 = current_index.try_into().expect("this should always be valid as there must never be more than 32 user code instructions");
-        let label = self
+    ) -> Result<Value> {
+        let found = Label::resolve(&self.labels, current_index, ident);
+        if found.is_none() && self.imports && LabelKind::of(ident) == LabelKind::Global {
+            // the label is in another module, linking gives it its address
+            return Ok(Value::symbol(Symbol::Label(ident.to_string()), 0));
+        }
+        let label = found.ok_or_else(|| {
+            let references = Label::references(&self.labels, current_index);
+            let help = match did_you_mean(ident.as_str(), references.iter().map(Ident::as_str)) {
+                Some(name) => format!("did you mean `{name}`?"),
+                None => "add the label in front of an instruction in the `.code` segment".into(),
+            };
+            self.diagnostics.push(
+                Error::LabelNotFound {
+                    name: ident.clone(),
+                    ins: ins.clone(),
+                    help,
+                    span: self.current_span,
+                }
+                .into(),
+            );
+            Failure::Skip
+        })?;
+        if label.code_addr > self.instructions.len() {
+            return Err(Failure::Skip); // already reported when validating labels
+        }
+        let index = self
             .labels
             .iter()
-            .find(|l| &l.ident == ident)
-            .ok_or_else(|| {
-                self.diagnostics.push(
-                    Error::LabelNotFound {
-                        name: ident.clone(),
-                        ins: ins.clone(),
-                    }
-                    .into(),
-                );
+            .position(|l| std::ptr::eq(l, label))
+            .expect("the label was found in the labels");
+        Ok(Value::symbol(
+            Symbol::Label(self.label_names[index].clone()),
+            0,
+        ))
+    }
+
+    /// Fold an expression reporting any errors for the current instruction
+    ///
+    /// Identifiers are only looked up in `space` if it is given, otherwise labels and variables
+    /// can both be used.
+    fn fold(&mut self, expr: &Expr, space: Option<MemorySpace>) -> Result<Linear> {
+        self.fold_expr(expr, self.current_span, space)
+            .map_err(|err| {
+                self.diagnostics.push(err.into());
                 Failure::Skip
-            })?;
-        let label_index: i8 = label.code_addr.try_into().expect("this should always be valid as there must never be more than 32 user code instructions");
-        Ok(label_index - current_index - 1)
+            })
     }
 
-    fn get_var_address(&mut self, ident: &Ident, ins: &Instruction) -> Result<i8> {
-        let var = self
-            .variables
-            .iter()
-            .find(|v| &v.ident == ident)
-            .ok_or_else(|| {
+    /// Fold an expression, names that are not defined anywhere are imported if that is allowed
+    ///
+    /// An import is at address zero while folding so the expression has to be the import plus a
+    /// constant, otherwise linking could not give the value its real address. The same goes for
+    /// every label and variable that is not fixed when the module is linked later, as linking
+    /// moves them.
+    fn fold_expr(
+        &self,
+        expr: &Expr,
+        span: Option<SourceSpan>,
+        space: Option<MemorySpace>,
+    ) -> std::result::Result<Linear, Error> {
+        let lookup = |space| {
+            symbol_value(
+                &self.variables,
+                &self.labels,
+                &self.label_names,
+                self.current_index,
+                space,
+            )
+        };
+        let defined = lookup(space);
+        let anywhere = lookup(None);
+        let import = |ident: &Ident| {
+            let symbol = match space {
+                Some(MemorySpace::Code) => Symbol::Label(ident.to_string()),
+                _ => Symbol::Variable(ident.to_string()),
+            };
+            (self.imports && anywhere(ident).is_none()).then_some((symbol, 0))
+        };
+        let folded = eval::fold(expr, span, |ident| defined(ident).or_else(|| import(ident)))?;
+
+        if !self.imports {
+            return Ok(folded);
+        }
+        let moved = |ident: &Ident| match defined(ident) {
+            Some((Symbol::Variable(_), _)) => self
+                .variables
+                .iter()
+                .position(|var| &var.ident == ident)
+                .is_some_and(|index| !is_fixed(&self.variables, index)),
+            Some((Symbol::Label(_), _)) => true,
+            None => import(ident).is_some(),
+        };
+        let added = match folded.symbols.as_slice() {
+            [(symbol, 1)] if !folded.consumed.contains(symbol) => Some(symbol),
+            _ => None,
+        };
+        // a local label is named by its qualified name so symbols are compared and not names
+        let symbol = |ident: &Ident| defined(ident).or_else(|| import(ident)).map(|(s, _)| s);
+        match expr
+            .idents()
+            .find(|ident| moved(ident) && added != symbol(ident).as_ref())
+        {
+            Some(name) if import(name).is_some() => Err(Error::ImportNotAdded {
+                name: name.clone(),
+                expr: expr.clone(),
+                span,
+            }),
+            Some(name) => Err(Error::MovedNotAdded {
+                name: name.clone(),
+                expr: expr.clone(),
+                span,
+            }),
+            None => Ok(folded),
+        }
+    }
+
+    /// Returns true if the value is an import, its address is not known until linking
+    fn is_import(&self, value: &Value) -> bool {
+        value.symbol.as_ref().is_some_and(|symbol| {
+            let name = symbol.name();
+            self.variables.iter().all(|v| v.ident.as_str() != name)
+                && self.label_names.iter().all(|l| l != name)
+        })
+    }
+
+    fn get_immediate(&mut self, expr: &Expr) -> Result<Value> {
+        if !expr.is_const() {
+            self.diagnostics.push(
+                Error::ConstantWithRegister {
+                    expr: expr.clone(),
+                    span: self.current_span,
+                }
+                .into(),
+            );
+            return Err(Failure::Skip);
+        }
+        // folding checks every value fits in a byte, values above 127 are stored in two's complement
+        let folded = self.fold(expr, None)?;
+        self.diagnostics
+            .extend(ambiguous_byte(expr, &folded, self.current_span));
+        Ok(folded.to_value())
+    }
+
+    /// The address `{var}` or `{var + 1}` points to, `LOADP` is a `LOADI` of it
+    fn get_pointer(&mut self, pointer: &Pointer) -> Result<Value> {
+        let var = Expr::Ident(pointer.var.clone());
+        let expr = match &pointer.offset {
+            Some((oper, offset)) => Expr::Binary {
+                left: Box::new(var),
+                oper: *oper,
+                right: Box::new(Expr::Lit(offset.clone())),
+            },
+            None => var,
+        };
+        let folded = self.fold(&expr, Some(MemorySpace::Data))?;
+        self.diagnostics
+            .extend(ambiguous_byte(&expr, &folded, self.current_span));
+        Ok(folded.to_value())
+    }
+
+    /// Make sure every identifier in an address is in the memory the address points into
+    fn check_memory_space(
+        &mut self,
+        address: &Address,
+        space: MemorySpace,
+        ins: &Instruction,
+    ) -> Result<()> {
+        let other = match space {
+            MemorySpace::Code => MemorySpace::Data,
+            MemorySpace::Data => MemorySpace::Code,
+        };
+        let lookup = |space| {
+            symbol_value(
+                &self.variables,
+                &self.labels,
+                &self.label_names,
+                self.current_index,
+                Some(space),
+            )
+        };
+        for ident in address.to.idents() {
+            let (in_space, in_other) = (lookup(space), lookup(other));
+            if in_space(ident).is_none() && in_other(ident).is_some() {
                 self.diagnostics.push(
-                    Error::VariableNotFound {
+                    Error::WrongMemorySpace {
                         name: ident.clone(),
+                        found: other,
+                        expected: space,
                         ins: ins.clone(),
+                        span: self.current_span,
                     }
                     .into(),
                 );
-                Failure::Skip
-            })?;
-        let var_addr: i8 = var.data_addr.try_into().expect("this should always be valid as there must never be more than 16 user data addresses assigned");
-        Ok(var_addr)
+                return Err(Failure::Skip);
+            }
+        }
+        Ok(())
     }
 
-    fn get_address_value(&mut self, address: &Address, ins: &Instruction) -> Result<i8> {
+    fn get_address_value(
+        &mut self,
+        address: &Address,
+        space: MemorySpace,
+        ins: &Instruction,
+    ) -> Result<Value> {
         if !address.to.is_const() {
-            self.diagnostics
-                .push(Error::AddressWithRegister { ins: ins.clone() }.into());
+            self.diagnostics.push(
+                Error::AddressWithRegister {
+                    ins: ins.clone(),
+                    span: self.current_span,
+                }
+                .into(),
+            );
             return Err(Failure::Skip);
         }
 
-        let address = address
-            .to
-            .iter()
-            .try_fold((0i8, None), |(mut addr, oper), (item, next_oper)| {
-                let value = match item {
-                    AddressItem::Var(v) => self.get_var_address(v, ins)?,
-                    AddressItem::Lit(b) => b.0,
-                    AddressItem::Reg(_) => unreachable!(),
-                };
-                match oper {
-                    Some(Oper::Add) => match addr.checked_add(value) {
-                        Some(v) => addr = v,
-                        None => {
-                            self.diagnostics
-                                .push(Error::AddressOOB { ins: ins.clone() }.into());
-                            return Err(Failure::Skip);
-                        }
-                    },
-                    Some(Oper::Sub) => match addr.checked_sub(value) {
-                        Some(v) => addr = v,
-                        None => {
-                            self.diagnostics
-                                .push(Error::AddressOOB { ins: ins.clone() }.into());
-                            return Err(Failure::Skip);
-                        }
-                    },
-                    _ => addr = value,
+        self.check_memory_space(address, space, ins)?;
+        let folded = self.fold(&address.to, Some(space))?;
+        let value = folded.to_value();
+        if !self.is_import(&value) && !space.contains(folded.value) {
+            self.diagnostics.push(
+                Error::AddressOOB {
+                    ins: ins.clone(),
+                    span: self.current_span,
                 }
-                Result::Ok((addr, next_oper.copied()))
-            })?
-            .0;
-
-        if address < DATA_ADDR_MIN || address > DATA_ADDR_MAX {
-            self.diagnostics
-                .push(Error::AddressOOB { ins: ins.clone() }.into());
+                .into(),
+            );
             return Err(Failure::Skip);
         }
 
-        Ok(address)
+        Ok(value)
     }
 
     fn get_address_value_with_offset(
         &mut self,
         address: &Address,
+        space: MemorySpace,
         ins: &Instruction,
-    ) -> Result<(i8, Register)> {
+    ) -> Result<(Value, Register)> {
         if address.to.is_const() {
-            self.diagnostics
-                .push(Error::AddressMissingRegister { ins: ins.clone() }.into());
+            self.diagnostics.push(
+                Error::AddressMissingRegister {
+                    ins: ins.clone(),
+                    span: self.current_span,
+                }
+                .into(),
+            );
             return Err(Failure::Skip);
         }
 
         // if there is more than one register specified in the address we have a problem
-        if address
-            .to
-            .iter()
-            .filter(|(i, _)| i.as_reg().is_some())
-            .skip(1)
-            .next()
-            .is_some()
-        {
-            self.diagnostics
-                .push(Error::AddressTooManyRegisters { ins: ins.clone() }.into());
+        if address.to.registers().nth(1).is_some() {
+            self.diagnostics.push(
+                Error::AddressTooManyRegisters {
+                    ins: ins.clone(),
+                    span: self.current_span,
+                }
+                .into(),
+            );
             return Err(Failure::Skip);
         }
 
-        // unwrap is safe because we check is_const before
-        let register = address
-            .to
-            .iter()
-            .find_map(|(item, _)| item.as_reg().copied())
-            .unwrap();
-
-        let address = address
-            .to
-            .iter()
-            .try_fold((0i8, None), |(mut addr, oper), (item, next_oper)| {
-                let value = match item {
-                    AddressItem::Var(v) => self.get_var_address(v, ins)?,
-                    AddressItem::Lit(b) => b.0,
-                    AddressItem::Reg(_) => 0,
-                };
-                match oper {
-                    Some(Oper::Add) => match addr.checked_add(value) {
-                        Some(v) => addr = v,
-                        None => {
-                            self.diagnostics
-                                .push(Error::AddressOOB { ins: ins.clone() }.into());
-                            return Err(Failure::Skip);
-                        }
-                    },
-                    Some(Oper::Sub) => match addr.checked_sub(value) {
-                        Some(v) => addr = v,
-                        None => {
-                            self.diagnostics
-                                .push(Error::AddressOOB { ins: ins.clone() }.into());
-                            return Err(Failure::Skip);
-                        }
-                    },
-                    _ => addr = value,
+        self.check_memory_space(address, space, ins)?;
+        let folded = self.fold(&address.to, Some(space))?;
+        let Some(register) = folded.offset_register() else {
+            self.diagnostics.push(
+                Error::RegisterNotAdded {
+                    expr: address.to.clone(),
+                    span: self.current_span,
                 }
-                Result::Ok((addr, next_oper.copied()))
-            })?
-            .0;
+                .into(),
+            );
+            return Err(Failure::Skip);
+        };
 
-        if address < DATA_ADDR_MIN || address > DATA_ADDR_MAX {
-            self.diagnostics
-                .push(Warning::AddressOOBPossible(ins.clone()).into());
+        let value = folded.to_value();
+        if !self.is_import(&value) && !space.contains(folded.value) {
+            self.diagnostics.push(
+                Warning::AddressOOBPossible {
+                    ins: ins.clone(),
+                    span: self.current_span,
+                }
+                .into(),
+            );
         }
 
-        Ok((address, register))
+        Ok((value, register))
     }
 
-    fn validate_variables(&mut self) -> Result<Vec<i281_ir::Variable>> {
-        let vars_end = self
-            .variables
-            .last()
-            .map(|v| v.data_addr + v.value.size_of());
-        if let Some(found) = vars_end.filter(|end| *end > DATA_ADDR_MAX as usize) {
+    fn validate_variables(&mut self) -> Vec<SymbolicVariable> {
+        // the parser only places variables at positive addresses but an ast can come from elsewhere
+        let mut misplaced = false;
+        for var in self.variables.iter() {
+            let Some(Placement::Org(addr) | Placement::At(addr)) = &var.placement else {
+                continue;
+            };
+            if !MemorySpace::Data.contains(addr.0.into()) {
+                misplaced = true;
+                self.diagnostics.push(
+                    Error::PlacementOOB {
+                        name: var.ident.clone(),
+                        data_addr: addr.0,
+                        span: var.span,
+                    }
+                    .into(),
+                );
+            }
+        }
+
+        let var_end = |v: &Variable| v.data_addr.saturating_add(v.value.size_of());
+        let vars_end = self.variables.iter().map(var_end).max();
+        if misplaced {
+            // the variables after a bad `.org` are also out of bounds, that is already reported
+        } else if let Some(found) = vars_end.filter(|end| *end > DATA_ADDR_MAX as usize) {
+            let overflowing = self
+                .variables
+                .iter()
+                .find(|v| var_end(v) > DATA_ADDR_MAX as usize);
             self.diagnostics.push(
                 Error::TooMuchUserData {
                     found,
                     max: DATA_ADDR_MAX as usize,
+                    span: overflowing.and_then(|v| v.span),
                 }
                 .into(),
             );
             // push a diagnostic but do nothing as we want to continue compiling
+        } else if let Some(used) = vars_end.filter(|end| nearly_full(*end, DATA_ADDR_MAX as usize))
+        {
+            self.diagnostics.push(
+                Warning::MemoryNearlyFull {
+                    memory: "data",
+                    used,
+                    max: DATA_ADDR_MAX as usize,
+                }
+                .into(),
+            );
         };
 
-        let mut unique = HashSet::with_capacity(self.variables.len());
+        // placing variables with `.org` or `@` can put two of them in the same place
+        for (i, var) in self.variables.iter().enumerate() {
+            let range = var.data_addr..var_end(var);
+            let overlapping = self.variables[..i]
+                .iter()
+                .find(|other| other.data_addr < range.end && range.start < var_end(other));
+            if let Some(other) = overlapping {
+                self.diagnostics.push(
+                    Error::OverlappingVariables {
+                        name: var.ident.clone(),
+                        other: other.ident.clone(),
+                        data_addr: range.start.max(other.data_addr),
+                        span: var.span,
+                        other_span: other.span,
+                    }
+                    .into(),
+                );
+            }
+        }
+
+        // the first definition of a name wins, a redefinition still takes up data memory so the
+        // addresses of the variables after it don't change
+        let mut first_defined = HashMap::with_capacity(self.variables.len());
         let mut vars = Vec::with_capacity(self.variables.len());
-        for var in self.variables.iter() {
-            if !unique.insert(&var.ident) {
-                return Err(Failure::NonUniqueVariable(var.clone()));
+        for (index, var) in self.variables.iter().enumerate() {
+            if let Some(first) = first_defined.get(&var.ident) {
+                self.diagnostics.push(
+                    Error::NonUniqueVariable {
+                        name: var.ident.clone(),
+                        span: var.span,
+                        first: *first,
+                    }
+                    .into(),
+                );
+            } else {
+                first_defined.insert(&var.ident, var.span);
+                if !var.ident.is_valid() {
+                    self.diagnostics.push(
+                        Error::InvalidIdent {
+                            name: var.ident.clone(),
+                        }
+                        .into(),
+                    );
+                } else if let Some(diagnostic) =
+                    reserved_name(&var.ident, "var", var.span, &self.variables, &self.labels)
+                {
+                    self.diagnostics.push(diagnostic);
+                }
+            }
+            if var.value.is_nested() {
+                self.diagnostics.push(
+                    Error::NestedArray {
+                        name: var.ident.clone(),
+                    }
+                    .into(),
+                );
+                continue;
+            }
+            if var.value.size_of() > DATA_ADDR_MAX as usize {
+                continue; // already reported, repetitions can be too big to write out
+            }
+
+            // expressions are folded here as they can refer to any variable or label
+            let values = var.value.elements();
+            let mut data = Vec::with_capacity(values.len());
+            for value in values {
+                data.push(match value {
+                    // the parser checks this but an ast can also come from elsewhere
+                    Literal::Byte(byte) if !byte.in_range() => {
+                        self.diagnostics.push(
+                            Error::ByteOutOfRange {
+                                name: var.ident.clone(),
+                                value: byte.0,
+                                span: var.span,
+                            }
+                            .into(),
+                        );
+                        Value::constant(0)
+                    }
+                    Literal::Byte(byte) => Value::constant(byte.as_i8().into()),
+                    Literal::Expr(literal::Expr(expr)) if !expr.is_const() => {
+                        self.diagnostics.push(
+                            Error::ConstantWithRegister {
+                                expr: expr.clone(),
+                                span: var.span,
+                            }
+                            .into(),
+                        );
+                        Value::constant(0)
+                    }
+                    Literal::Expr(literal::Expr(expr)) => {
+                        match self.fold_expr(expr, var.span, None) {
+                            Ok(folded) => {
+                                self.diagnostics
+                                    .extend(ambiguous_byte(expr, &folded, var.span));
+                                folded.to_value()
+                            }
+                            Err(err) => {
+                                self.diagnostics.push(err.into());
+                                Value::constant(0)
+                            }
+                        }
+                    }
+                    Literal::NotSet(..) | Literal::Array(..) | Literal::Dup(..) => {
+                        Value::constant(0)
+                    }
+                });
             }
-            vars.push(i281_ir::Variable::from(var));
+            vars.push(SymbolicVariable {
+                name: var.ident.to_string(),
+                data_addr: var.data_addr,
+                data,
+                fixed: is_fixed(&self.variables, index),
+            });
         }
 
-        Ok(vars)
+        vars
     }
 
-    fn validate_labels(&mut self) -> Result<()> {
-        let mut unique = HashSet::new();
-        for label in self.labels.iter() {
-            if !unique.insert(&label.ident) {
-                return Err(Failure::NonUniqueLabel(label.clone()));
+    fn validate_labels(&mut self) {
+        // the first definition of a label wins, jumps always go to it. numeric labels can be
+        // defined many times and local labels only have to be unique within their scope
+        let mut first_defined = HashMap::new();
+        let qualified_names = Label::qualified_names(&self.labels);
+        for (label, qualified) in self.labels.iter().zip(qualified_names) {
+            if label.kind() != LabelKind::Numeric {
+                if let Some(first) = first_defined.get(&qualified) {
+                    self.diagnostics.push(
+                        Error::NonUniqueLabel {
+                            name: qualified,
+                            span: label.span,
+                            first: *first,
+                        }
+                        .into(),
+                    );
+                    continue;
+                }
+                first_defined.insert(qualified, label.span);
+            }
+            if !label.has_valid_name() {
+                self.diagnostics.push(
+                    Error::InvalidIdent {
+                        name: label.ident.clone(),
+                    }
+                    .into(),
+                );
+            } else if label.kind() == LabelKind::Global {
+                if let Some(diagnostic) = reserved_name(
+                    &label.ident,
+                    "label",
+                    label.span,
+                    &self.variables,
+                    &self.labels,
+                ) {
+                    self.diagnostics.push(diagnostic);
+                }
+            }
+            if label.code_addr > self.instructions.len() {
+                self.diagnostics.push(
+                    Error::LabelOutOfRange {
+                        name: label.ident.clone(),
+                        code_addr: label.code_addr,
+                        len: self.instructions.len(),
+                    }
+                    .into(),
+                );
             }
         }
-        Ok(())
     }
 
     fn build_instruction(
         &mut self,
         index: usize,
         ins: &Instruction,
-    ) -> Result<i281_ir::Instruction> {
+    ) -> Result<i281_ir::Instruction<Value>> {
         Ok(match ins {
             Instruction::NoOp => NoOp,
             Instruction::InputC(addr) => InputC {
-                code_addr: self.get_address_value(addr, ins)?,
+                code_addr: self.get_address_value(addr, MemorySpace::Code, ins)?,
             },
             Instruction::InputCF(addr) => {
-                let (code_offset, rx) = self.get_address_value_with_offset(addr, ins)?;
+                let (code_offset, rx) =
+                    self.get_address_value_with_offset(addr, MemorySpace::Code, ins)?;
                 InputCF {
                     rx: rx.into(),
                     code_offset,
                 }
             }
             Instruction::InputD(addr) => InputD {
-                data_addr: self.get_address_value(addr, ins)?,
+                data_addr: self.get_address_value(addr, MemorySpace::Data, ins)?,
             },
             Instruction::InputDF(addr) => {
-                let (data_offset, rx) = self.get_address_value_with_offset(addr, ins)?;
+                let (data_offset, rx) =
+                    self.get_address_value_with_offset(addr, MemorySpace::Data, ins)?;
                 InputDF {
                     rx: rx.into(),
                     data_offset,
@@ -297,35 +784,37 @@ impl Analyzer {
                 rx: rx.into(),
                 ry: ry.into(),
             },
-            Instruction::LoadI(rx, Byte(val)) => LoadI {
+            Instruction::LoadI(rx, value) => LoadI {
                 rx: rx.into(),
-                value: *val,
+                value: self.get_immediate(value)?,
+            },
+            Instruction::LoadP(rx, pointer) => LoadI {
+                rx: rx.into(),
+                value: self.get_pointer(pointer)?,
             },
-            Instruction::LoadP(_, _) => {
-                unimplemented!("the compiler is not yet built to handle pointer operations")
-            }
             Instruction::Add(rx, ry) => Add {
                 rx: rx.into(),
                 ry: ry.into(),
             },
-            Instruction::AddI(rx, Byte(val)) => AddI {
+            Instruction::AddI(rx, value) => AddI {
                 rx: rx.into(),
-                value: *val,
+                value: self.get_immediate(value)?,
             },
             Instruction::Sub(rx, ry) => Sub {
                 rx: rx.into(),
                 ry: ry.into(),
             },
-            Instruction::SubI(rx, Byte(val)) => SubI {
+            Instruction::SubI(rx, value) => SubI {
                 rx: rx.into(),
-                value: *val,
+                value: self.get_immediate(value)?,
             },
             Instruction::Load(rx, addr) => Load {
                 rx: rx.into(),
-                data_addr: self.get_address_value(addr, ins)?,
+                data_addr: self.get_address_value(addr, MemorySpace::Data, ins)?,
             },
             Instruction::LoadF(rx, addr) => {
-                let (data_offset, ry) = self.get_address_value_with_offset(addr, ins)?;
+                let (data_offset, ry) =
+                    self.get_address_value_with_offset(addr, MemorySpace::Data, ins)?;
                 LoadF {
                     rx: rx.into(),
                     ry: ry.into(),
@@ -333,11 +822,12 @@ impl Analyzer {
                 }
             }
             Instruction::Store(addr, rx) => Store {
-                data_addr: self.get_address_value(addr, ins)?,
+                data_addr: self.get_address_value(addr, MemorySpace::Data, ins)?,
                 rx: rx.into(),
             },
             Instruction::StoreF(addr, rx) => {
-                let (data_offset, ry) = self.get_address_value_with_offset(addr, ins)?;
+                let (data_offset, ry) =
+                    self.get_address_value_with_offset(addr, MemorySpace::Data, ins)?;
                 StoreF {
                     ry: ry.into(),
                     data_offset,
@@ -357,23 +847,23 @@ impl Analyzer {
                 ry: ry.into(),
             },
             Instruction::Jump(ident) => Jump {
-                pc_offset: self.get_label_offset(index, ident, ins)?,
+                pc_offset: self.get_label_target(index, ident, ins)?,
             },
             Instruction::BrE(ident) => Branch {
                 cond: BranchCondition::Equal,
-                pc_offset: self.get_label_offset(index, ident, ins)?,
+                pc_offset: self.get_label_target(index, ident, ins)?,
             },
             Instruction::BrNE(ident) => Branch {
                 cond: BranchCondition::NotEqual,
-                pc_offset: self.get_label_offset(index, ident, ins)?,
+                pc_offset: self.get_label_target(index, ident, ins)?,
             },
             Instruction::BrG(ident) => Branch {
                 cond: BranchCondition::Greater,
-                pc_offset: self.get_label_offset(index, ident, ins)?,
+                pc_offset: self.get_label_target(index, ident, ins)?,
             },
             Instruction::BrGE(ident) => Branch {
                 cond: BranchCondition::GreaterEqual,
-                pc_offset: self.get_label_offset(index, ident, ins)?,
+                pc_offset: self.get_label_target(index, ident, ins)?,
             },
         })
     }
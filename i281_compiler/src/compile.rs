@@ -0,0 +1,1084 @@
+use i281_ast::Root;
+use i281_ir::{Ir, Object, SymbolicIr};
+
+use crate::{
+    compile_verilog, validate_ir, Analyzer, CycleEstimate, Diagnostic, Error, LintLevels, Listing,
+    Pragma, Report, VerilogOutput,
+};
+
+/// The artifacts that [`compile`] can produce
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EmitKind {
+    Ast,
+    Ir,
+    Verilog,
+    Listing,
+    Report,
+    Cycles,
+    /// A module that is linked with others, names it does not define are imported
+    Object,
+}
+
+/// The format machine code is produced in
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Target {
+    /// The three verilog modules the i281 quartus project expects
+    #[default]
+    Verilog,
+}
+
+#[derive(Clone, Debug)]
+pub struct CompileOptions {
+    pub emit: Vec<EmitKind>,
+    pub target: Target,
+    pub lints: LintLevels,
+    /// The name an object is given, its private labels are qualified with it when linking
+    pub module_name: String,
+}
+
+impl CompileOptions {
+    pub fn emits(&self, kind: EmitKind) -> bool {
+        self.emit.contains(&kind)
+    }
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        Self {
+            emit: vec![EmitKind::Verilog],
+            target: Target::default(),
+            lints: LintLevels::default(),
+            module_name: "main".into(),
+        }
+    }
+}
+
+/// A single generated verilog module
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerilogModule {
+    pub name: String,
+    pub source: String,
+}
+
+/// Everything produced by [`compile`]
+///
+/// An artifact is only present if it was requested and compilation got far enough to produce it.
+/// The ir, verilog, listing and cycle estimate are never produced when an error was encountered,
+/// the report is produced whenever the source could be parsed. An object that imports names has
+/// no addresses for them yet so nothing after the object is produced for it.
+#[derive(Clone, Debug, Default)]
+pub struct CompileOutput {
+    pub ast: Option<Root>,
+    /// The ir as it came out of the analyzer, operands still refer to labels and variables
+    pub symbolic_ir: Option<SymbolicIr>,
+    /// The ir with every symbol resolved
+    pub ir: Option<Ir>,
+    pub object: Option<Object>,
+    pub verilog: Vec<VerilogModule>,
+    pub listing: Option<Listing>,
+    pub report: Option<Report>,
+    pub cycles: Option<CycleEstimate>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl CompileOutput {
+    /// Returns true if any of the diagnostics prevented compilation from succeeding
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(Diagnostic::is_error)
+    }
+}
+
+/// Compile assembly source text entirely in memory
+///
+/// This never touches the filesystem, all artifacts and diagnostics are returned in the output.
+/// Lint pragmas in the source comments are honoured.
+pub fn compile(source: &str, options: &CompileOptions) -> CompileOutput {
+    match Root::parse(source) {
+        Ok(ast) => {
+            let (pragmas, diagnostics) = Pragma::find_all(source, &ast.code.instruction_spans);
+            analyze_ast(ast, options, &pragmas, diagnostics)
+        }
+        Err(err) => CompileOutput {
+            diagnostics: vec![Diagnostic::Fatal(err.into())],
+            ..Default::default()
+        },
+    }
+}
+
+/// Compile an ast that was not necessarily produced by the parser
+///
+/// Variables are laid out again from their declaration order so the result is the same as if the
+/// ast had been printed and parsed.
+pub fn compile_ast(ast: Root, options: &CompileOptions) -> CompileOutput {
+    analyze_ast(ast, options, &[], Vec::new())
+}
+
+fn analyze_ast(
+    mut ast: Root,
+    options: &CompileOptions,
+    pragmas: &[Pragma],
+    mut diagnostics: Vec<Diagnostic>,
+) -> CompileOutput {
+    let mut output = CompileOutput::default();
+
+    if let Some(data) = ast.data.as_mut() {
+        data.assign_addresses();
+    }
+    if options.emits(EmitKind::Ast) {
+        output.ast = Some(ast.clone());
+    }
+    if options.emits(EmitKind::Report) {
+        output.report = Some(Report::new(&ast));
+    }
+
+    let listing_ast = options.emits(EmitKind::Listing).then(|| ast.clone());
+    let labels = options
+        .emits(EmitKind::Cycles)
+        .then(|| ast.code.labels.clone());
+    let analyzer = Analyzer::new(ast).allow_imports(options.emits(EmitKind::Object));
+    let symbolic_ir = match analyzer.validate() {
+        Ok((ir, found)) => {
+            diagnostics.extend(found);
+            ir
+        }
+        Err(failure) => {
+            diagnostics.push(Diagnostic::Fatal(failure));
+            output.diagnostics = options.lints.apply(diagnostics, pragmas);
+            return output;
+        }
+    };
+    output.diagnostics = options.lints.apply(diagnostics, pragmas);
+    if output.has_errors() {
+        return output;
+    }
+    if options.emits(EmitKind::Object) {
+        let object = Object::new(options.module_name.clone(), symbolic_ir.clone());
+        let imports = !object.imports.is_empty();
+        output.object = Some(object);
+        if imports {
+            return output;
+        }
+    }
+    let Some(ir) = resolve(symbolic_ir, options, &mut output) else {
+        return output;
+    };
+
+    if let Some(ast) = listing_ast {
+        output.listing = Some(Listing::new(&ast, &ir));
+    }
+    let mut output = emit_ir(ir, options, output);
+    if let (Some(cycles), Some(labels)) = (output.cycles.as_mut(), labels) {
+        cycles.name_loops(&labels);
+    }
+    output
+}
+
+/// Resolve the symbols of an ir, a failure is added to the diagnostics of the output
+fn resolve(ir: SymbolicIr, options: &CompileOptions, output: &mut CompileOutput) -> Option<Ir> {
+    match ir.resolve() {
+        Ok(resolved) => {
+            if options.emits(EmitKind::Ir) {
+                output.symbolic_ir = Some(ir);
+            }
+            Some(resolved)
+        }
+        Err(err) => {
+            output.diagnostics.push(Error::from(err).into());
+            None
+        }
+    }
+}
+
+/// Compile a symbolic ir that was not necessarily produced by the analyzer
+///
+/// There is no source to create a listing from so one is never produced.
+pub fn compile_symbolic_ir(ir: SymbolicIr, options: &CompileOptions) -> CompileOutput {
+    let mut output = CompileOutput::default();
+    let Some(ir) = resolve(ir, options, &mut output) else {
+        return output;
+    };
+    let mut resolved = compile_ir(ir, options);
+    if !resolved.has_errors() {
+        resolved.symbolic_ir = output.symbolic_ir;
+    }
+    resolved
+}
+
+/// Compile an ir that was not necessarily produced by the analyzer
+///
+/// There is no source to create a listing from so one is never produced.
+pub fn compile_ir(ir: Ir, options: &CompileOptions) -> CompileOutput {
+    let output = CompileOutput {
+        diagnostics: validate_ir(&ir),
+        ..Default::default()
+    };
+    if output.has_errors() {
+        return output;
+    }
+    emit_ir(ir, options, output)
+}
+
+fn emit_ir(ir: Ir, options: &CompileOptions, mut output: CompileOutput) -> CompileOutput {
+    if options.emits(EmitKind::Cycles) {
+        output.cycles = Some(CycleEstimate::new(&ir));
+    }
+    if options.emits(EmitKind::Verilog) {
+        output.verilog = match options.target {
+            Target::Verilog => verilog_modules(ir.clone()),
+        };
+    }
+    if options.emits(EmitKind::Ir) {
+        output.ir = Some(ir);
+    }
+
+    output
+}
+
+fn verilog_modules(ir: Ir) -> Vec<VerilogModule> {
+    const NAMES: [&str; 3] = ["User_Code_Low", "User_Code_High", "User_Data"];
+
+    let (mut code_low, mut code_high, mut data) = (Vec::new(), Vec::new(), Vec::new());
+    compile_verilog(
+        (
+            VerilogOutput::new(NAMES[0], &mut code_low),
+            VerilogOutput::new(NAMES[1], &mut code_high),
+            VerilogOutput::new(NAMES[2], &mut data),
+        ),
+        ir,
+    )
+    .expect("the ir was validated and writing to a vec never fails");
+
+    NAMES
+        .into_iter()
+        .zip([code_low, code_high, data])
+        .map(|(name, source)| VerilogModule {
+            name: name.to_owned(),
+            source: String::from_utf8(source).expect("verilog output is always ascii"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use i281_ast::{literal::Byte, Label, Literal, Placement, Root};
+
+    use super::{compile, compile_ast, compile_symbolic_ir, CompileOptions, EmitKind};
+    use crate::{Diagnostic, Error, MemorySpace, Warning};
+
+    const SOURCE: &str = "
+.data
+x BYTE 2
+y BYTE ?
+.code
+    LOAD A, [x]
+loop: ADDI A, 1
+    STORE [y], A
+    JUMP loop
+";
+
+    #[test]
+    fn compile1() {
+        let output = compile(SOURCE, &CompileOptions::default());
+        assert!(!output.has_errors());
+        assert!(output.ast.is_none());
+        assert!(output.ir.is_none());
+        let names: Vec<_> = output.verilog.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["User_Code_Low", "User_Code_High", "User_Data"]);
+        assert!(output.verilog[2]
+            .source
+            .contains("assign b0I[7:0] = 8'b00000010;"));
+    }
+
+    #[test]
+    fn compile2() {
+        let options = CompileOptions {
+            emit: vec![EmitKind::Ast, EmitKind::Ir, EmitKind::Listing],
+            ..Default::default()
+        };
+        let output = compile(SOURCE, &options);
+        assert!(output.ast.is_some());
+        assert_eq!(output.ir.unwrap().instructions.len(), 4);
+        assert!(output.verilog.is_empty());
+        let listing = output.listing.unwrap();
+        assert_eq!(listing.code[1].labels, ["loop".into()]);
+        assert_eq!(listing.code[3].source, "JUMP loop");
+    }
+
+    #[test]
+    fn compile3() {
+        let options = CompileOptions {
+            emit: vec![EmitKind::Verilog, EmitKind::Report],
+            ..Default::default()
+        };
+        let output = compile(".code\n    JUMP nowhere\n", &options);
+        assert!(output.has_errors());
+        assert!(output.verilog.is_empty());
+        assert_eq!(output.report.unwrap().instructions, 1);
+
+        let output = compile(
+            ".code\n    NOT AN INSTRUCTION\n",
+            &CompileOptions::default(),
+        );
+        assert!(output.has_errors());
+    }
+
+    #[test]
+    fn compile4() {
+        let source = ".data\nx BYTE 1\nx BYTE 2\n.code\nl: LOAD A, [x]\nl: NOOP\n    JUMP l\n";
+        let options = CompileOptions {
+            emit: vec![EmitKind::Ir],
+            ..Default::default()
+        };
+        let output = compile(source, &options);
+        let [Diagnostic::Error(Error::NonUniqueLabel { span, first, .. }), Diagnostic::Error(Error::NonUniqueVariable { .. })] =
+            output.diagnostics.as_slice()
+        else {
+            panic!("unexpected diagnostics: {:?}", output.diagnostics);
+        };
+        assert_eq!(
+            source[span.unwrap().offset()..].lines().next(),
+            Some("l: NOOP")
+        );
+        assert_eq!(
+            source[first.unwrap().offset()..].lines().next(),
+            Some("l: LOAD A, [x]")
+        );
+        assert!(output.ir.is_none());
+    }
+
+    #[test]
+    fn compile5() {
+        let mut source =
+            String::from(".data\nx BYTE 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14\n.code\n");
+        source.push_str(&"    NOOP\n".repeat(29));
+        let output = compile(&source, &CompileOptions::default());
+        assert!(matches!(
+            output.diagnostics.as_slice(),
+            [Diagnostic::Warning(Warning::MemoryNearlyFull {
+                memory: "code",
+                used: 29,
+                max: 32
+            })]
+        ));
+        assert!(!output.has_errors());
+    }
+
+    #[test]
+    fn compile6() {
+        let source = "
+.data
+arr BYTE 1, 2, 3
+len BYTE end - arr
+last BYTE arr + (3 - 1)
+.code
+    LOADI A, len * 4 | 1
+    LOADF B, [arr + (len - 1) + C]
+end: SUBI A, -(1 << 2)
+";
+        let options = CompileOptions {
+            emit: vec![EmitKind::Ir],
+            ..Default::default()
+        };
+        let output = compile(source, &options);
+        assert!(output.diagnostics.is_empty(), "{:?}", output.diagnostics);
+        let ir = output.ir.unwrap();
+        assert_eq!(ir.variables[1].data, [2]);
+        assert_eq!(ir.variables[2].data, [2]);
+        assert!(matches!(
+            ir.instructions.as_slice(),
+            [
+                i281_ir::Instruction::LoadI { value: 13, .. },
+                i281_ir::Instruction::LoadF { data_offset: 2, .. },
+                i281_ir::Instruction::SubI { value: -4, .. },
+            ]
+        ));
+    }
+
+    #[test]
+    fn compile7() {
+        let source =
+            ".data\ny BYTE 1\nx BYTE 9\n.code\n    LOADI A, x * 20 + 250\n    STORE [x * B], A\n";
+        let output = compile(source, &CompileOptions::default());
+        match output.diagnostics.as_slice() {
+            [Diagnostic::Error(Error::ExprOverflow {
+                expr, value, steps, ..
+            }), Diagnostic::Error(Error::AddressWithRegister { .. })] => {
+                assert_eq!(expr.to_string(), "x*20+250");
+                assert_eq!(*value, 270);
+                assert_eq!(steps.as_deref(), Some("where x = 1, x*20 = 20"));
+            }
+            other => panic!("unexpected diagnostics {other:?}"),
+        }
+
+        let options = CompileOptions {
+            emit: vec![EmitKind::Ir],
+            ..Default::default()
+        };
+        let output = compile(".code\n    LOADI A, 100 + 100\n", &options);
+        assert!(matches!(
+            output.ir.unwrap().instructions.as_slice(),
+            [i281_ir::Instruction::LoadI { value: -56, .. }]
+        ));
+    }
+
+    #[test]
+    fn compile8() {
+        let source = "
+.data
+x BYTE 1
+.code
+    INPUTC [patch + 1]
+    INPUTCF [patch + A]
+    INPUTC [31]
+    LOAD A, [15]
+patch: NOOP
+";
+        let options = CompileOptions {
+            emit: vec![EmitKind::Ir],
+            ..Default::default()
+        };
+        let output = compile(source, &options);
+        assert!(output.diagnostics.is_empty(), "{:?}", output.diagnostics);
+        assert!(matches!(
+            output.ir.unwrap().instructions.as_slice(),
+            [
+                i281_ir::Instruction::InputC { code_addr: 5 },
+                i281_ir::Instruction::InputCF { code_offset: 4, .. },
+                i281_ir::Instruction::InputC { code_addr: 31 },
+                i281_ir::Instruction::Load { data_addr: 15, .. },
+                i281_ir::Instruction::NoOp,
+            ]
+        ));
+
+        let source = ".data\nx BYTE 1\n.code\nl: INPUTC [x]\n    LOAD A, [l]\n    INPUTC [32]\n    LOAD A, [16]\n";
+        let output = compile(source, &options);
+        assert!(matches!(
+            output.diagnostics.as_slice(),
+            [
+                Diagnostic::Error(Error::WrongMemorySpace {
+                    found: MemorySpace::Data,
+                    expected: MemorySpace::Code,
+                    ..
+                }),
+                Diagnostic::Error(Error::WrongMemorySpace {
+                    found: MemorySpace::Code,
+                    expected: MemorySpace::Data,
+                    ..
+                }),
+                Diagnostic::Error(Error::AddressOOB { .. }),
+                Diagnostic::Error(Error::AddressOOB { .. }),
+            ]
+        ));
+    }
+
+    #[test]
+    fn compile9() {
+        let source = "
+.data
+    arr BYTE 1, 2
+    out BYTE 5 @ 0
+.org 6
+    last BYTE -1
+.code
+    LOAD A, [last]
+";
+        let output = compile(source, &CompileOptions::default());
+        assert!(output.diagnostics.is_empty(), "{:?}", output.diagnostics);
+        let data = &output.verilog[2].source;
+        let expected = [
+            (0, "00000101"),
+            (1, "00000001"),
+            (2, "00000010"),
+            (5, "00000000"),
+            (6, "11111111"),
+        ];
+        for (index, byte) in expected {
+            assert!(data.contains(&format!("assign b{index}I[7:0] = 8'b{byte};")));
+        }
+
+        let source = ".data\nx BYTE 1, 2\n.org 1\ny BYTE 3\n.code\nNOOP\n";
+        let output = compile(source, &CompileOptions::default());
+        assert!(matches!(
+            output.diagnostics.as_slice(),
+            [Diagnostic::Error(Error::OverlappingVariables {
+                data_addr: 1,
+                ..
+            })]
+        ));
+    }
+
+    #[test]
+    fn compile10() {
+        let source = ".data\nbuf BYTE 3 DUP(?)\nrows BYTE 2 DUP(buf, 2 DUP(-1))\n.code\nNOOP\n";
+        let options = CompileOptions {
+            emit: vec![EmitKind::Ir],
+            ..Default::default()
+        };
+        let output = compile(source, &options);
+        assert!(output.diagnostics.is_empty(), "{:?}", output.diagnostics);
+        let ir = output.ir.unwrap();
+        assert_eq!(ir.variables[0].data, [0, 0, 0]);
+        assert_eq!(ir.variables[1].data_addr, 3);
+        assert_eq!(ir.variables[1].data, [0, -1, -1, 0, -1, -1]);
+
+        let source = ".data\nbig BYTE 100 DUP(100 DUP(100 DUP(0)))\n.code\nNOOP\n";
+        let output = compile(source, &options);
+        assert!(matches!(
+            output.diagnostics.as_slice(),
+            [Diagnostic::Error(Error::TooMuchUserData {
+                found: 1_000_000,
+                ..
+            })]
+        ));
+    }
+
+    #[test]
+    fn compile11() {
+        let source = "
+.code
+sort: LOADI A, 3
+1: SUBI A, 1
+.loop: BRNZ 1f
+    JUMP 1b
+1: JUMP sort.loop
+fill: NOOP
+.loop: BRE .loop
+";
+        let options = CompileOptions {
+            emit: vec![EmitKind::Ir],
+            ..Default::default()
+        };
+        let output = compile(source, &options);
+        assert!(output.diagnostics.is_empty(), "{:?}", output.diagnostics);
+        assert!(matches!(
+            output.ir.unwrap().instructions.as_slice(),
+            [
+                i281_ir::Instruction::LoadI { .. },
+                i281_ir::Instruction::SubI { .. },
+                i281_ir::Instruction::Branch { pc_offset: 1, .. },
+                i281_ir::Instruction::Jump { pc_offset: -3 },
+                i281_ir::Instruction::Jump { pc_offset: -3 },
+                i281_ir::Instruction::NoOp,
+                i281_ir::Instruction::Branch { pc_offset: -1, .. },
+            ]
+        ));
+
+        let source = ".code
+main: NOOP
+.loop: JUMP .lop
+1: JUMP 1f
+";
+        let output = compile(source, &CompileOptions::default());
+        match output.diagnostics.as_slice() {
+            [Diagnostic::Error(Error::LabelNotFound { help: a, .. }), Diagnostic::Error(Error::LabelNotFound { help: b, .. })] =>
+            {
+                assert_eq!(a, "did you mean `.loop`?");
+                assert_eq!(b, "did you mean `1b`?");
+            }
+            other => panic!("unexpected diagnostics {other:?}"),
+        }
+    }
+
+    #[test]
+    fn compile12() {
+        let source = "
+.data
+size BYTE end - start
+.code
+start:
+main: LOADI A, end
+halt: JUMP end
+end:
+";
+        let options = CompileOptions {
+            emit: vec![EmitKind::Ir],
+            ..Default::default()
+        };
+        let output = compile(source, &options);
+        assert!(output.diagnostics.is_empty(), "{:?}", output.diagnostics);
+        let ir = output.ir.unwrap();
+        assert_eq!(ir.variables[0].data, [2]);
+        assert!(matches!(
+            ir.instructions.as_slice(),
+            [
+                i281_ir::Instruction::LoadI { value: 2, .. },
+                i281_ir::Instruction::Jump { pc_offset: 0 },
+            ]
+        ));
+    }
+
+    #[test]
+    fn compile13() {
+        let source = ".data\nb BYTE 1\nb_var BYTE 2\ndata BYTE 3\n.code\nloadi: NOOP\n";
+        let output = compile(source, &CompileOptions::default());
+        match output.diagnostics.as_slice() {
+            [Diagnostic::Warning(Warning::ReservedName {
+                kind: "opcode",
+                word: "LOADI",
+                rename: loadi,
+                ..
+            }), Diagnostic::Error(Error::RegisterName {
+                register: "B",
+                rename: b,
+                ..
+            }), Diagnostic::Warning(Warning::ReservedName {
+                kind: "keyword",
+                rename: data,
+                ..
+            })] => {
+                assert_eq!(b.as_str(), "b_var2");
+                assert_eq!(data.as_str(), "data_var");
+                assert_eq!(loadi.as_str(), "loadi_label");
+            }
+            other => panic!("unexpected diagnostics {other:?}"),
+        }
+    }
+
+    #[test]
+    fn compile14() {
+        let source = "
+.data
+arr BYTE 1, 2, 3
+last BYTE arr + 2
+.code
+1: LOADF A, [arr + 1 + B]
+main: LOADI B, last * 2
+.loop: BRNE 1b
+    JUMP main.loop
+";
+        let options = CompileOptions {
+            emit: vec![EmitKind::Ir],
+            ..Default::default()
+        };
+        let output = compile(source, &options);
+        assert!(output.diagnostics.is_empty(), "{:?}", output.diagnostics);
+        let symbolic = output.symbolic_ir.unwrap();
+        let names: Vec<_> = symbolic.labels.iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(names, ["1#0", "main", "main.loop"]);
+        assert_eq!(symbolic.variables[1].data[0].to_string(), "arr + 2");
+        let values: Vec<_> = symbolic
+            .instructions
+            .iter()
+            .map(|ins| ins.value().map(ToString::to_string))
+            .collect();
+        assert_eq!(
+            values,
+            [
+                Some("arr + 1".into()),
+                Some("6".into()),
+                Some("1#0".into()),
+                Some("main.loop".into()),
+            ]
+        );
+        assert!(matches!(
+            output.ir.unwrap().instructions.as_slice(),
+            [
+                i281_ir::Instruction::LoadF { data_offset: 1, .. },
+                i281_ir::Instruction::LoadI { value: 6, .. },
+                i281_ir::Instruction::Branch { pc_offset: -3, .. },
+                i281_ir::Instruction::Jump { pc_offset: -2 },
+            ]
+        ));
+    }
+
+    fn object(source: &str, name: &str) -> i281_ir::Object {
+        let options = CompileOptions {
+            emit: vec![EmitKind::Object, EmitKind::Verilog],
+            module_name: name.into(),
+            ..Default::default()
+        };
+        let output = compile(source, &options);
+        assert!(!output.has_errors(), "{:?}", output.diagnostics);
+        let object = output.object.unwrap();
+        // nothing that needs the address of an import can be produced before linking
+        assert_eq!(output.verilog.is_empty(), !object.imports.is_empty());
+        object
+    }
+
+    #[test]
+    fn compile15() {
+        let io = "
+.data
+input BYTE ?, ?
+.code
+    INPUTD [input]
+    INPUTD [input + 1]
+1:  JUMP sum
+";
+        let sum = "
+.data
+total BYTE ?
+.code
+sum: LOAD A, [input]
+    LOAD B, [input + 1]
+    ADD A, B
+    STORE [total], A
+1:  JUMP 1b
+";
+        let objects = [object(io, "io"), object(sum, "sum")];
+        assert_eq!(objects[0].imports, ["sum"]);
+        assert_eq!(objects[1].imports, ["input"]);
+
+        let linked = i281_ir::link(&objects).unwrap();
+        let options = CompileOptions {
+            emit: vec![EmitKind::Ir, EmitKind::Verilog],
+            ..Default::default()
+        };
+        let output = compile_symbolic_ir(linked, &options);
+        assert!(!output.has_errors(), "{:?}", output.diagnostics);
+        assert_eq!(output.verilog.len(), 3);
+        let ir = output.ir.unwrap();
+        assert_eq!(ir.data_memory().unwrap(), [0, 0, 0]);
+        assert!(matches!(
+            ir.instructions.as_slice(),
+            [
+                i281_ir::Instruction::InputD { data_addr: 0 },
+                i281_ir::Instruction::InputD { data_addr: 1 },
+                i281_ir::Instruction::Jump { pc_offset: 0 },
+                i281_ir::Instruction::Load { data_addr: 0, .. },
+                i281_ir::Instruction::Load { data_addr: 1, .. },
+                i281_ir::Instruction::Add { .. },
+                i281_ir::Instruction::Store { data_addr: 2, .. },
+                i281_ir::Instruction::Jump { pc_offset: -1 },
+            ]
+        ));
+    }
+
+    #[test]
+    fn compile16() {
+        let options = CompileOptions {
+            emit: vec![EmitKind::Object, EmitKind::Verilog],
+            ..Default::default()
+        };
+        let output = compile(".code\n    LOADI A, ext * 2\n", &options);
+        assert!(matches!(
+            output.diagnostics.as_slice(),
+            [Diagnostic::Error(Error::ImportNotAdded { name, .. })] if name.as_str() == "ext"
+        ));
+
+        // an object that imports nothing can still be compiled on its own
+        let output = compile(SOURCE, &options);
+        assert!(output.object.unwrap().imports.is_empty());
+        assert_eq!(output.verilog.len(), 3);
+
+        // names are only imported when building an object
+        let output = compile(".code\n    JUMP ext\n", &CompileOptions::default());
+        assert!(matches!(
+            output.diagnostics.as_slice(),
+            [Diagnostic::Error(Error::LabelNotFound { .. })]
+        ));
+    }
+
+    #[test]
+    fn compile17() {
+        let source = ".data\nx BYTE 200, 100+100\n.code\n    LOADI A, -(-128)\n    LOADI B, 200\n";
+        let output = compile(source, &CompileOptions::default());
+        assert!(!output.has_errors());
+        let warnings: Vec<_> = output
+            .diagnostics
+            .iter()
+            .map(|d| match d {
+                Diagnostic::Warning(Warning::AmbiguousByte {
+                    expr,
+                    value,
+                    signed,
+                    ..
+                }) => (expr.to_string(), *value, *signed),
+                other => panic!("unexpected diagnostic: {other:?}"),
+            })
+            .collect();
+        // literals above 127 are already written as unsigned values
+        assert_eq!(
+            warnings,
+            [("100+100".into(), 200, -56), ("-(-128)".into(), 128, -128)]
+        );
+    }
+
+    #[test]
+    fn compile18() {
+        // the data of `count` moves to make room for `main`, the display stays where it is pinned
+        let main = "
+.data
+x BYTE 1, 2
+.code
+    LOAD A, [x + 1]
+    JUMP count
+";
+        let count = "
+.data
+digits BYTE 0, 0, 0, 0 @ 0
+n BYTE 7
+.code
+count: LOAD B, [n]
+    STORE [digits + 3], B
+    LOADI C, digits * 2
+1:  JUMP 1b
+";
+        let objects = [object(main, "main"), object(count, "count")];
+        assert!(objects[1].ir.variables[0].fixed);
+        assert_eq!(objects[1].ir.variables[1].data_addr, 4);
+
+        let linked = i281_ir::link(&objects).unwrap();
+        let options = CompileOptions {
+            emit: vec![EmitKind::Ir],
+            ..Default::default()
+        };
+        let output = compile_symbolic_ir(linked, &options);
+        assert!(!output.has_errors(), "{:?}", output.diagnostics);
+        let ir = output.ir.unwrap();
+        assert_eq!(ir.data_memory().unwrap(), [0, 0, 0, 0, 1, 2, 7]);
+        assert!(matches!(
+            ir.instructions.as_slice(),
+            [
+                i281_ir::Instruction::Load { data_addr: 5, .. },
+                i281_ir::Instruction::Jump { pc_offset: 0 },
+                i281_ir::Instruction::Load { data_addr: 6, .. },
+                i281_ir::Instruction::Store { data_addr: 3, .. },
+                i281_ir::Instruction::LoadI { value: 0, .. },
+                i281_ir::Instruction::Jump { pc_offset: -1 },
+            ]
+        ));
+
+        // two modules can not pin the same address
+        let other = object(
+            ".data
+seg BYTE 0 @ 2
+.code
+    NOOP
+",
+            "other",
+        );
+        let errors = i281_ir::link(&[objects[0].clone(), objects[1].clone(), other]).unwrap_err();
+        assert!(matches!(
+            errors.as_slice(),
+            [i281_ir::LinkError::OverlappingVariables { name, other, data_addr: 2, .. }]
+                if name == "seg" && other == "digits"
+        ));
+    }
+
+    #[test]
+    fn compile19() {
+        // linking moves labels and variables so only a constant can be added to them
+        let options = CompileOptions {
+            emit: vec![EmitKind::Object],
+            ..Default::default()
+        };
+        for (source, moved) in [
+            (
+                ".data
+x BYTE 1
+.code
+    LOADI B, x * 2
+",
+                "x",
+            ),
+            (
+                ".data
+x BYTE 1
+.code
+    LOADI B, x | 1
+",
+                "x",
+            ),
+            (
+                ".data
+x BYTE 1
+.code
+    LOADI B, x + (x & 1)
+",
+                "x",
+            ),
+            (
+                ".data
+x BYTE 1
+.code
+start: LOADI B, start - x
+",
+                "start",
+            ),
+            (
+                ".data
+x BYTE done - start
+.code
+start: NOOP
+done: NOOP
+",
+                "done",
+            ),
+        ] {
+            let output = compile(source, &options);
+            assert!(
+                matches!(
+                    output.diagnostics.as_slice(),
+                    [Diagnostic::Error(Error::MovedNotAdded { name, .. })] if name.as_str() == moved
+                ),
+                "{source}: {:?}",
+                output.diagnostics
+            );
+            // without linking the address is known
+            let output = compile(source, &CompileOptions::default());
+            assert!(!output.has_errors(), "{source}");
+        }
+
+        // a fixed variable keeps its address
+        let source = ".data
+x BYTE 1
+.org 4
+y BYTE 2
+.code
+    LOADI B, y * 2
+    LOADI C, x - 1
+";
+        let output = compile(source, &options);
+        assert!(!output.has_errors(), "{:?}", output.diagnostics);
+    }
+
+    #[test]
+    fn compile20() {
+        // `LOADP` loads the address of a variable like `LOADI`
+        let source =
+            ".data\nw BYTE 0\nx BYTE 1, 2\n.code\n    LOADP A, {x}\n    LOADP B, {x + 1}\n";
+        let options = CompileOptions {
+            emit: vec![EmitKind::Ir, EmitKind::Verilog],
+            ..Default::default()
+        };
+        let output = compile(source, &options);
+        assert!(!output.has_errors(), "{:?}", output.diagnostics);
+        assert!(matches!(
+            output.ir.unwrap().instructions.as_slice(),
+            [
+                i281_ir::Instruction::LoadI { value: 1, .. },
+                i281_ir::Instruction::LoadI { value: 2, .. },
+            ]
+        ));
+
+        // a pointer can only point into data memory
+        let output = compile(".code\nstart: LOADP A, {start}\n", &options);
+        assert!(matches!(
+            output.diagnostics.as_slice(),
+            [Diagnostic::Error(Error::SymbolNotFound { name, .. })] if name.as_str() == "start"
+        ));
+        assert!(output.verilog.is_empty());
+    }
+
+    #[test]
+    fn compile21() {
+        // `.in` is defined under both globals, each address uses the one in its own scope
+        let source = "
+.code
+main: INPUTC [.in]
+.in: NOOP
+    JUMP other
+other: INPUTC [.in + 1]
+    NOOP
+.in: NOOP
+    INPUTC [main.in]
+";
+        let options = CompileOptions {
+            emit: vec![EmitKind::Ir],
+            ..Default::default()
+        };
+        let output = compile(source, &options);
+        assert!(output.diagnostics.is_empty(), "{:?}", output.diagnostics);
+        assert!(matches!(
+            output.ir.unwrap().instructions.as_slice(),
+            [
+                i281_ir::Instruction::InputC { code_addr: 1 },
+                i281_ir::Instruction::NoOp,
+                i281_ir::Instruction::Jump { .. },
+                i281_ir::Instruction::InputC { code_addr: 6 },
+                i281_ir::Instruction::NoOp,
+                i281_ir::Instruction::NoOp,
+                i281_ir::Instruction::InputC { code_addr: 1 },
+            ]
+        ));
+
+        // a local label is only found in its own scope
+        let output = compile(
+            ".code\nmain: INPUTC [.in]\nother: NOOP\n.in: NOOP\n",
+            &options,
+        );
+        assert!(matches!(
+            output.diagnostics.as_slice(),
+            [Diagnostic::Error(Error::SymbolNotFound { name, .. })] if name.as_str() == ".in"
+        ));
+    }
+
+    #[test]
+    fn compile_ast1() {
+        let mut ast = Root::parse(SOURCE).unwrap();
+        ast.data.as_mut().unwrap().variables[1].data_addr = 10;
+        ast.code.labels.push(Label {
+            ident: "1bad".into(),
+            code_addr: 40,
+            span: None,
+        });
+
+        let output = compile_ast(ast, &CompileOptions::default());
+        assert!(matches!(
+            output.diagnostics.as_slice(),
+            [
+                Diagnostic::Error(Error::InvalidIdent { .. }),
+                Diagnostic::Error(Error::LabelOutOfRange { code_addr: 40, .. }),
+            ]
+        ));
+    }
+
+    #[test]
+    fn compile_ast2() {
+        let mut ast = Root::parse(SOURCE).unwrap();
+        ast.data.as_mut().unwrap().variables[1].data_addr = 10;
+
+        let options = CompileOptions {
+            emit: vec![EmitKind::Ast],
+            ..Default::default()
+        };
+        let output = compile_ast(ast, &options);
+        assert_eq!(output.ast.unwrap().data.unwrap().variables[1].data_addr, 1);
+    }
+
+    #[test]
+    fn compile_ast3() {
+        let mut ast = Root::parse(SOURCE).unwrap();
+        ast.data.as_mut().unwrap().variables[0].value = Literal::Byte(Byte(1000));
+
+        let output = compile_ast(ast, &CompileOptions::default());
+        assert!(matches!(
+            output.diagnostics.as_slice(),
+            [Diagnostic::Error(Error::ByteOutOfRange { value: 1000, .. })]
+        ));
+        assert!(output.verilog.is_empty());
+    }
+
+    #[test]
+    fn compile_ast4() {
+        let mut ast = Root::parse(SOURCE).unwrap();
+        ast.data.as_mut().unwrap().variables[0].placement = Some(Placement::At(Byte(-1)));
+
+        let output = compile_ast(ast, &CompileOptions::default());
+        assert!(matches!(
+            output.diagnostics.as_slice(),
+            [Diagnostic::Error(Error::PlacementOOB { data_addr: -1, .. })]
+        ));
+        assert!(output.verilog.is_empty());
+
+        let mut ast = Root::parse(SOURCE).unwrap();
+        let variables = &mut ast.data.as_mut().unwrap().variables;
+        variables[0].placement = Some(Placement::Org(Byte(i16::MIN)));
+        variables[1].placement = Some(Placement::Org(Byte(16)));
+
+        let output = compile_ast(ast, &CompileOptions::default());
+        assert!(matches!(
+            output.diagnostics.as_slice(),
+            [
+                Diagnostic::Error(Error::PlacementOOB {
+                    data_addr: i16::MIN,
+                    ..
+                }),
+                Diagnostic::Error(Error::PlacementOOB { data_addr: 16, .. }),
+                ..
+            ]
+        ));
+    }
+}
@@ -0,0 +1,931 @@
+//! Static estimate of how many cycles a program takes to run
+//!
+//! The i281 is a single cycle cpu so the estimate counts executed instructions. Value ranges of
+//! the registers and data memory are found with an interval analysis, loops are found from the
+//! back edges of the control flow graph and bounded when their exit compares an induction
+//! register (one that only changes by a constant every iteration) against a known range.
+//!
+//! Instructions written into code memory by `INPUTC` are assumed to never change the branch
+//! structure of the program.
+
+use std::fmt;
+
+use i281_ast::{Ident, Label};
+use i281_ir::{BranchCondition, Instruction, Ir, Register, ShiftDirection};
+
+use crate::DATA_ADDR_MAX;
+
+/// Cycles taken by every instruction
+const CYCLES_PER_INSTRUCTION: u64 = 1;
+/// An 8 bit induction register repeats its values after this many iterations
+const MAX_ITERATIONS: u64 = 256;
+/// Addresses `0..DATA_ADDR_MAX` are accepted by the analyzer
+const DATA_LEN: usize = DATA_ADDR_MAX as usize;
+
+/// The estimated running time of a program
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CycleEstimate {
+    /// Every loop in the program ordered by where it starts
+    pub loops: Vec<LoopInfo>,
+    /// The fewest cycles the program can take, only known for programs without loops
+    pub best_case: Option<u64>,
+    /// The most cycles the program can take, `None` if a loop could not be bounded
+    pub worst_case: Option<u64>,
+    /// The control flow could not be split into loops (e.g. jumps into the middle of a loop)
+    pub irreducible: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LoopInfo {
+    /// The first instruction of the loop
+    pub header: usize,
+    /// The last instruction that jumps back to the header
+    pub end: usize,
+    /// The label of the header if it is known
+    pub label: Option<Ident>,
+    /// How many loops this loop is nested in
+    pub depth: usize,
+    /// The most times the loop jumps back to its header each time it is entered
+    pub iterations: Option<u64>,
+}
+
+impl CycleEstimate {
+    pub fn new(ir: &Ir) -> Self {
+        let cfg = Cfg::new(&ir.instructions);
+        let states = Intervals::new(ir, &cfg);
+        let Some(mut loops) = cfg.loops() else {
+            return Self {
+                loops: Vec::new(),
+                best_case: None,
+                worst_case: None,
+                irreducible: true,
+            };
+        };
+
+        for i in 0..loops.len() {
+            loops[i].iterations = loop_bound(&cfg, &states, &loops, i);
+        }
+
+        let (best_case, worst_case) = match loops.is_empty() {
+            true => {
+                let paths = cfg.paths();
+                (paths.map(|p| p.0), paths.map(|p| p.1))
+            }
+            false => (None, cfg.worst_case(&loops)),
+        };
+
+        let mut infos: Vec<_> = loops
+            .iter()
+            .map(|l| LoopInfo {
+                header: l.header,
+                end: l.tails.iter().copied().max().unwrap_or(l.header),
+                label: None,
+                depth: loops
+                    .iter()
+                    .filter(|outer| outer.header != l.header && outer.body[l.header])
+                    .count(),
+                iterations: l.iterations,
+            })
+            .collect();
+        infos.sort_by_key(|l| l.header);
+
+        Self {
+            loops: infos,
+            best_case,
+            worst_case,
+            irreducible: false,
+        }
+    }
+
+    /// Name each loop after the label on its first instruction
+    pub fn name_loops(&mut self, labels: &[Label]) {
+        for info in self.loops.iter_mut() {
+            info.label = labels
+                .iter()
+                .find(|l| l.code_addr == info.header)
+                .map(|l| l.ident.clone());
+        }
+    }
+}
+
+impl fmt::Display for CycleEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.irreducible {
+            return writeln!(
+                f,
+                "the program jumps into the middle of a loop so its cycles can not be estimated"
+            );
+        }
+
+        if self.loops.is_empty() {
+            return match (self.best_case, self.worst_case) {
+                (Some(best), Some(worst)) if best == worst => {
+                    writeln!(f, "no loops: exactly {worst} cycles")
+                }
+                (Some(best), Some(worst)) => {
+                    writeln!(f, "no loops: between {best} and {worst} cycles")
+                }
+                _ => writeln!(f, "no loops: the program never reaches its end"),
+            };
+        }
+
+        writeln!(f, "loops:")?;
+        for info in self.loops.iter() {
+            let name = match &info.label {
+                Some(label) => format!("{label} ({:02}..{:02})", info.header, info.end),
+                None => format!("{:02}..{:02}", info.header, info.end),
+            };
+            let indent = "  ".repeat(info.depth + 1);
+            match info.iterations {
+                Some(n) => writeln!(f, "{indent}{name}: at most {n} iterations")?,
+                None => writeln!(f, "{indent}{name}: could not be bounded")?,
+            }
+        }
+        match self.worst_case {
+            Some(worst) => writeln!(f, "worst case: {worst} cycles"),
+            None => writeln!(f, "worst case: unknown, some loops could not be bounded"),
+        }
+    }
+}
+
+/// The control flow graph, node `len` is the end of the program
+struct Cfg<'a> {
+    instructions: &'a [Instruction],
+    succs: Vec<Vec<usize>>,
+    preds: Vec<Vec<usize>>,
+    /// `dominators[n][d]` is true if every path from the start to `n` goes through `d`
+    dominators: Vec<Vec<bool>>,
+    reachable: Vec<bool>,
+}
+
+/// A natural loop, loops with the same header are merged
+struct Loop {
+    header: usize,
+    /// The instructions that jump back to the header
+    tails: Vec<usize>,
+    body: Vec<bool>,
+    iterations: Option<u64>,
+}
+
+impl Loop {
+    fn len(&self) -> usize {
+        self.body.iter().filter(|b| **b).count()
+    }
+}
+
+fn target(index: usize, pc_offset: i8, len: usize) -> usize {
+    // the validator makes sure targets are within the program
+    (index as isize + 1 + pc_offset as isize).clamp(0, len as isize) as usize
+}
+
+impl<'a> Cfg<'a> {
+    fn new(instructions: &'a [Instruction]) -> Self {
+        let len = instructions.len();
+        let mut succs: Vec<Vec<usize>> = instructions
+            .iter()
+            .enumerate()
+            .map(|(i, ins)| match *ins {
+                // a jump to itself is how programs commonly halt
+                Instruction::Jump { pc_offset: -1 } => vec![len],
+                Instruction::Jump { pc_offset } => vec![target(i, pc_offset, len)],
+                Instruction::Branch { pc_offset, .. } => {
+                    let mut succs = vec![i + 1, target(i, pc_offset, len)];
+                    succs.dedup();
+                    succs
+                }
+                _ => vec![i + 1],
+            })
+            .collect();
+        succs.push(Vec::new());
+
+        let mut preds = vec![Vec::new(); len + 1];
+        for (i, s) in succs.iter().enumerate() {
+            for &s in s {
+                preds[s].push(i);
+            }
+        }
+
+        let mut reachable = vec![false; len + 1];
+        let mut stack = vec![0];
+        while let Some(n) = stack.pop() {
+            if !std::mem::replace(&mut reachable[n], true) {
+                stack.extend(succs[n].iter().copied());
+            }
+        }
+
+        let mut dominators = vec![vec![true; len + 1]; len + 1];
+        dominators[0] = (0..=len).map(|d| d == 0).collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for n in 1..=len {
+                let mut dom = vec![reachable[n]; len + 1];
+                for &p in preds[n].iter().filter(|p| reachable[**p]) {
+                    for d in 0..=len {
+                        dom[d] &= dominators[p][d];
+                    }
+                }
+                dom[n] = true;
+                if dom != dominators[n] {
+                    dominators[n] = dom;
+                    changed = true;
+                }
+            }
+        }
+
+        Self {
+            instructions,
+            succs,
+            preds,
+            dominators,
+            reachable,
+        }
+    }
+
+    fn end(&self) -> usize {
+        self.instructions.len()
+    }
+
+    fn is_back_edge(&self, from: usize, to: usize) -> bool {
+        self.reachable[from] && self.dominators[from][to]
+    }
+
+    /// Find all natural loops, `None` if the graph is irreducible
+    fn loops(&self) -> Option<Vec<Loop>> {
+        let mut loops: Vec<Loop> = Vec::new();
+        for from in 0..self.end() {
+            for &to in self.succs[from].iter() {
+                if !self.is_back_edge(from, to) {
+                    continue;
+                }
+                let index = match loops.iter().position(|l| l.header == to) {
+                    Some(index) => index,
+                    None => {
+                        let mut body = vec![false; self.end() + 1];
+                        body[to] = true;
+                        loops.push(Loop {
+                            header: to,
+                            tails: Vec::new(),
+                            body,
+                            iterations: None,
+                        });
+                        loops.len() - 1
+                    }
+                };
+                let l = &mut loops[index];
+                l.tails.push(from);
+                let mut stack = vec![from];
+                while let Some(n) = stack.pop() {
+                    if !std::mem::replace(&mut l.body[n], true) {
+                        stack.extend(self.preds[n].iter().filter(|p| self.reachable[**p]));
+                    }
+                }
+            }
+        }
+
+        // without the back edges the graph has to be acyclic
+        let mut in_degree: Vec<usize> = (0..=self.end())
+            .map(|n| {
+                self.preds[n]
+                    .iter()
+                    .filter(|p| self.reachable[**p] && !self.is_back_edge(**p, n))
+                    .count()
+            })
+            .collect();
+        let mut ready = vec![0];
+        let mut visited = 0;
+        while let Some(n) = ready.pop() {
+            visited += 1;
+            for &s in self.succs[n].iter() {
+                if !self.is_back_edge(n, s) {
+                    in_degree[s] -= 1;
+                    if in_degree[s] == 0 {
+                        ready.push(s);
+                    }
+                }
+            }
+        }
+        if visited != self.reachable.iter().filter(|r| **r).count() {
+            return None;
+        }
+
+        // inner loops are smaller than the loops they are nested in
+        loops.sort_by_key(Loop::len);
+        Some(loops)
+    }
+
+    /// The shortest and longest path through a program without loops
+    fn paths(&self) -> Option<(u64, u64)> {
+        let mut memo = vec![None; self.end() + 1];
+        self.path_lengths(0, &mut memo)
+    }
+
+    fn path_lengths(
+        &self,
+        n: usize,
+        memo: &mut [Option<Option<(u64, u64)>>],
+    ) -> Option<(u64, u64)> {
+        if n == self.end() {
+            return Some((0, 0));
+        }
+        if let Some(found) = memo[n] {
+            return found;
+        }
+        let found = self.succs[n]
+            .iter()
+            .filter_map(|&s| self.path_lengths(s, memo))
+            .reduce(|(a_min, a_max), (b_min, b_max)| (a_min.min(b_min), a_max.max(b_max)))
+            .map(|(min, max)| (min + CYCLES_PER_INSTRUCTION, max + CYCLES_PER_INSTRUCTION));
+        memo[n] = Some(found);
+        found
+    }
+
+    /// The longest path through the program with every loop collapsed into a single node
+    fn worst_case(&self, loops: &[Loop]) -> Option<u64> {
+        // the outermost loop already costed that contains each node
+        let mut unit: Vec<Option<usize>> = vec![None; self.end() + 1];
+        let mut cost: Vec<Path> = Vec::with_capacity(loops.len());
+
+        // inner loops come first so they are collapsed before the loops they are nested in
+        for (index, l) in loops.iter().enumerate() {
+            // the last time through only runs until the loop is left
+            let iteration = self.longest(Some(l), false, &unit, &cost, l.header);
+            let exit = self.longest(Some(l), true, &unit, &cost, l.header);
+            cost.push(match (l.iterations, iteration, exit) {
+                (Some(n), Path::Cycles(c), Path::Cycles(e)) => Path::Cycles(n * c + e),
+                _ => Path::Unbounded,
+            });
+            for n in (0..=self.end()).filter(|n| l.body[*n]) {
+                unit[n] = Some(index);
+            }
+        }
+        match self.longest(None, false, &unit, &cost, 0) {
+            Path::Cycles(cycles) => Some(cycles),
+            Path::Unbounded | Path::Dead => None,
+        }
+    }
+
+    /// Longest path from `start` to the end of an iteration of `within` (a jump back to its
+    /// header or out of it) or to the end of the program, with `to_exit` only paths that leave
+    /// `within` are counted
+    fn longest(
+        &self,
+        within: Option<&Loop>,
+        to_exit: bool,
+        unit: &[Option<usize>],
+        cost: &[Path],
+        start: usize,
+    ) -> Path {
+        let mut memo = vec![None; self.end() + 1];
+        self.longest_from(start, within, to_exit, unit, cost, &mut memo)
+    }
+
+    fn longest_from(
+        &self,
+        n: usize,
+        within: Option<&Loop>,
+        to_exit: bool,
+        unit: &[Option<usize>],
+        cost: &[Path],
+        memo: &mut [Option<Path>],
+    ) -> Path {
+        if n == self.end() {
+            return Path::Cycles(0);
+        }
+        if let Some(found) = memo[n] {
+            return found;
+        }
+
+        // a collapsed inner loop is entered at its header and left through any of its exits
+        let (members, own): (Vec<usize>, Path) = match unit[n] {
+            Some(u) => (
+                (0..self.end()).filter(|m| unit[*m] == Some(u)).collect(),
+                cost[u],
+            ),
+            None => (vec![n], Path::Cycles(CYCLES_PER_INSTRUCTION)),
+        };
+
+        let mut rest = Path::Dead;
+        for &m in members.iter() {
+            for &s in self.succs[m].iter().filter(|s| !members.contains(s)) {
+                let after = match within {
+                    Some(l) if s == l.header && to_exit => Path::Dead,
+                    Some(l) if s == l.header || !l.body[s] => Path::Cycles(0),
+                    _ => self.longest_from(s, within, to_exit, unit, cost, memo),
+                };
+                rest = rest.max(after);
+            }
+        }
+
+        let found = own.then(rest);
+        memo[n] = Some(found);
+        found
+    }
+}
+
+/// The length of the longest path through part of the control flow graph
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Path {
+    /// There is no way to continue from here
+    Dead,
+    /// The path goes through a loop that could not be bounded
+    Unbounded,
+    Cycles(u64),
+}
+
+impl Path {
+    fn max(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::Dead, path) | (path, Self::Dead) => path,
+            (Self::Cycles(a), Self::Cycles(b)) => Self::Cycles(a.max(b)),
+            _ => Self::Unbounded,
+        }
+    }
+
+    /// This path followed by `rest`
+    fn then(self, rest: Self) -> Self {
+        match (self, rest) {
+            (_, Self::Dead) | (Self::Dead, _) => Self::Dead,
+            (Self::Cycles(a), Self::Cycles(b)) => Self::Cycles(a + b),
+            _ => Self::Unbounded,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Interval {
+    lo: i8,
+    hi: i8,
+}
+
+impl Interval {
+    const FULL: Self = Self {
+        lo: i8::MIN,
+        hi: i8::MAX,
+    };
+
+    fn constant(value: i8) -> Self {
+        Self {
+            lo: value,
+            hi: value,
+        }
+    }
+
+    fn join(self, other: Self) -> Self {
+        Self {
+            lo: self.lo.min(other.lo),
+            hi: self.hi.max(other.hi),
+        }
+    }
+
+    /// Build an interval from wider bounds, a result that can overflow may wrap to any value
+    fn from_wide(lo: i16, hi: i16) -> Self {
+        match (i8::try_from(lo), i8::try_from(hi)) {
+            (Ok(lo), Ok(hi)) => Self { lo, hi },
+            _ => Self::FULL,
+        }
+    }
+
+    fn add(self, other: Self) -> Self {
+        Self::from_wide(
+            self.lo as i16 + other.lo as i16,
+            self.hi as i16 + other.hi as i16,
+        )
+    }
+
+    fn sub(self, other: Self) -> Self {
+        Self::from_wide(
+            self.lo as i16 - other.hi as i16,
+            self.hi as i16 - other.lo as i16,
+        )
+    }
+
+    fn addresses(self, offset: i8) -> impl Iterator<Item = usize> {
+        let lo = (self.lo as i16 + offset as i16).max(0);
+        let hi = (self.hi as i16 + offset as i16).min(DATA_LEN as i16 - 1);
+        (lo..=hi).map(|a| a as usize)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct State {
+    regs: [Interval; 4],
+    data: [Interval; DATA_LEN],
+}
+
+impl State {
+    fn join(&mut self, other: &Self) {
+        for (a, b) in self.regs.iter_mut().zip(other.regs.iter()) {
+            *a = a.join(*b);
+        }
+        for (a, b) in self.data.iter_mut().zip(other.data.iter()) {
+            *a = a.join(*b);
+        }
+    }
+
+    fn reg(&self, r: Register) -> Interval {
+        self.regs[r as usize]
+    }
+
+    fn set(&mut self, r: Register, value: Interval) {
+        self.regs[r as usize] = value;
+    }
+
+    fn load(&self, offset: i8, index: Interval) -> Interval {
+        let mut addresses = index.addresses(offset);
+        let first = addresses.next().map_or(Interval::FULL, |a| self.data[a]);
+        addresses.fold(first, |acc, a| acc.join(self.data[a]))
+    }
+
+    fn store(&mut self, offset: i8, index: Interval, value: Interval) {
+        let strong = index.lo == index.hi;
+        for a in index.addresses(offset) {
+            self.data[a] = match strong {
+                true => value,
+                false => self.data[a].join(value),
+            };
+        }
+    }
+}
+
+/// Does `cond` hold between `x` and `y` after `CMP x, y`
+fn holds(cond: BranchCondition, x: i8, y: i8) -> bool {
+    match cond {
+        BranchCondition::Equal => x == y,
+        BranchCondition::NotEqual => x != y,
+        BranchCondition::Greater => x > y,
+        BranchCondition::GreaterEqual => x >= y,
+    }
+}
+
+/// Narrow `x` and `y` to the values for which `cond` holds (or not), `None` if there are none
+fn refine(
+    cond: BranchCondition,
+    taken: bool,
+    x: Interval,
+    y: Interval,
+) -> Option<(Interval, Interval)> {
+    let wide = |lo: i16, hi: i16| (lo <= hi).then(|| Interval::from_wide(lo, hi));
+    let (xl, xh, yl, yh) = (x.lo as i16, x.hi as i16, y.lo as i16, y.hi as i16);
+    match (cond, taken) {
+        (BranchCondition::Equal, true) | (BranchCondition::NotEqual, false) => {
+            let both = wide(xl.max(yl), xh.min(yh))?;
+            Some((both, both))
+        }
+        (BranchCondition::Greater, true) => {
+            Some((wide(xl.max(yl + 1), xh)?, wide(yl, yh.min(xh - 1))?))
+        }
+        (BranchCondition::GreaterEqual, true) => {
+            Some((wide(xl.max(yl), xh)?, wide(yl, yh.min(xh))?))
+        }
+        (BranchCondition::Greater, false) => Some((wide(xl, xh.min(yh))?, wide(yl.max(xl), yh)?)),
+        (BranchCondition::GreaterEqual, false) => {
+            Some((wide(xl, xh.min(yh - 1))?, wide(yl.max(xl + 1), yh)?))
+        }
+        // a value can only be excluded from an interval at its ends so nothing is learned
+        (BranchCondition::Equal, false) | (BranchCondition::NotEqual, true) => {
+            let same = x.lo == x.hi && x == y;
+            (!same).then_some((x, y))
+        }
+    }
+}
+
+/// The range of every register and byte of data memory before each instruction
+struct Intervals {
+    before: Vec<Option<State>>,
+}
+
+impl Intervals {
+    fn new(ir: &Ir, cfg: &Cfg) -> Self {
+        let mut data = [Interval::FULL; DATA_LEN];
+        // an ir that was not validated can have too much data, then nothing is known about it
+        for (slot, value) in data.iter_mut().zip(ir.data_memory().unwrap_or_default()) {
+            *slot = Interval::constant(value);
+        }
+        let mut before = vec![None; cfg.end() + 1];
+        before[0] = Some(State {
+            regs: [Interval::FULL; 4],
+            data,
+        });
+
+        // every interval can only grow and is bounded so this always finishes
+        let mut work = vec![0];
+        while let Some(n) = work.pop() {
+            let Some(state) = before[n].clone() else {
+                continue;
+            };
+            for (s, out) in transfer(cfg, n, state) {
+                let changed = match &mut before[s] {
+                    Some(old) => {
+                        let previous = old.clone();
+                        old.join(&out);
+                        *old != previous
+                    }
+                    slot @ None => {
+                        *slot = Some(out);
+                        true
+                    }
+                };
+                if changed && s != cfg.end() {
+                    work.push(s);
+                }
+            }
+        }
+
+        Self { before }
+    }
+
+    /// The state on every edge from outside of the loop into its header
+    fn entry(&self, cfg: &Cfg, l: &Loop) -> Option<State> {
+        let mut entry: Option<State> = None;
+        for &p in cfg.preds[l.header].iter().filter(|p| !l.body[**p]) {
+            let Some(state) = self.before[p].clone() else {
+                continue;
+            };
+            for (s, out) in transfer(cfg, p, state) {
+                if s == l.header {
+                    match &mut entry {
+                        Some(e) => e.join(&out),
+                        None => entry = Some(out),
+                    }
+                }
+            }
+        }
+        entry
+    }
+}
+
+/// Run instruction `n` on `state` giving the state for each of its successors
+fn transfer(cfg: &Cfg, n: usize, mut state: State) -> Vec<(usize, State)> {
+    let next = n + 1;
+    match cfg.instructions[n] {
+        Instruction::NoOp
+        | Instruction::Cmp { .. }
+        | Instruction::InputC { .. }
+        | Instruction::InputCF { .. } => {}
+        Instruction::InputD { data_addr } => {
+            state.store(data_addr, Interval::constant(0), Interval::FULL)
+        }
+        Instruction::InputDF { rx, data_offset } => {
+            let index = state.reg(rx);
+            for a in index.addresses(data_offset) {
+                state.data[a] = Interval::FULL;
+            }
+        }
+        Instruction::Move { rx, ry } => state.set(rx, state.reg(ry)),
+        Instruction::LoadI { rx, value } => state.set(rx, Interval::constant(value)),
+        Instruction::Add { rx, ry } => state.set(rx, state.reg(rx).add(state.reg(ry))),
+        Instruction::AddI { rx, value } => {
+            state.set(rx, state.reg(rx).add(Interval::constant(value)))
+        }
+        Instruction::Sub { rx, ry } => state.set(rx, state.reg(rx).sub(state.reg(ry))),
+        Instruction::SubI { rx, value } => {
+            state.set(rx, state.reg(rx).sub(Interval::constant(value)))
+        }
+        Instruction::Load { rx, data_addr } => {
+            state.set(rx, state.load(data_addr, Interval::constant(0)))
+        }
+        Instruction::LoadF {
+            rx,
+            ry,
+            data_offset,
+        } => state.set(rx, state.load(data_offset, state.reg(ry))),
+        Instruction::Store { data_addr, rx } => {
+            state.store(data_addr, Interval::constant(0), state.reg(rx))
+        }
+        Instruction::StoreF {
+            ry,
+            data_offset,
+            rx,
+        } => state.store(data_offset, state.reg(ry), state.reg(rx)),
+        Instruction::Shift { dir, rx } => {
+            let x = state.reg(rx);
+            let shifted = match dir {
+                ShiftDirection::Left => Interval::from_wide(x.lo as i16 * 2, x.hi as i16 * 2),
+                ShiftDirection::Right if x.lo >= 0 => Interval {
+                    lo: x.lo >> 1,
+                    hi: x.hi >> 1,
+                },
+                ShiftDirection::Right => Interval::FULL,
+            };
+            state.set(rx, shifted);
+        }
+        Instruction::Jump { .. } => return vec![(cfg.succs[n][0], state)],
+        Instruction::Branch { cond, pc_offset } => {
+            let taken = target(n, pc_offset, cfg.end());
+            let Some((x, y)) = compared(cfg, n) else {
+                return cfg.succs[n].iter().map(|&s| (s, state.clone())).collect();
+            };
+            let mut out = Vec::new();
+            for (s, is_taken) in [(next, false), (taken, true)] {
+                if let Some((xi, yi)) = refine(cond, is_taken, state.reg(x), state.reg(y)) {
+                    let mut refined = state.clone();
+                    refined.set(x, xi);
+                    refined.set(y, yi);
+                    if x == y && xi != yi {
+                        continue;
+                    }
+                    out.push((s, refined));
+                }
+            }
+            return out;
+        }
+    }
+    vec![(next, state)]
+}
+
+/// The registers compared right before the branch at `n`, if nothing else can reach the branch
+fn compared(cfg: &Cfg, n: usize) -> Option<(Register, Register)> {
+    if n == 0 || cfg.preds[n] != [n - 1] {
+        return None;
+    }
+    match cfg.instructions[n - 1] {
+        Instruction::Cmp { rx, ry } => Some((rx, ry)),
+        _ => None,
+    }
+}
+
+fn writes(ins: Instruction) -> Option<Register> {
+    match ins {
+        Instruction::Move { rx, .. }
+        | Instruction::LoadI { rx, .. }
+        | Instruction::Add { rx, .. }
+        | Instruction::AddI { rx, .. }
+        | Instruction::Sub { rx, .. }
+        | Instruction::SubI { rx, .. }
+        | Instruction::Load { rx, .. }
+        | Instruction::LoadF { rx, .. }
+        | Instruction::Shift { rx, .. } => Some(rx),
+        _ => None,
+    }
+}
+
+/// The most iterations of `loops[index]`, the smallest bound found from any of its exits
+fn loop_bound(cfg: &Cfg, states: &Intervals, loops: &[Loop], index: usize) -> Option<u64> {
+    let l = &loops[index];
+    let entry = states.entry(cfg, l)?;
+    let in_inner = |n: usize| {
+        loops
+            .iter()
+            .any(|inner| inner.header != l.header && l.body[inner.header] && inner.body[n])
+    };
+
+    let mut bound: Option<u64> = None;
+    for b in (0..cfg.end()).filter(|b| l.body[*b]) {
+        let Instruction::Branch { cond, pc_offset } = cfg.instructions[b] else {
+            continue;
+        };
+        let taken = target(b, pc_offset, cfg.end());
+        let exit_on_taken = match (l.body[b + 1], l.body[taken]) {
+            (true, false) => true,
+            (false, true) => false,
+            _ => continue,
+        };
+        let Some((rx, ry)) = compared(cfg, b) else {
+            continue;
+        };
+        let Some(at_cmp) = &states.before[b - 1] else {
+            continue;
+        };
+
+        for (x, y, x_first) in [(rx, ry, true), (ry, rx, false)] {
+            if x == y {
+                continue;
+            }
+            let steps: Option<Vec<(usize, i64)>> = (0..cfg.end())
+                .filter(|n| l.body[*n] && writes(cfg.instructions[*n]) == Some(x))
+                .map(|n| match cfg.instructions[n] {
+                    Instruction::AddI { value, .. } => Some((n, value as i64)),
+                    Instruction::SubI { value, .. } => Some((n, -(value as i64))),
+                    _ => None,
+                })
+                .collect();
+            let Some(steps) = steps else {
+                continue;
+            };
+            // every step has to happen exactly once per iteration
+            let every_iteration =
+                |n: usize| !in_inner(n) && l.tails.iter().all(|t| cfg.dominators[*t][n]);
+            if steps.is_empty() || !steps.iter().all(|(n, _)| every_iteration(*n)) {
+                continue;
+            }
+            let step: i64 = steps.iter().map(|(_, s)| s).sum();
+            let before_cmp: i64 = steps
+                .iter()
+                .filter(|(n, _)| cfg.dominators[b - 1][*n])
+                .map(|(_, s)| s)
+                .sum();
+            if step == 0 {
+                continue;
+            }
+
+            let others = at_cmp.reg(y);
+            let exits = |value: i8| {
+                // the loop can only be left if it is left for every value the other register has
+                (others.lo..=others.hi).all(|other| {
+                    let (a, b) = if x_first {
+                        (value, other)
+                    } else {
+                        (other, value)
+                    };
+                    holds(cond, a, b) == exit_on_taken
+                })
+            };
+
+            let start = entry.reg(x);
+            let mut most = 0;
+            for x0 in start.lo..=start.hi {
+                let mut value = (x0 as i64 + before_cmp) as i8;
+                let mut count = 0;
+                while !exits(value) {
+                    count += 1;
+                    if count >= MAX_ITERATIONS {
+                        break;
+                    }
+                    value = (value as i64 + step) as i8;
+                }
+                most = most.max(count);
+            }
+            if most < MAX_ITERATIONS {
+                bound = Some(bound.map_or(most, |b| b.min(most)));
+            }
+        }
+    }
+    bound
+}
+
+#[cfg(test)]
+mod test {
+    use i281_ast::Root;
+
+    use super::CycleEstimate;
+    use crate::Analyzer;
+
+    fn estimate(source: &str) -> CycleEstimate {
+        let ast = Root::parse(source).unwrap();
+        let labels = ast.code.labels.clone();
+        let (ir, diagnostics) = Analyzer::new(ast).validate().unwrap();
+        assert!(diagnostics.is_empty());
+        let mut estimate = CycleEstimate::new(&ir.resolve().unwrap());
+        estimate.name_loops(&labels);
+        estimate
+    }
+
+    #[test]
+    fn cycles1() {
+        let found = estimate(".code\n    LOADI A, 1\n    LOADI B, 2\n    ADD A, B\n");
+        assert_eq!((found.best_case, found.worst_case), (Some(3), Some(3)));
+
+        let found = estimate(
+            ".code\n    LOADI A, 1\n    LOADI B, 2\n    CMP A, B\n    BRG End\n    NOOP\n    NOOP\nEnd: NOOP\n",
+        );
+        assert_eq!((found.best_case, found.worst_case), (Some(5), Some(7)));
+        assert!(found.loops.is_empty());
+    }
+
+    #[test]
+    fn cycles2() {
+        // for (i = 0; i < 5; i++) sum += i;
+        let found = estimate(
+            "
+.data
+n BYTE 5
+.code
+    LOADI A, 0
+    LOADI B, 0
+    LOAD D, [n]
+Loop: CMP A, D
+    BRGE End
+    ADD B, A
+    ADDI A, 1
+    JUMP Loop
+End: NOOP
+",
+        );
+        assert_eq!(found.loops.len(), 1);
+        assert_eq!(found.loops[0].label, Some("Loop".into()));
+        assert_eq!(found.loops[0].iterations, Some(5));
+        // 3 before the loop, 5 iterations of 5 instructions, the compare and branch that leave
+        // the loop and the last instruction
+        assert_eq!(found.worst_case, Some(3 + 5 * 5 + 2 + 1));
+    }
+
+    #[test]
+    fn cycles3() {
+        let found = estimate(
+            "
+.code
+Wait: INPUTD [0]
+    LOAD A, [0]
+    LOADI B, 0
+    CMP A, B
+    BRE Wait
+",
+        );
+        assert_eq!(found.loops[0].iterations, None);
+        assert_eq!(found.worst_case, None);
+        assert!(found
+            .to_string()
+            .contains("Wait (00..04): could not be bounded"));
+    }
+}
@@ -0,0 +1,44 @@
+use std::fmt;
+
+use miette::{LabeledSpan, SourceCode};
+
+use super::Warning;
+
+/// A warning whose lint was denied, it is reported as an error
+#[derive(Clone, Debug)]
+pub struct DeniedWarning(pub Warning);
+
+impl fmt::Display for DeniedWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::error::Error for DeniedWarning {}
+
+impl miette::Diagnostic for DeniedWarning {
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        self.0.code()
+    }
+
+    fn severity(&self) -> Option<miette::Severity> {
+        Some(miette::Severity::Error)
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        let lint = self.0.lint();
+        let denied = format!("The `{lint}` lint is denied, use `--allow {lint}` to silence it");
+        Some(Box::new(match self.0.help() {
+            Some(help) => format!("{help}. {denied}"),
+            None => denied,
+        }))
+    }
+
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        self.0.source_code()
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        self.0.labels()
+    }
+}
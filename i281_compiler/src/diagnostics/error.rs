@@ -1,21 +1,294 @@
-use i281_ast::{Ident, Instruction};
+use i281_ast::{Expr, Ident, Instruction};
+use miette::SourceSpan;
+
+use crate::MemorySpace;
 
 #[derive(Clone, Debug, miette::Diagnostic, thiserror::Error)]
 pub enum Error {
     #[error("too much user data was defined amount: `{found}` maximum allowed: `{max}`")]
-    TooMuchUserData { found: usize, max: usize },
+    #[diagnostic(
+        code(compiler::too_much_user_data),
+        help("remove variables or make arrays smaller so all data fits in memory")
+    )]
+    TooMuchUserData {
+        found: usize,
+        max: usize,
+        #[label("this variable does not fit in data memory")]
+        span: Option<SourceSpan>,
+    },
     #[error("there are too many code instructions: `{found}` maximum allowed: `{max}`")]
-    TooManyInstructions { found: usize, max: usize },
-    #[error("variable `{name}` was not found in user defined data for instruction: `{ins}`")]
-    VariableNotFound { name: Ident, ins: Instruction },
+    #[diagnostic(
+        code(compiler::too_many_instructions),
+        help("remove instructions so the program fits in code memory")
+    )]
+    TooManyInstructions {
+        found: usize,
+        max: usize,
+        #[label("this is the first instruction that does not fit in code memory")]
+        span: Option<SourceSpan>,
+    },
+    #[error("`{name}` is not a variable or a label: `{expr}`")]
+    #[diagnostic(
+        code(compiler::symbol_not_found),
+        help("declare the variable in the `.data` segment or add the label in front of an instruction")
+    )]
+    SymbolNotFound {
+        name: Ident,
+        expr: Expr,
+        #[label("used here")]
+        span: Option<SourceSpan>,
+    },
     #[error("label `{name}` was not found")]
-    LabelNotFound { name: Ident, ins: Instruction },
+    #[diagnostic(code(compiler::label_not_found))]
+    LabelNotFound {
+        name: Ident,
+        ins: Instruction,
+        /// Suggests a similar label that can be reached from the instruction if there is one
+        #[help]
+        help: String,
+        #[label("used here")]
+        span: Option<SourceSpan>,
+    },
     #[error("address requires a register to be specified: `{ins}`")]
-    AddressMissingRegister { ins: Instruction },
+    #[diagnostic(
+        code(compiler::address_missing_register),
+        help("add a register to the address like `[var+B]` or use the instruction without the `F` suffix")
+    )]
+    AddressMissingRegister {
+        ins: Instruction,
+        #[label("this address is constant")]
+        span: Option<SourceSpan>,
+    },
     #[error("address is not allowed to have a register specified: `{ins}`")]
-    AddressWithRegister { ins: Instruction },
+    #[diagnostic(
+        code(compiler::address_with_register),
+        help("remove the register from the address or use the instruction with the `F` suffix")
+    )]
+    AddressWithRegister {
+        ins: Instruction,
+        #[label("this address uses a register")]
+        span: Option<SourceSpan>,
+    },
     #[error("address specified with multiple registers when only one is allowed: `{ins}`")]
-    AddressTooManyRegisters { ins: Instruction },
+    #[diagnostic(
+        code(compiler::address_too_many_registers),
+        help("add the registers together with `ADD` before using them as an offset")
+    )]
+    AddressTooManyRegisters {
+        ins: Instruction,
+        #[label("this address uses more than one register")]
+        span: Option<SourceSpan>,
+    },
+    #[error("`{name}` is a {found} address but a {expected} address is expected: `{ins}`")]
+    #[diagnostic(
+        code(compiler::wrong_memory_space),
+        help("labels are addresses in code memory and variables are addresses in data memory")
+    )]
+    WrongMemorySpace {
+        name: Ident,
+        found: MemorySpace,
+        expected: MemorySpace,
+        ins: Instruction,
+        #[label("used here")]
+        span: Option<SourceSpan>,
+    },
+    #[error("the register can only be added to the address: `{expr}`")]
+    #[diagnostic(
+        code(compiler::register_not_added),
+        help("write the address like `[var+B]`, shift or add the register beforehand to scale it")
+    )]
+    RegisterNotAdded {
+        expr: Expr,
+        #[label("this address uses the register in another way")]
+        span: Option<SourceSpan>,
+    },
+    #[error("`{name}` is imported from another module so only a constant can be added to it")]
+    #[diagnostic(
+        code(compiler::import_not_added),
+        help("the address of `{name}` is only known once the modules are linked, write it like `{name} + 1`")
+    )]
+    ImportNotAdded {
+        name: Ident,
+        expr: Expr,
+        #[label("`{expr}` uses the import in another way")]
+        span: Option<SourceSpan>,
+    },
+    #[error("`{name}` is moved when the module is linked so only a constant can be added to it")]
+    #[diagnostic(
+        code(compiler::moved_not_added),
+        help("the address of `{name}` is only known once the modules are linked, write it like `{name} + 1`")
+    )]
+    MovedNotAdded {
+        name: Ident,
+        expr: Expr,
+        #[label("`{expr}` uses the address in another way")]
+        span: Option<SourceSpan>,
+    },
+    #[error("`{expr}` has to be a constant but it uses a register")]
+    #[diagnostic(
+        code(compiler::constant_with_register),
+        help("registers can only be used in the addresses of instructions with the `F` suffix")
+    )]
+    ConstantWithRegister {
+        expr: Expr,
+        #[label("this is not a constant")]
+        span: Option<SourceSpan>,
+    },
+    #[error("`{expr}` evaluates to `{value}` which does not fit in a byte")]
+    #[diagnostic(code(compiler::expr_overflow))]
+    ExprOverflow {
+        expr: Expr,
+        value: i64,
+        #[help]
+        steps: Option<String>,
+        #[label("this expression overflows")]
+        span: Option<SourceSpan>,
+    },
+    #[error("`{value}` in variable `{name}` does not fit in a byte")]
+    #[diagnostic(
+        code(compiler::byte_out_of_range),
+        help("a byte is either signed from -128 to 127 or unsigned from 0 to 255")
+    )]
+    ByteOutOfRange {
+        name: Ident,
+        value: i16,
+        #[label("this variable")]
+        span: Option<SourceSpan>,
+    },
+    #[error("`{expr}` divides by zero")]
+    #[diagnostic(code(compiler::divide_by_zero))]
+    DivideByZero {
+        expr: Expr,
+        #[help]
+        steps: Option<String>,
+        #[label("this expression divides by zero")]
+        span: Option<SourceSpan>,
+    },
+    #[error("`{expr}` shifts by `{amount}` but shifts must be between `0` and `7`")]
+    #[diagnostic(code(compiler::invalid_shift))]
+    InvalidShift {
+        expr: Expr,
+        amount: i64,
+        #[help]
+        steps: Option<String>,
+        #[label("this expression shifts too far")]
+        span: Option<SourceSpan>,
+    },
     #[error("address value is out of bounds: `{ins}`")]
-    AddressOOB { ins: Instruction },
+    #[diagnostic(
+        code(compiler::address_out_of_bounds),
+        help("the address must be within the memory it is accessing")
+    )]
+    AddressOOB {
+        ins: Instruction,
+        #[label("this address is out of bounds")]
+        span: Option<SourceSpan>,
+    },
+    #[error("variable `{name}` is defined more than once")]
+    #[diagnostic(
+        code(compiler::non_unique_variable),
+        help("rename one of the variables, uses of `{name}` refer to the first definition")
+    )]
+    NonUniqueVariable {
+        name: Ident,
+        #[label("redefined here")]
+        span: Option<SourceSpan>,
+        #[label("first defined here")]
+        first: Option<SourceSpan>,
+    },
+    #[error("variable `{name}` overlaps `{other}` at data address {data_addr}")]
+    #[diagnostic(
+        code(compiler::overlapping_variables),
+        help("move one of the variables with `.org` or `@` so each byte of data memory is only used once")
+    )]
+    OverlappingVariables {
+        name: Ident,
+        other: Ident,
+        data_addr: usize,
+        #[label("placed here")]
+        span: Option<SourceSpan>,
+        #[label("`{other}` was placed here")]
+        other_span: Option<SourceSpan>,
+    },
+    #[error("variable `{name}` is placed at {data_addr} which is outside data memory")]
+    #[diagnostic(
+        code(compiler::placement_out_of_bounds),
+        help("`.org` and `@` must give an address in data memory")
+    )]
+    PlacementOOB {
+        name: Ident,
+        data_addr: i16,
+        #[label("placed here")]
+        span: Option<SourceSpan>,
+    },
+    #[error("label `{name}` is defined more than once")]
+    #[diagnostic(
+        code(compiler::non_unique_label),
+        help("rename one of the labels, jumps to `{name}` go to the first definition")
+    )]
+    NonUniqueLabel {
+        name: Ident,
+        #[label("redefined here")]
+        span: Option<SourceSpan>,
+        #[label("first defined here")]
+        first: Option<SourceSpan>,
+    },
+    #[error("`{name}` is not a valid identifier")]
+    #[diagnostic(
+        code(compiler::invalid_ident),
+        help("identifiers must start with a letter or `_` followed by letters, digits or `_`")
+    )]
+    InvalidIdent { name: Ident },
+    #[error("`{name}` can not be used as a name as it is the register `{register}`")]
+    #[diagnostic(
+        code(compiler::register_name),
+        help("`{name}` and `[{name}]` are always read as the register, rename it to something like `{rename}`")
+    )]
+    RegisterName {
+        name: Ident,
+        register: &'static str,
+        rename: Ident,
+        #[label("declared here")]
+        span: Option<SourceSpan>,
+    },
+    #[error("label `{name}` points to code address `{code_addr}` but there are only `{len}` instructions")]
+    #[diagnostic(
+        code(compiler::label_out_of_range),
+        help("a label can point at an instruction or right after the last one, where the program halts")
+    )]
+    LabelOutOfRange {
+        name: Ident,
+        code_addr: usize,
+        len: usize,
+    },
+    #[error("variable `{name}` contains a nested array which can not be laid out in memory")]
+    #[diagnostic(
+        code(compiler::nested_array),
+        help("flatten the array so every element is a byte")
+    )]
+    NestedArray { name: Ident },
+    #[error("instruction `{index}` accesses an address that is out of bounds: `{ins:?}`")]
+    #[diagnostic(
+        code(compiler::ir_address_out_of_bounds),
+        help("every address must be within data memory, check the offsets of the ir")
+    )]
+    IrAddressOOB {
+        index: usize,
+        ins: i281_ir::Instruction,
+    },
+    #[error("instruction `{index}` jumps outside of user code: `{ins:?}`")]
+    #[diagnostic(
+        code(compiler::ir_jump_out_of_bounds),
+        help("a jump must land on an instruction or right after the last one, check the offset of the ir")
+    )]
+    IrJumpOOB {
+        index: usize,
+        ins: i281_ir::Instruction,
+    },
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Resolve(#[from] i281_ir::ResolveError),
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Link(#[from] i281_ir::LinkError),
 }
@@ -1,16 +1,12 @@
-use i281_ast::{Label, ParseError, Variable};
+use i281_ast::ParseError;
 
 #[derive(Clone, Debug, miette::Diagnostic, thiserror::Error)]
 pub enum Failure {
-    #[error("{0}")]
+    #[error(transparent)]
+    #[diagnostic(transparent)]
     Parse(ParseError<'static>),
-    // these are failures because I am unsure how the compiler should resolve name collisions beyond
-    // immediatly spitting out an error
-    #[error("variable with name `{}` is defined more than once", .0.ident)]
-    NonUniqueVariable(Variable),
-    #[error("label with name `{}` is defined more than once", .0.ident)]
-    NonUniqueLabel(Label),
     #[error("Encountered an error in the compilation process see diagnostic info")]
+    #[diagnostic(code(compiler::encountered_error))]
     EncounteredError,
     #[error("")]
     Skip, // signal variant
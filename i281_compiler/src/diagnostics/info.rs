@@ -0,0 +1,210 @@
+use std::fmt;
+
+use miette::{LabeledSpan, SourceCode};
+
+/// How serious a diagnostic is
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Severity {
+    Error,
+    Warning,
+    Advice,
+}
+
+impl From<Option<miette::Severity>> for Severity {
+    fn from(severity: Option<miette::Severity>) -> Self {
+        match severity {
+            None | Some(miette::Severity::Error) => Self::Error,
+            Some(miette::Severity::Warning) => Self::Warning,
+            Some(miette::Severity::Advice) => Self::Advice,
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Error => write!(f, "error"),
+            Self::Warning => write!(f, "warning"),
+            Self::Advice => write!(f, "note"),
+        }
+    }
+}
+
+/// A location in the source that a diagnostic refers to
+///
+/// Lines and columns start at 1, columns are counted in characters and `offset`/`len` in bytes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SpanInfo {
+    pub offset: usize,
+    pub len: usize,
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+    pub label: Option<String>,
+    /// The primary span is the one the diagnostic is about, all others give extra context
+    pub primary: bool,
+}
+
+/// A diagnostic flattened into plain data so it can be serialized or displayed by other tools
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DiagnosticInfo {
+    pub severity: Severity,
+    pub code: Option<String>,
+    pub message: String,
+    pub spans: Vec<SpanInfo>,
+    pub help: Option<String>,
+}
+
+impl DiagnosticInfo {
+    /// Collect the information of a diagnostic, resolving its spans within `source`
+    ///
+    /// The first label of a diagnostic is considered its primary span.
+    pub fn new(diagnostic: &dyn miette::Diagnostic, source: &str) -> Self {
+        let spans = diagnostic
+            .labels()
+            .into_iter()
+            .flatten()
+            .enumerate()
+            .map(|(i, label)| SpanInfo::new(&label, source, i == 0))
+            .collect();
+
+        Self {
+            severity: diagnostic.severity().into(),
+            code: diagnostic.code().map(|c| c.to_string()),
+            message: diagnostic.to_string(),
+            spans,
+            help: diagnostic.help().map(|h| h.to_string()),
+        }
+    }
+
+    pub fn primary_span(&self) -> Option<&SpanInfo> {
+        self.spans.iter().find(|s| s.primary)
+    }
+}
+
+impl SpanInfo {
+    fn new(label: &LabeledSpan, source: &str, primary: bool) -> Self {
+        let offset = label.offset().min(source.len());
+        let end = (label.offset() + label.len()).min(source.len());
+        let (line, column) = line_column(source, offset);
+        let (end_line, end_column) = line_column(source, end);
+        Self {
+            offset,
+            len: end - offset,
+            line,
+            column,
+            end_line,
+            end_column,
+            label: label.label().map(str::to_owned),
+            primary,
+        }
+    }
+}
+
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = source.get(..offset).unwrap_or(source);
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let column = before[line_start..].chars().count() + 1;
+    (line, column)
+}
+
+/// Attaches source code to a diagnostic that does not carry its own
+///
+/// The diagnostics produced by the analyzer only know spans into the source, wrapping them in
+/// this allows miette to render a snippet of the source for every label.
+#[derive(Debug)]
+pub struct WithSource<'a> {
+    pub diagnostic: &'a dyn miette::Diagnostic,
+    pub source: &'a str,
+}
+
+impl fmt::Display for WithSource<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.diagnostic, f)
+    }
+}
+
+impl std::error::Error for WithSource<'_> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.diagnostic.source()
+    }
+}
+
+impl miette::Diagnostic for WithSource<'_> {
+    fn code<'b>(&'b self) -> Option<Box<dyn fmt::Display + 'b>> {
+        self.diagnostic.code()
+    }
+
+    fn severity(&self) -> Option<miette::Severity> {
+        self.diagnostic.severity()
+    }
+
+    fn help<'b>(&'b self) -> Option<Box<dyn fmt::Display + 'b>> {
+        self.diagnostic.help()
+    }
+
+    fn url<'b>(&'b self) -> Option<Box<dyn fmt::Display + 'b>> {
+        self.diagnostic.url()
+    }
+
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        self.diagnostic
+            .source_code()
+            .or(Some(&self.source as &dyn SourceCode))
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        self.diagnostic.labels()
+    }
+
+    fn related<'b>(&'b self) -> Option<Box<dyn Iterator<Item = &'b dyn miette::Diagnostic> + 'b>> {
+        self.diagnostic.related()
+    }
+
+    fn diagnostic_source(&self) -> Option<&dyn miette::Diagnostic> {
+        self.diagnostic.diagnostic_source()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use i281_ast::Root;
+
+    use super::{DiagnosticInfo, Severity};
+    use crate::Analyzer;
+
+    #[test]
+    fn diagnostic_info1() {
+        let source = ".code\n    LOADI A, 1\n    JUMP nowhere\n";
+        let (_, diagnostics) = Analyzer::new(Root::parse(source).unwrap())
+            .validate()
+            .unwrap();
+
+        let info = DiagnosticInfo::new(&diagnostics[0], source);
+        assert_eq!(info.severity, Severity::Error);
+        assert_eq!(info.code.as_deref(), Some("compiler::label_not_found"));
+        assert_eq!(info.message, "label `nowhere` was not found");
+        assert!(info.help.is_some());
+
+        let span = info.primary_span().unwrap();
+        assert_eq!((span.line, span.column), (3, 5));
+        assert_eq!((span.end_line, span.end_column), (3, 17));
+        assert_eq!(&source[span.offset..span.offset + span.len], "JUMP nowhere");
+    }
+
+    #[test]
+    fn diagnostic_info2() {
+        let source = ".code\n    LOADI A, 1000\n";
+        let err = Root::parse(source).unwrap_err();
+
+        let info = DiagnosticInfo::new(&err, source);
+        assert_eq!(info.code.as_deref(), Some("ast::parse_error"));
+        assert_eq!(info.primary_span().unwrap().line, 2);
+    }
+}
@@ -1,17 +1,66 @@
+mod denied;
 mod error;
 mod failure;
+mod info;
 mod warning;
 
+pub use denied::DeniedWarning;
 pub use error::Error;
 pub use failure::Failure;
+pub use info::{DiagnosticInfo, Severity, SpanInfo, WithSource};
 pub use warning::Warning;
 
 #[derive(Clone, Debug, miette::Diagnostic, thiserror::Error)]
 pub enum Diagnostic {
-    #[error("ERROR occurred while analyzing the assembly")]
+    #[error(transparent)]
+    #[diagnostic(transparent)]
     Error(#[from] Error),
-    #[error("WARNING something *could* be wrong with the assembly")]
+    #[error(transparent)]
+    #[diagnostic(transparent)]
     Warning(#[from] Warning),
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Denied(#[from] DeniedWarning),
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Fatal(#[from] Failure),
+}
+
+impl Diagnostic {
+    /// Returns `true` if the diagnostic prevents the assembly from being compiled
+    pub fn is_error(&self) -> bool {
+        matches!(self, Self::Error(..) | Self::Denied(..) | Self::Fatal(..))
+    }
 }
 
 pub type Result<T> = core::result::Result<T, Failure>;
+
+/// The candidate closest to `name` if it is close enough to be a likely typo
+pub(crate) fn did_you_mean<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let max = (name.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// The number of single character insertions, deletions or substitutions between `a` and `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitute.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
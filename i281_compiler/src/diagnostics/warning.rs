@@ -1,7 +1,105 @@
-use i281_ast::Instruction;
+use i281_ast::{Expr, Ident, Instruction};
+use miette::SourceSpan;
+
+use crate::lints::Lint;
 
 #[derive(Clone, Debug, miette::Diagnostic, thiserror::Error)]
 pub enum Warning {
-    #[error("possible address out of bounds depending on value of register: `{0}`")]
-    AddressOOBPossible(Instruction),
+    #[error("possible address out of bounds depending on value of register: `{ins}`")]
+    #[diagnostic(
+        code(compiler::address_out_of_bounds_possible),
+        severity(Warning),
+        help("make sure the register always keeps the address within data memory")
+    )]
+    AddressOOBPossible {
+        ins: Instruction,
+        #[label("this address may be out of bounds")]
+        span: Option<SourceSpan>,
+    },
+    #[error("{memory} memory is almost full: `{used}` of `{max}` used")]
+    #[diagnostic(
+        code(compiler::memory_nearly_full),
+        severity(Warning),
+        help("run with `--emit report` to see what is using the most {memory} memory")
+    )]
+    MemoryNearlyFull {
+        memory: &'static str,
+        used: usize,
+        max: usize,
+    },
+    #[error("`{name}` has the same name as the {kind} `{word}`")]
+    #[diagnostic(
+        code(compiler::reserved_names),
+        severity(Warning),
+        help("opcodes and keywords are not case sensitive so this is easy to misread, rename it to something like `{rename}`")
+    )]
+    ReservedName {
+        name: Ident,
+        kind: &'static str,
+        word: &'static str,
+        rename: Ident,
+        #[label("declared here")]
+        span: Option<SourceSpan>,
+    },
+    #[error("`{expr}` is `{value}` unsigned but `{signed}` when read as a signed byte")]
+    #[diagnostic(
+        code(compiler::ambiguous_bytes),
+        severity(Warning),
+        help("write the value as `{signed}` or `{value}u8` so it is clear which one is meant")
+    )]
+    AmbiguousByte {
+        expr: Expr,
+        value: i64,
+        signed: i8,
+        #[label("this is stored as `{signed}`")]
+        span: Option<SourceSpan>,
+    },
+    #[error("malformed pragma `{pragma}`")]
+    #[diagnostic(
+        code(compiler::malformed_pragmas),
+        severity(Warning),
+        help("pragmas are written as `; i281: allow(lint_name)`")
+    )]
+    MalformedPragma {
+        pragma: String,
+        #[label("this pragma could not be understood")]
+        span: Option<SourceSpan>,
+    },
+    #[error("unknown lint `{name}`")]
+    #[diagnostic(
+        code(compiler::unknown_lints),
+        severity(Warning),
+        help("run `compile281 --help` to see all lint names")
+    )]
+    UnknownLint {
+        name: String,
+        #[label("in this pragma")]
+        span: Option<SourceSpan>,
+    },
+}
+
+impl Warning {
+    /// The lint that controls the level of this warning
+    pub fn lint(&self) -> Lint {
+        match self {
+            Self::AddressOOBPossible { .. } => Lint::AddressOOBPossible,
+            Self::MemoryNearlyFull { .. } => Lint::MemoryNearlyFull,
+            Self::ReservedName { .. } => Lint::ReservedNames,
+            Self::AmbiguousByte { .. } => Lint::AmbiguousBytes,
+            Self::MalformedPragma { .. } => Lint::MalformedPragmas,
+            Self::UnknownLint { .. } => Lint::UnknownLints,
+        }
+    }
+
+    /// Where in the source the warning was found
+    pub fn span(&self) -> Option<SourceSpan> {
+        match self {
+            Self::AddressOOBPossible { span, .. }
+            | Self::ReservedName { span, .. }
+            | Self::AmbiguousByte { span, .. }
+            | Self::MalformedPragma { span, .. }
+            | Self::UnknownLint { span, .. } => *span,
+            Self::MemoryNearlyFull { .. } => None,
+        }
+    }
 }
@@ -0,0 +1,361 @@
+use i281_ast::{Expr, Ident, Label, LabelKind, Oper, Register, Variable};
+use i281_ir::{Symbol, Value};
+use miette::SourceSpan;
+
+use crate::{diagnostics::Error, MemorySpace};
+
+/// Every subexpression has to fit in a byte, either signed or unsigned
+const BYTE_MIN: i64 = i8::MIN as i64;
+const BYTE_MAX: i64 = u8::MAX as i64;
+
+/// A folded expression, a constant plus the registers that are added to it
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Linear {
+    pub value: i64,
+    /// How many times each register is added, registers that cancel out are removed
+    pub registers: Vec<(Register, i64)>,
+    /// How many times the address of each symbol is included in `value`, symbols only stay here
+    /// while they are added or subtracted
+    pub symbols: Vec<(Symbol, i64)>,
+    /// Symbols whose address went into something other than an addition or subtraction, the
+    /// value can not follow them when they are moved
+    pub consumed: Vec<Symbol>,
+    /// The part of `value` that is not the address of a symbol
+    pub offset: i64,
+}
+
+impl Linear {
+    fn constant(value: i64) -> Self {
+        Self {
+            value,
+            registers: Vec::new(),
+            symbols: Vec::new(),
+            consumed: Vec::new(),
+            offset: value,
+        }
+    }
+
+    fn scale(mut self, by: i64) -> Self {
+        self.value *= by;
+        self.offset *= by;
+        for (_, times) in self.registers.iter_mut() {
+            *times *= by;
+        }
+        for (_, times) in self.symbols.iter_mut() {
+            *times *= by;
+        }
+        self
+    }
+
+    fn add(mut self, other: Self) -> Self {
+        self.value += other.value;
+        self.offset += other.offset;
+        merge(&mut self.registers, other.registers);
+        merge(&mut self.symbols, other.symbols);
+        self.consumed.extend(other.consumed);
+        self
+    }
+
+    /// The register if the expression is a constant plus exactly one register
+    pub fn offset_register(&self) -> Option<Register> {
+        match self.registers.as_slice() {
+            [(reg, 1)] => Some(*reg),
+            _ => None,
+        }
+    }
+
+    /// The value as a symbol plus a constant, if symbols are used in any other way it is constant
+    ///
+    /// Folding keeps every value in a byte so the offset always fits.
+    pub fn to_value(&self) -> Value {
+        match self.symbols.as_slice() {
+            [(symbol, 1)] => Value::symbol(symbol.clone(), self.offset as i16),
+            _ => Value::constant(self.value as i16),
+        }
+    }
+}
+
+/// Add up the terms of two sums, terms that cancel out are removed
+fn merge<T: PartialEq>(terms: &mut Vec<(T, i64)>, other: Vec<(T, i64)>) {
+    for (term, times) in other {
+        match terms.iter_mut().find(|(t, _)| *t == term) {
+            Some((_, existing)) => *existing += times,
+            None => terms.push((term, times)),
+        }
+    }
+    terms.retain(|(_, times)| *times != 0);
+}
+
+/// The symbol an identifier refers to and its value, the address of a variable or a label
+///
+/// Only the given memory space is searched, without one variables are preferred over labels.
+/// A local label `.name` is looked up in the scope of the instruction at `from`, outside of an
+/// instruction it has to be qualified as `global.name`. Labels are named by `label_names`. The
+/// first definition of a name is used as duplicates are reported elsewhere.
+pub(crate) fn symbol_value<'a>(
+    variables: &'a [Variable],
+    labels: &'a [Label],
+    label_names: &'a [String],
+    from: Option<usize>,
+    space: Option<MemorySpace>,
+) -> impl Fn(&Ident) -> Option<(Symbol, i64)> + 'a {
+    move |ident| {
+        let var = variables
+            .iter()
+            .find(|v| &v.ident == ident)
+            .filter(|_| space != Some(MemorySpace::Code));
+        let label = match from {
+            Some(from) => Label::resolve(labels, from, ident),
+            None if LabelKind::of(ident) == LabelKind::Local => None,
+            None => Label::resolve(labels, 0, ident),
+        }
+        .filter(|_| space != Some(MemorySpace::Data));
+        var.map(|v| (Symbol::Variable(ident.to_string()), v.data_addr as i64))
+            .or_else(|| {
+                let label = label?;
+                let index = labels.iter().position(|l| std::ptr::eq(l, label))?;
+                let name = label_names.get(index)?.clone();
+                Some((Symbol::Label(name), label.code_addr as i64))
+            })
+    }
+}
+
+/// Fold an expression into a constant plus registers
+///
+/// Registers can only be added or subtracted, anything else must be a constant. When a
+/// subexpression is out of range the value of every subexpression before it is included in the
+/// error so it is clear where the value came from.
+pub(crate) fn fold<F>(expr: &Expr, span: Option<SourceSpan>, lookup: F) -> Result<Linear, Error>
+where
+    F: Fn(&Ident) -> Option<(Symbol, i64)>,
+{
+    let mut folder = Folder {
+        lookup,
+        root: expr,
+        span,
+        steps: Vec::new(),
+    };
+    folder.fold(expr)
+}
+
+struct Folder<'a, F> {
+    lookup: F,
+    root: &'a Expr,
+    span: Option<SourceSpan>,
+    /// Every constant subexpression that is not a literal with its value, in evaluation order
+    steps: Vec<String>,
+}
+
+impl<'a, F> Folder<'a, F>
+where
+    F: Fn(&Ident) -> Option<(Symbol, i64)>,
+{
+    fn steps(&self) -> Option<String> {
+        (!self.steps.is_empty()).then(|| format!("where {}", self.steps.join(", ")))
+    }
+
+    fn fold(&mut self, expr: &Expr) -> Result<Linear, Error> {
+        let folded = match expr {
+            Expr::Lit(byte) if byte.in_range() => return Ok(Linear::constant(byte.0 as i64)),
+            // only an ast that did not come from the parser can have these
+            Expr::Lit(byte) => Linear::constant(byte.0 as i64),
+            Expr::Reg(reg) => {
+                return Ok(Linear {
+                    registers: vec![(*reg, 1)],
+                    ..Default::default()
+                })
+            }
+            Expr::Ident(ident) => match (self.lookup)(ident) {
+                Some((symbol, value)) => Linear {
+                    value,
+                    symbols: vec![(symbol, 1)],
+                    ..Default::default()
+                },
+                None => {
+                    return Err(Error::SymbolNotFound {
+                        name: ident.clone(),
+                        expr: self.root.clone(),
+                        span: self.span,
+                    })
+                }
+            },
+            Expr::Neg(inner) => self.fold(inner)?.scale(-1),
+            Expr::Binary { left, oper, right } => {
+                let left = self.fold(left)?;
+                let right = self.fold(right)?;
+                self.binary(expr, left, *oper, right)?
+            }
+        };
+
+        if !(BYTE_MIN..=BYTE_MAX).contains(&folded.value) {
+            return Err(Error::ExprOverflow {
+                expr: expr.clone(),
+                value: folded.value,
+                steps: self.steps(),
+                span: self.span,
+            });
+        }
+        if folded.registers.is_empty() {
+            // a value above 127 is a different number once it is stored as a signed byte
+            let value = folded.value;
+            self.steps.push(match value > i8::MAX as i64 {
+                true => format!("{expr} = {value} ({} as i8)", value as i8),
+                false => format!("{expr} = {value}"),
+            });
+        }
+        Ok(folded)
+    }
+
+    fn binary(
+        &self,
+        expr: &Expr,
+        left: Linear,
+        oper: Oper,
+        right: Linear,
+    ) -> Result<Linear, Error> {
+        match oper {
+            Oper::Add => return Ok(left.add(right)),
+            Oper::Sub => return Ok(left.add(right.scale(-1))),
+            _ => {}
+        }
+        if !left.registers.is_empty() || !right.registers.is_empty() {
+            return Err(Error::RegisterNotAdded {
+                expr: self.root.clone(),
+                span: self.span,
+            });
+        }
+
+        let (a, b) = (left.value, right.value);
+        let value = match oper {
+            Oper::Mul => a * b,
+            Oper::Div if b == 0 => {
+                return Err(Error::DivideByZero {
+                    expr: expr.clone(),
+                    steps: self.steps(),
+                    span: self.span,
+                })
+            }
+            Oper::Div => a / b,
+            Oper::Shl | Oper::Shr if !(0..8).contains(&b) => {
+                return Err(Error::InvalidShift {
+                    expr: expr.clone(),
+                    amount: b,
+                    steps: self.steps(),
+                    span: self.span,
+                })
+            }
+            Oper::Shl => a << b,
+            Oper::Shr => a >> b,
+            Oper::And => a & b,
+            Oper::Or => a | b,
+            Oper::Add | Oper::Sub => unreachable!(),
+        };
+        let consumed = [left, right]
+            .into_iter()
+            .flat_map(|l| {
+                l.consumed
+                    .into_iter()
+                    .chain(l.symbols.into_iter().map(|(s, _)| s))
+            })
+            .collect();
+        Ok(Linear {
+            consumed,
+            ..Linear::constant(value)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use i281_ast::{literal::Byte, Expr, Ident, Parse, Register};
+    use i281_ir::{Symbol, Value};
+
+    use super::{fold, Linear};
+    use crate::Error;
+
+    fn eval(source: &str) -> Result<Linear, Error> {
+        let lookup = |ident: &Ident| {
+            let value = match ident.as_str() {
+                "x" => 9,
+                "arr" => 3,
+                _ => return None,
+            };
+            Some((Symbol::Variable(ident.to_string()), value))
+        };
+        fold(&Expr::parse(source).unwrap().1, None, lookup)
+    }
+
+    #[test]
+    fn eval1() {
+        assert_eq!(eval("1+2*3").unwrap().value, 7);
+        assert_eq!(eval("(1+2)*3").unwrap().value, 9);
+        assert_eq!(eval("x<<2|1").unwrap().value, 37);
+        assert_eq!(eval("-x/2").unwrap().value, -4);
+        assert_eq!(eval("0x0F&-2").unwrap().value, 14);
+        assert_eq!(eval("0xF0>>4").unwrap().value, 15);
+        assert_eq!(eval("200u8/2").unwrap().value, 100);
+    }
+
+    #[test]
+    fn eval2() {
+        let folded = eval("arr+B+2-x").unwrap();
+        assert_eq!(folded.value, -4);
+        assert_eq!(folded.offset_register(), Some(Register::B));
+        assert_eq!(eval("arr+B-B").unwrap().registers, []);
+        assert_eq!(eval("arr-B").unwrap().offset_register(), None);
+        assert!(matches!(
+            eval("arr+B*2"),
+            Err(Error::RegisterNotAdded { .. })
+        ));
+    }
+
+    #[test]
+    fn eval3() {
+        let Err(Error::ExprOverflow {
+            expr, value, steps, ..
+        }) = eval("x*20+100")
+        else {
+            panic!("expected an overflow");
+        };
+        assert_eq!(expr.to_string(), "x*20+100");
+        assert_eq!(value, 280);
+        assert_eq!(steps.unwrap(), "where x = 9, x*20 = 180 (-76 as i8)");
+
+        assert!(matches!(eval("x/(3-3)"), Err(Error::DivideByZero { .. })));
+        assert!(matches!(
+            eval("1<<8"),
+            Err(Error::InvalidShift { amount: 8, .. })
+        ));
+        assert!(matches!(
+            eval("-(200)"),
+            Err(Error::ExprOverflow { value: -200, .. })
+        ));
+        // fits as an unsigned byte, the analyzer warns that it is -128 when read as signed
+        assert_eq!(eval("-(-128)").unwrap().value, 128);
+        assert!(matches!(eval("y+1"), Err(Error::SymbolNotFound { .. })));
+
+        let literal = Expr::Lit(Byte(1000));
+        assert!(matches!(
+            fold(&literal, None, |_| None),
+            Err(Error::ExprOverflow { value: 1000, .. })
+        ));
+    }
+
+    #[test]
+    fn eval4() {
+        // a symbol only stays in the value when its address is added exactly once
+        let arr = |offset| Value::symbol(Symbol::Variable("arr".into()), offset);
+        assert_eq!(eval("arr").unwrap().to_value(), arr(0));
+        assert_eq!(eval("arr+B+2-1").unwrap().to_value(), arr(1));
+        assert_eq!(eval("x-x+arr-(3-1)").unwrap().to_value(), arr(-2));
+        assert_eq!(eval("arr*2").unwrap().to_value(), Value::constant(6));
+        assert_eq!(eval("arr+x").unwrap().to_value(), Value::constant(12));
+        assert_eq!(eval("x-arr").unwrap().to_value(), Value::constant(6));
+        assert_eq!(eval("200u8+0").unwrap().to_value(), Value::constant(200));
+
+        let x = || Symbol::Variable("x".into());
+        assert_eq!(eval("arr+(x|1)").unwrap().consumed, [x()]);
+        assert_eq!(eval("-(x*2)+arr").unwrap().consumed, [x()]);
+        assert!(eval("(x-x)*2+arr").unwrap().consumed.is_empty());
+    }
+}
@@ -1,46 +1,83 @@
 #![forbid(unsafe_code)]
 
 mod analyzer;
+mod compile;
+mod cycles;
 mod diagnostics;
+mod eval;
+mod lints;
+mod listing;
+mod report;
+mod simulate;
+mod validate;
 mod verilog;
 
-pub use analyzer::Analyzer;
-pub use diagnostics::{Diagnostic, Error, Failure, Warning};
+pub use analyzer::{Analyzer, MemorySpace};
+pub use compile::{
+    compile, compile_ast, compile_ir, compile_symbolic_ir, CompileOptions, CompileOutput, EmitKind,
+    Target, VerilogModule,
+};
+pub use cycles::{CycleEstimate, LoopInfo};
+pub use diagnostics::{
+    DeniedWarning, Diagnostic, DiagnosticInfo, Error, Failure, Severity, SpanInfo, Warning,
+    WithSource,
+};
+pub use lints::{Level, Lint, LintLevels, LintSelector, Pragma};
+pub use listing::{CodeLine, DataLine, Listing};
+pub use report::{BackwardBranch, Report, VariableUsage};
+pub use simulate::{
+    Flags, InputEvent, InputRequest, ScriptedSwitches, SimulationError, Simulator, SwitchValue,
+    Switches, TraceEvent, DEFAULT_MAX_STEPS,
+};
+pub use validate::validate_ir;
 pub use verilog::{compile_verilog, VerilogOutput};
 
 pub const BLOCK_SIZE: i8 = 16; // the size of the blocks in the generated output
 pub const DATA_ADDR_MIN: i8 = 0;
-pub const DATA_ADDR_MAX: i8 = 16; // in java compiler this is 64 but really this should be 16
+pub const DATA_ADDR_MAX: i8 = i281_ir::DATA_MEMORY_SIZE as i8; // in java compiler this is 64 but really this should be 16
 pub const CODE_ADDR_MIN: i8 = 0;
-pub const CODE_ADDR_MAX: i8 = 32; // there are a maximum of 30 instructions in user code
+pub const CODE_ADDR_MAX: i8 = i281_ir::CODE_MEMORY_SIZE as i8; // there are a maximum of 30 instructions in user code
 
 pub fn analyze<W: std::io::Write>(
     diagnostic_out: &mut W,
     ast: i281_ast::Root,
 ) -> Result<i281_ir::Ir, miette::ErrReport> {
-    use miette::IntoDiagnostic;
     let (ir, diagnostics) = Analyzer::new(ast).validate()?;
 
     // output any diagnosics to the specified output writer and if any of the diagnosics were
     // errors we return a failure indicating we encountered errors
-    if diagnostics.len() > 0 {
-        let handler = miette::GraphicalReportHandler::new();
-        // TODO: add a handler for warnings that makes it print yellow or something
-        let mut report = String::new();
-        let mut found_error = false;
-        for diagnostic in diagnostics.iter() {
-            if matches!(diagnostic, Diagnostic::Error(..)) {
-                found_error = true;
-            }
-            handler
-                .render_report(&mut report, diagnostic)
-                .into_diagnostic()?;
-        }
-        diagnostic_out.write(report.as_bytes()).into_diagnostic()?;
-        if found_error {
-            return Err(Failure::EncounteredError.into());
-        }
+    render_diagnostics(diagnostic_out, None, &diagnostics)?;
+    if diagnostics.iter().any(Diagnostic::is_error) {
+        return Err(Failure::EncounteredError.into());
     }
 
-    Ok(ir)
+    Ok(ir.resolve()?)
+}
+
+/// Render diagnostics in a human readable format to the specified writer
+///
+/// If the source the diagnostics were produced from is given a snippet of it is shown for each
+/// diagnostic.
+pub fn render_diagnostics<W: std::io::Write>(
+    diagnostic_out: &mut W,
+    source: Option<&str>,
+    diagnostics: &[Diagnostic],
+) -> Result<(), miette::ErrReport> {
+    use miette::IntoDiagnostic;
+    if diagnostics.is_empty() {
+        return Ok(());
+    }
+
+    let handler = miette::GraphicalReportHandler::new();
+    let mut report = String::new();
+    for diagnostic in diagnostics.iter() {
+        match source {
+            Some(source) => handler.render_report(&mut report, &WithSource { diagnostic, source }),
+            None => handler.render_report(&mut report, diagnostic),
+        }
+        .into_diagnostic()?;
+    }
+    diagnostic_out
+        .write_all(report.as_bytes())
+        .into_diagnostic()
 }
@@ -0,0 +1,289 @@
+use std::{fmt, str::FromStr};
+
+use miette::SourceSpan;
+
+use crate::diagnostics::{DeniedWarning, Diagnostic, Warning};
+
+/// The prefix of a comment that controls lint levels in the source
+const PRAGMA_PREFIX: &str = "i281:";
+
+macro_rules! lints {
+    ($($variant:ident == $name:literal),+ $(,)?) => {
+        /// A warning that can be allowed, warned about or denied
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        pub enum Lint {
+            $($variant),+
+        }
+
+        impl Lint {
+            pub const ALL: &'static [Lint] = &[$(Self::$variant),+];
+
+            /// The stable name used to refer to the lint on the command line and in pragmas
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Self::$variant => $name),+
+                }
+            }
+        }
+    };
+}
+
+lints! {
+    AddressOOBPossible == "address_out_of_bounds_possible",
+    MemoryNearlyFull == "memory_nearly_full",
+    ReservedNames == "reserved_names",
+    AmbiguousBytes == "ambiguous_bytes",
+    MalformedPragmas == "malformed_pragmas",
+    UnknownLints == "unknown_lints",
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Lint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|lint| lint.name() == s)
+            .ok_or_else(|| format!("unknown lint `{s}`"))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Level {
+    /// The lint is not reported
+    Allow,
+    /// The lint is reported as a warning
+    #[default]
+    Warn,
+    /// The lint is reported as an error and prevents compilation
+    Deny,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Allow => write!(f, "allow"),
+            Self::Warn => write!(f, "warn"),
+            Self::Deny => write!(f, "deny"),
+        }
+    }
+}
+
+/// Either a single lint or the `warnings` group which contains every lint
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LintSelector {
+    Warnings,
+    Lint(Lint),
+}
+
+impl FromStr for LintSelector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "warnings" => Ok(Self::Warnings),
+            other => other.parse().map(Self::Lint),
+        }
+    }
+}
+
+/// The effective level of every lint
+///
+/// Levels are applied in the order they are set so later settings override earlier ones, the
+/// same as `rustc` handles `-A`, `-W` and `-D`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LintLevels {
+    settings: Vec<(LintSelector, Level)>,
+}
+
+impl LintLevels {
+    pub fn set(&mut self, selector: LintSelector, level: Level) -> &mut Self {
+        self.settings.push((selector, level));
+        self
+    }
+
+    pub fn level(&self, lint: Lint) -> Level {
+        self.settings
+            .iter()
+            .rev()
+            .find(|(selector, _)| match selector {
+                LintSelector::Warnings => true,
+                LintSelector::Lint(l) => *l == lint,
+            })
+            .map(|(_, level)| *level)
+            .unwrap_or_default()
+    }
+
+    /// Apply the lint levels to the diagnostics
+    ///
+    /// Allowed warnings are removed and denied warnings are turned into errors, a warning that a
+    /// pragma allows for its instruction is always removed.
+    pub fn apply(&self, diagnostics: Vec<Diagnostic>, pragmas: &[Pragma]) -> Vec<Diagnostic> {
+        diagnostics
+            .into_iter()
+            .filter_map(|diagnostic| match diagnostic {
+                Diagnostic::Warning(warning) => {
+                    let lint = warning.lint();
+                    let allowed_by_pragma = pragmas.iter().any(|p| p.allows(lint, warning.span()));
+                    match self.level(lint) {
+                        _ if allowed_by_pragma => None,
+                        Level::Allow => None,
+                        Level::Warn => Some(Diagnostic::Warning(warning)),
+                        Level::Deny => Some(DeniedWarning(warning).into()),
+                    }
+                }
+                other => Some(other),
+            })
+            .collect()
+    }
+}
+
+/// A `; i281: allow(lint_name)` comment that allows lints for the instruction after it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pragma {
+    pub lints: Vec<Lint>,
+    /// The span of the instruction the pragma applies to
+    pub target: SourceSpan,
+}
+
+impl Pragma {
+    fn allows(&self, lint: Lint, span: Option<SourceSpan>) -> bool {
+        self.lints.contains(&lint) && span.is_some_and(|s| s.offset() == self.target.offset())
+    }
+
+    /// Find all pragmas in the source and match them to the instruction that follows them
+    ///
+    /// Pragmas that are malformed or name unknown lints produce warnings, pragmas without an
+    /// instruction after them are ignored.
+    pub fn find_all(
+        source: &str,
+        instruction_spans: &[SourceSpan],
+    ) -> (Vec<Self>, Vec<Diagnostic>) {
+        let mut pragmas = Vec::new();
+        let mut diagnostics = Vec::new();
+
+        let mut line_start = 0;
+        for line in source.split_inclusive('\n') {
+            let offset = line_start;
+            line_start += line.len();
+
+            let Some(comment_start) = line.find(';') else {
+                continue;
+            };
+            let comment = line[comment_start + 1..].trim();
+            let Some(body) = comment.strip_prefix(PRAGMA_PREFIX) else {
+                continue;
+            };
+            let span = SourceSpan::new(
+                (offset + comment_start).into(),
+                line[comment_start..].trim_end().len().into(),
+            );
+
+            let Some(names) = body
+                .trim()
+                .strip_prefix("allow(")
+                .and_then(|rest| rest.strip_suffix(')'))
+            else {
+                diagnostics.push(
+                    Warning::MalformedPragma {
+                        pragma: comment.to_owned(),
+                        span: Some(span),
+                    }
+                    .into(),
+                );
+                continue;
+            };
+
+            let mut lints = Vec::new();
+            for name in names.split(',').map(str::trim) {
+                match name.parse() {
+                    Ok(lint) => lints.push(lint),
+                    Err(_) => diagnostics.push(
+                        Warning::UnknownLint {
+                            name: name.to_owned(),
+                            span: Some(span),
+                        }
+                        .into(),
+                    ),
+                }
+            }
+
+            let pragma_end = offset + line.len();
+            if let Some(&target) = instruction_spans.iter().find(|s| s.offset() >= pragma_end) {
+                pragmas.push(Self { lints, target });
+            }
+        }
+
+        (pragmas, diagnostics)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use i281_ast::Root;
+
+    use super::{Level, Lint, LintLevels, LintSelector, Pragma};
+    use crate::{Analyzer, Diagnostic, Warning};
+
+    const SOURCE: &str = "
+.data
+x BYTE 1
+.code
+    LOADF A, [x+B+20]
+    ; i281: allow(address_out_of_bounds_possible)
+    LOADF A, [x+B+20]
+    LOADF A, [x+B+20]
+";
+
+    fn diagnostics(levels: &LintLevels) -> Vec<Diagnostic> {
+        let ast = Root::parse(SOURCE).unwrap();
+        let (pragmas, mut diagnostics) = Pragma::find_all(SOURCE, &ast.code.instruction_spans);
+        let (_, found) = Analyzer::new(ast).validate().unwrap();
+        diagnostics.extend(found);
+        levels.apply(diagnostics, &pragmas)
+    }
+
+    #[test]
+    fn lint_levels1() {
+        let found = diagnostics(&LintLevels::default());
+        assert_eq!(found.len(), 2);
+        assert!(found.iter().all(|d| matches!(d, Diagnostic::Warning(..))));
+    }
+
+    #[test]
+    fn lint_levels2() {
+        let mut levels = LintLevels::default();
+        levels.set(LintSelector::Warnings, Level::Deny);
+        let found = diagnostics(&levels);
+        assert_eq!(found.len(), 2);
+        assert!(found.iter().all(Diagnostic::is_error));
+
+        levels.set(LintSelector::Lint(Lint::AddressOOBPossible), Level::Allow);
+        assert!(diagnostics(&levels).is_empty());
+    }
+
+    #[test]
+    fn pragma1() {
+        let source =
+            ".code\n; i281: allow(nonsense, address_out_of_bounds_possible)\nNOOP\n; i281: deny\n";
+        let ast = Root::parse(source).unwrap();
+        let (pragmas, diagnostics) = Pragma::find_all(source, &ast.code.instruction_spans);
+        assert_eq!(pragmas.len(), 1);
+        assert_eq!(pragmas[0].lints, [Lint::AddressOOBPossible]);
+        assert_eq!(pragmas[0].target, ast.code.instruction_spans[0]);
+        assert!(matches!(
+            diagnostics.as_slice(),
+            [
+                Diagnostic::Warning(Warning::UnknownLint { .. }),
+                Diagnostic::Warning(Warning::MalformedPragma { .. })
+            ]
+        ));
+    }
+}
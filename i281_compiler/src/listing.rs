@@ -0,0 +1,129 @@
+use i281_ast::{Ident, Root};
+use i281_ir::Ir;
+
+/// A human readable listing of where everything ended up in memory
+///
+/// Every byte of user data and every instruction is listed with its address, its value in
+/// binary and the source it was produced from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Listing {
+    pub data: Vec<DataLine>,
+    pub code: Vec<CodeLine>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DataLine {
+    pub data_addr: usize,
+    pub value: i8,
+    /// The variable that starts at this address, if any
+    pub variable: Option<Ident>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CodeLine {
+    pub code_addr: usize,
+    pub machine_code: u16,
+    pub labels: Vec<Ident>,
+    pub source: String,
+}
+
+impl Listing {
+    /// Create a listing from the ast and the ir that was produced from it
+    ///
+    /// The ir must have been produced without errors so that every instruction in the ast has a
+    /// matching instruction in the ir.
+    pub fn new(ast: &Root, ir: &Ir) -> Self {
+        let variables = ast
+            .data
+            .as_ref()
+            .map(|d| d.variables.as_slice())
+            .unwrap_or(&[]);
+
+        let mut data: Vec<_> = variables
+            .iter()
+            .zip(ir.variables.iter())
+            .flat_map(|(ast_var, ir_var)| {
+                ir_var
+                    .data
+                    .iter()
+                    .enumerate()
+                    .map(|(offset, &value)| DataLine {
+                        data_addr: ast_var.data_addr + offset,
+                        value,
+                        variable: (offset == 0).then(|| ast_var.ident.clone()),
+                    })
+            })
+            .collect();
+        // `.org` and `@` can place variables out of order
+        data.sort_by_key(|line| line.data_addr);
+
+        let code = ast
+            .code
+            .instructions
+            .iter()
+            .zip(ir.instructions.iter())
+            .enumerate()
+            .map(|(code_addr, (ast_ins, ir_ins))| CodeLine {
+                code_addr,
+                machine_code: ir_ins.build(),
+                labels: ast
+                    .code
+                    .labels
+                    .iter()
+                    .filter(|l| l.code_addr == code_addr)
+                    .map(|l| l.ident.clone())
+                    .collect(),
+                source: ast_ins.to_string(),
+            })
+            .collect();
+
+        Self { data, code }
+    }
+}
+
+impl std::fmt::Display for Listing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.data.is_empty() {
+            writeln!(f, ".data")?;
+            for line in self.data.iter() {
+                writeln!(f, "{line}")?;
+            }
+        }
+        writeln!(f, ".code")?;
+        for line in self.code.iter() {
+            writeln!(f, "{line}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for DataLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:02}  {:08b}", self.data_addr, self.value)?;
+        if let Some(var) = &self.variable {
+            write!(f, "  {var}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for CodeLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let code = self.machine_code;
+        let labels = self
+            .labels
+            .iter()
+            .map(|l| format!("{l}: "))
+            .collect::<String>();
+        write!(
+            f,
+            "{:02}  {:04b}_{:02b}_{:02b}_{:08b}  {labels}{}",
+            self.code_addr,
+            code >> 12,
+            (code >> 10) & 0b11,
+            (code >> 8) & 0b11,
+            code & 0xFF,
+            self.source,
+        )
+    }
+}
@@ -0,0 +1,196 @@
+use std::fmt;
+
+use i281_ast::{Ident, Label, OpCode, Register, Root};
+
+use crate::{CODE_ADDR_MAX, DATA_ADDR_MAX};
+
+/// How much of the cpu's resources a program uses
+///
+/// The report only needs the ast so it is produced even when the program does not fit in memory,
+/// which is exactly when it is most useful.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Report {
+    pub instructions: usize,
+    pub variables: Vec<VariableUsage>,
+    /// Every register used by any instruction in the order `A`, `B`, `C`, `D`
+    pub registers: Vec<Register>,
+    /// How often each opcode is used, the most used opcode comes first
+    pub opcodes: Vec<(OpCode, usize)>,
+    pub largest_backward_branch: Option<BackwardBranch>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VariableUsage {
+    pub name: Ident,
+    pub data_addr: usize,
+    pub size: usize,
+}
+
+/// A jump or branch to a label at or before the instruction itself
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BackwardBranch {
+    pub from: usize,
+    pub to: usize,
+    pub label: Ident,
+}
+
+impl BackwardBranch {
+    /// How many instructions the branch goes back
+    pub fn distance(&self) -> usize {
+        self.from - self.to
+    }
+}
+
+impl Report {
+    pub fn new(ast: &Root) -> Self {
+        let variables = ast
+            .data
+            .iter()
+            .flat_map(|data| data.variables.iter())
+            .map(|var| VariableUsage {
+                name: var.ident.clone(),
+                data_addr: var.data_addr,
+                size: var.value.size_of(),
+            })
+            .collect();
+
+        let instructions = &ast.code.instructions;
+        let used: Vec<_> = instructions.iter().flat_map(|i| i.registers()).collect();
+        let registers = [Register::A, Register::B, Register::C, Register::D]
+            .into_iter()
+            .filter(|r| used.contains(r))
+            .collect();
+
+        let mut opcodes: Vec<(OpCode, usize)> = Vec::new();
+        for opcode in instructions.iter().map(|i| i.opcode()) {
+            match opcodes.iter_mut().find(|(o, _)| *o == opcode) {
+                Some((_, count)) => *count += 1,
+                None => opcodes.push((opcode, 1)),
+            }
+        }
+        opcodes.sort_by(|(_, a), (_, b)| b.cmp(a)); // stable so ties stay in order of first use
+
+        let largest_backward_branch = instructions
+            .iter()
+            .enumerate()
+            .filter_map(|(from, ins)| {
+                let label = ins.target()?;
+                let to = Label::resolve(&ast.code.labels, from, label)?.code_addr;
+                (to <= from).then(|| BackwardBranch {
+                    from,
+                    to,
+                    label: label.clone(),
+                })
+            })
+            .reduce(|a, b| if b.distance() > a.distance() { b } else { a });
+
+        Self {
+            instructions: instructions.len(),
+            variables,
+            registers,
+            opcodes,
+            largest_backward_branch,
+        }
+    }
+
+    /// Bytes of data memory used by all variables
+    pub fn data(&self) -> usize {
+        self.variables.iter().map(|v| v.size).sum()
+    }
+}
+
+fn percent(used: usize, max: i8) -> usize {
+    used * 100 / max as usize
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "code memory: {}/{CODE_ADDR_MAX} instructions ({}%)",
+            self.instructions,
+            percent(self.instructions, CODE_ADDR_MAX)
+        )?;
+        writeln!(
+            f,
+            "data memory: {}/{DATA_ADDR_MAX} bytes ({}%)",
+            self.data(),
+            percent(self.data(), DATA_ADDR_MAX)
+        )?;
+        let width = self
+            .variables
+            .iter()
+            .map(|v| v.name.as_str().len())
+            .max()
+            .unwrap_or(0);
+        for var in self.variables.iter() {
+            let unit = if var.size == 1 { "byte" } else { "bytes" };
+            writeln!(
+                f,
+                "  {:02}  {:width$}  {} {unit}",
+                var.data_addr,
+                var.name.as_str(),
+                var.size
+            )?;
+        }
+
+        let registers: Vec<_> = self.registers.iter().map(|r| r.to_string()).collect();
+        match registers.is_empty() {
+            true => writeln!(f, "registers used: none")?,
+            false => writeln!(f, "registers used: {}", registers.join(", "))?,
+        }
+
+        writeln!(f, "opcodes:")?;
+        for (opcode, count) in self.opcodes.iter() {
+            writeln!(f, "  {:8}{count}", opcode.to_string())?;
+        }
+
+        match &self.largest_backward_branch {
+            Some(branch) => writeln!(
+                f,
+                "largest backward branch: {} instructions from {:02} to `{}` at {:02}",
+                branch.distance(),
+                branch.from,
+                branch.label,
+                branch.to
+            ),
+            None => writeln!(f, "largest backward branch: none"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use i281_ast::{Register, Root};
+
+    use super::Report;
+
+    #[test]
+    fn report1() {
+        let source = "
+.data
+arr BYTE 1, 2, 3
+x BYTE ?
+.code
+    LOADI B, 0
+loop: LOADF A, [arr+B]
+    ADDI B, 1
+    STORE [x], A
+inner: CMP A, B
+    BRNE inner
+    BRG loop
+    JUMP end
+end: NOOP
+";
+        let report = Report::new(&Root::parse(source).unwrap());
+        assert_eq!(report.instructions, 9);
+        assert_eq!(report.data(), 4);
+        assert_eq!(report.variables[1].data_addr, 3);
+        assert_eq!(report.registers, [Register::A, Register::B]);
+        assert_eq!(report.opcodes[0].0.to_string(), "LOADI");
+        assert!(report.opcodes.iter().all(|(_, count)| *count == 1));
+
+        let branch = report.largest_backward_branch.unwrap();
+        assert_eq!((branch.from, branch.to, branch.distance()), (6, 1, 5));
+    }
+}
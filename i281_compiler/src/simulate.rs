@@ -0,0 +1,737 @@
+//! Simulated execution of a compiled program
+//!
+//! Code memory holds the built instruction words and every word is decoded when it runs, so
+//! instructions entered with `INPUTC` execute like the rest of the program. The input
+//! instructions read the switches on the board from a [`Switches`] source: `INPUTC` and
+//! `INPUTCF` read all 16 switches (SW15-SW0) and `INPUTD` and `INPUTDF` only the low 8 (SW7-SW0).
+//!
+//! The program halts when the pc moves past the last instruction of the program or reaches a
+//! jump to itself, the usual way to end an i281 program.
+
+use std::{collections::VecDeque, fmt, str::FromStr};
+
+use i281_ir::{
+    BranchCondition, Instruction, Ir, Register, ShiftDirection, CODE_MEMORY_SIZE, DATA_MEMORY_SIZE,
+};
+
+use crate::{validate_ir, Diagnostic, MemorySpace};
+
+/// How many instructions are run before giving up on the program halting
+pub const DEFAULT_MAX_STEPS: usize = 10_000;
+
+const CODE_LEN: usize = CODE_MEMORY_SIZE;
+const DATA_LEN: usize = DATA_MEMORY_SIZE;
+
+/// The value set on the switches, SW15 is the most significant bit
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SwitchValue(pub u16);
+
+impl SwitchValue {
+    /// Parse values separated by commas or whitespace, `;` starts a comment until the end of the
+    /// line like in assembly
+    pub fn parse_list(input: &str) -> Result<Vec<Self>, String> {
+        input
+            .lines()
+            .map(|line| line.split(';').next().unwrap_or_default())
+            .flat_map(|line| line.split(|c: char| c == ',' || c.is_whitespace()))
+            .filter(|value| !value.is_empty())
+            .map(str::parse)
+            .collect()
+    }
+}
+
+/// Accepts decimal, `0x` hexadecimal and `0b` binary values, negative values are stored in two's
+/// complement
+impl FromStr for SwitchValue {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let value = s.trim();
+        let (negative, digits) = match value.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, value),
+        };
+        let parsed = match (digits.get(..2), digits.get(2..)) {
+            (Some("0x" | "0X"), Some(hex)) => i32::from_str_radix(hex, 16),
+            (Some("0b" | "0B"), Some(bin)) => i32::from_str_radix(bin, 2),
+            _ => digits.parse::<i32>(),
+        }
+        .map_err(|_| format!("`{value}` is not a number"))?;
+
+        let parsed = if negative { -parsed } else { parsed };
+        match (i16::MIN as i32..=u16::MAX as i32).contains(&parsed) {
+            true => Ok(Self(parsed as u16)),
+            false => Err(format!("`{value}` does not fit in 16 switches")),
+        }
+    }
+}
+
+impl fmt::Display for SwitchValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#06x}", self.0)
+    }
+}
+
+/// An input instruction waiting for the switches to be set
+#[derive(Clone, Copy, Debug)]
+pub struct InputRequest {
+    pub pc: usize,
+    pub ins: Instruction,
+    /// The memory the value is written to, code takes 16 bits and data 8 bits
+    pub space: MemorySpace,
+    pub addr: usize,
+}
+
+impl InputRequest {
+    /// The switches that are read
+    pub fn switches(&self) -> &'static str {
+        switch_names(self.space)
+    }
+}
+
+impl fmt::Display for InputRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "pc {:02}: {} reads {} into {}[{}]",
+            self.pc,
+            self.ins.mnemonic(),
+            self.switches(),
+            self.space,
+            self.addr
+        )
+    }
+}
+
+/// Where the input instructions read the switches from
+pub trait Switches {
+    /// Set the switches for an input instruction, `None` if there is no more input
+    fn read(&mut self, request: &InputRequest) -> Option<SwitchValue>;
+}
+
+/// Switch values given ahead of time, used in order by each input instruction
+#[derive(Clone, Debug, Default)]
+pub struct ScriptedSwitches(VecDeque<SwitchValue>);
+
+impl ScriptedSwitches {
+    pub fn new(values: impl IntoIterator<Item = SwitchValue>) -> Self {
+        Self(values.into_iter().collect())
+    }
+
+    /// The number of values that were not read
+    pub fn remaining(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl Switches for ScriptedSwitches {
+    fn read(&mut self, _: &InputRequest) -> Option<SwitchValue> {
+        self.0.pop_front()
+    }
+}
+
+/// The switches read by an input instruction and what was written to memory
+#[derive(Clone, Copy, Debug)]
+pub struct InputEvent {
+    pub request: InputRequest,
+    pub switches: SwitchValue,
+    /// The bits that were written, only the low byte for data memory
+    pub stored: u16,
+}
+
+impl fmt::Display for InputEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let InputRequest {
+            pc,
+            ins,
+            space,
+            addr,
+        } = self.request;
+        write!(
+            f,
+            "pc {pc:02}: {} {} = {} -> {space}[{addr}]",
+            ins.mnemonic(),
+            switch_names(space),
+            self.switches
+        )?;
+        match space {
+            MemorySpace::Code => write!(f, " = {:?}", Instruction::decode(self.stored)),
+            MemorySpace::Data => {
+                write!(f, " = {}", self.stored as u8 as i8)?;
+                match self.switches.0 >> 8 {
+                    0 => Ok(()),
+                    high => write!(f, " (SW15-SW8 = {high:#04x} ignored)"),
+                }
+            }
+        }
+    }
+}
+
+/// A line of the execution trace
+#[derive(Clone, Copy, Debug)]
+pub enum TraceEvent {
+    /// An instruction was run
+    Step {
+        pc: usize,
+        word: u16,
+        ins: Instruction,
+    },
+    /// An input instruction read the switches
+    Input(InputEvent),
+}
+
+impl fmt::Display for TraceEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Step { pc, word, ins } => write!(f, "pc {pc:02}: {word:016b} {ins:?}"),
+            Self::Input(event) => write!(f, "{event}"),
+        }
+    }
+}
+
+/// The flags set by the alu, branches compare with them
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Flags {
+    pub zero: bool,
+    pub negative: bool,
+    pub overflow: bool,
+    pub carry: bool,
+}
+
+impl Flags {
+    fn from_result(result: i8, overflow: bool, carry: bool) -> Self {
+        Self {
+            zero: result == 0,
+            negative: result < 0,
+            overflow,
+            carry,
+        }
+    }
+
+    /// The conditions are signed comparisons of the last two values subtracted
+    pub fn holds(self, cond: BranchCondition) -> bool {
+        match cond {
+            BranchCondition::Equal => self.zero,
+            BranchCondition::NotEqual => !self.zero,
+            BranchCondition::Greater => !self.zero && self.negative == self.overflow,
+            BranchCondition::GreaterEqual => self.negative == self.overflow,
+        }
+    }
+}
+
+impl fmt::Display for Flags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flag = |set: bool, name: char| if set { name } else { '-' };
+        write!(
+            f,
+            "{}{}{}{}",
+            flag(self.carry, 'C'),
+            flag(self.negative, 'N'),
+            flag(self.overflow, 'V'),
+            flag(self.zero, 'Z')
+        )
+    }
+}
+
+#[derive(Clone, Debug, miette::Diagnostic, thiserror::Error)]
+pub enum SimulationError {
+    #[error("there is no switch input left for `{}` at pc {}", .request.ins.mnemonic(), .request.pc)]
+    #[diagnostic(
+        code(simulator::no_input),
+        help("give a switch value for every input instruction that runs")
+    )]
+    NoInput { request: InputRequest },
+    #[error("`{}` at pc {pc} used {space} address {addr} which is outside of {space} memory", .ins.mnemonic())]
+    #[diagnostic(code(simulator::address_out_of_range))]
+    AddressOutOfRange {
+        pc: usize,
+        ins: Instruction,
+        space: MemorySpace,
+        addr: i64,
+    },
+    #[error("the program did not halt after {steps} steps")]
+    #[diagnostic(
+        code(simulator::step_limit),
+        help("end the program with a jump to itself or raise the step limit")
+    )]
+    StepLimit { steps: usize },
+}
+
+/// An i281 cpu running a program
+#[derive(Clone, Debug)]
+pub struct Simulator<S> {
+    pub code: [u16; CODE_LEN],
+    pub data: [i8; DATA_LEN],
+    pub registers: [i8; 4],
+    pub flags: Flags,
+    pub pc: usize,
+    /// The number of instructions that were run
+    pub steps: usize,
+    pub trace: Vec<TraceEvent>,
+    /// Running past the end of the program halts, the rest of code memory is `NOOP`
+    program_len: usize,
+    switches: S,
+}
+
+impl<S: Switches> Simulator<S> {
+    /// Load the program into memory, the errors [`validate_ir`] finds are returned instead
+    pub fn new(ir: &Ir, switches: S) -> Result<Self, Vec<Diagnostic>> {
+        let diagnostics = validate_ir(ir);
+        if diagnostics.iter().any(Diagnostic::is_error) {
+            return Err(diagnostics);
+        }
+        let memory = ir.data_memory().expect("the data was validated");
+
+        let mut code = [0; CODE_LEN];
+        for (word, ins) in code.iter_mut().zip(&ir.instructions) {
+            *word = ins.build();
+        }
+        let mut data = [0; DATA_LEN];
+        data[..memory.len()].copy_from_slice(&memory);
+
+        Ok(Self {
+            code,
+            data,
+            registers: [0; 4],
+            flags: Flags::default(),
+            pc: 0,
+            steps: 0,
+            trace: Vec::new(),
+            program_len: ir.instructions.len(),
+            switches,
+        })
+    }
+
+    pub fn register(&self, reg: Register) -> i8 {
+        self.registers[reg as usize]
+    }
+
+    /// The switch source, e.g. to check if any scripted values were not used
+    pub fn switches(&self) -> &S {
+        &self.switches
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.pc >= self.program_len
+            || matches!(
+                Instruction::decode(self.code[self.pc]),
+                Instruction::Jump { pc_offset: -1 }
+            )
+    }
+
+    /// Run until the program halts, erroring if it takes more than `max_steps` instructions
+    pub fn run(&mut self, max_steps: usize) -> Result<(), SimulationError> {
+        while !self.is_halted() {
+            if self.steps >= max_steps {
+                return Err(SimulationError::StepLimit { steps: self.steps });
+            }
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    /// Run the instruction at the pc
+    pub fn step(&mut self) -> Result<(), SimulationError> {
+        let pc = self.pc;
+        let word = self.code[pc];
+        let ins = Instruction::decode(word);
+        self.trace.push(TraceEvent::Step { pc, word, ins });
+        self.steps += 1;
+
+        let mut next = pc as i64 + 1;
+        match ins {
+            Instruction::NoOp => {}
+            Instruction::InputC { code_addr } => {
+                self.input(ins, MemorySpace::Code, code_addr as i64)?;
+            }
+            Instruction::InputCF { rx, code_offset } => {
+                let addr = self.register(rx) as i64 + code_offset as i64;
+                self.input(ins, MemorySpace::Code, addr)?;
+            }
+            Instruction::InputD { data_addr } => {
+                self.input(ins, MemorySpace::Data, data_addr as i64)?;
+            }
+            Instruction::InputDF { rx, data_offset } => {
+                let addr = self.register(rx) as i64 + data_offset as i64;
+                self.input(ins, MemorySpace::Data, addr)?;
+            }
+            Instruction::Move { rx, ry } => self.set(rx, self.register(ry)),
+            Instruction::LoadI { rx, value } => self.set(rx, value),
+            Instruction::Add { rx, ry } => self.add(rx, self.register(ry)),
+            Instruction::AddI { rx, value } => self.add(rx, value),
+            Instruction::Sub { rx, ry } => {
+                let result = self.sub(self.register(rx), self.register(ry));
+                self.set(rx, result);
+            }
+            Instruction::SubI { rx, value } => {
+                let result = self.sub(self.register(rx), value);
+                self.set(rx, result);
+            }
+            Instruction::Load { rx, data_addr } => {
+                let addr = self.data_index(ins, data_addr as i64)?;
+                self.set(rx, self.data[addr]);
+            }
+            Instruction::LoadF {
+                rx,
+                ry,
+                data_offset,
+            } => {
+                let addr = self.data_index(ins, self.register(ry) as i64 + data_offset as i64)?;
+                self.set(rx, self.data[addr]);
+            }
+            Instruction::Store { data_addr, rx } => {
+                let addr = self.data_index(ins, data_addr as i64)?;
+                self.data[addr] = self.register(rx);
+            }
+            Instruction::StoreF {
+                ry,
+                data_offset,
+                rx,
+            } => {
+                let addr = self.data_index(ins, self.register(ry) as i64 + data_offset as i64)?;
+                self.data[addr] = self.register(rx);
+            }
+            Instruction::Shift { dir, rx } => self.shift(dir, rx),
+            Instruction::Cmp { rx, ry } => {
+                self.sub(self.register(rx), self.register(ry));
+            }
+            Instruction::Jump { pc_offset } => next += pc_offset as i64,
+            Instruction::Branch { cond, pc_offset } => {
+                if self.flags.holds(cond) {
+                    next += pc_offset as i64;
+                }
+            }
+        }
+
+        // a program that fills code memory halts by running off its end
+        if next != self.program_len as i64 && !(0..CODE_LEN as i64).contains(&next) {
+            return Err(SimulationError::AddressOutOfRange {
+                pc,
+                ins,
+                space: MemorySpace::Code,
+                addr: next,
+            });
+        }
+        self.pc = next as usize;
+        Ok(())
+    }
+
+    fn set(&mut self, reg: Register, value: i8) {
+        self.registers[reg as usize] = value;
+    }
+
+    fn add(&mut self, rx: Register, value: i8) {
+        let x = self.register(rx);
+        let (result, overflow) = x.overflowing_add(value);
+        let carry = (x as u8).checked_add(value as u8).is_none();
+        self.flags = Flags::from_result(result, overflow, carry);
+        self.set(rx, result);
+    }
+
+    /// Subtract and set the flags, shared by `SUB`, `SUBI` and `CMP`
+    fn sub(&mut self, x: i8, y: i8) -> i8 {
+        let (result, overflow) = x.overflowing_sub(y);
+        let carry = (x as u8) < (y as u8);
+        self.flags = Flags::from_result(result, overflow, carry);
+        result
+    }
+
+    /// A right shift keeps the sign bit, the bit shifted out goes to the overflow flag
+    fn shift(&mut self, dir: ShiftDirection, rx: Register) {
+        let x = self.register(rx);
+        let (result, out) = match dir {
+            ShiftDirection::Left => (x << 1, x < 0),
+            ShiftDirection::Right => (x >> 1, x & 1 == 1),
+        };
+        self.flags = Flags::from_result(result, out, self.flags.carry);
+        self.set(rx, result);
+    }
+
+    fn data_index(&self, ins: Instruction, addr: i64) -> Result<usize, SimulationError> {
+        match (0..DATA_LEN as i64).contains(&addr) {
+            true => Ok(addr as usize),
+            false => Err(SimulationError::AddressOutOfRange {
+                pc: self.pc,
+                ins,
+                space: MemorySpace::Data,
+                addr,
+            }),
+        }
+    }
+
+    /// Read the switches and write the value, 16 bits to code memory or the low 8 to data memory
+    fn input(
+        &mut self,
+        ins: Instruction,
+        space: MemorySpace,
+        addr: i64,
+    ) -> Result<(), SimulationError> {
+        let len = match space {
+            MemorySpace::Code => CODE_LEN,
+            MemorySpace::Data => DATA_LEN,
+        };
+        if !(0..len as i64).contains(&addr) {
+            return Err(SimulationError::AddressOutOfRange {
+                pc: self.pc,
+                ins,
+                space,
+                addr,
+            });
+        }
+
+        let request = InputRequest {
+            pc: self.pc,
+            ins,
+            space,
+            addr: addr as usize,
+        };
+        let switches = self
+            .switches
+            .read(&request)
+            .ok_or(SimulationError::NoInput { request })?;
+        let stored = match space {
+            MemorySpace::Code => {
+                self.code[request.addr] = switches.0;
+                switches.0
+            }
+            MemorySpace::Data => {
+                let byte = switches.0 as u8;
+                self.data[request.addr] = byte as i8;
+                byte as u16
+            }
+        };
+        self.trace.push(TraceEvent::Input(InputEvent {
+            request,
+            switches,
+            stored,
+        }));
+        Ok(())
+    }
+}
+
+impl<S> fmt::Display for Simulator<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "pc: {:02}  steps: {}", self.pc, self.steps)?;
+        let [a, b, c, d] = self.registers;
+        writeln!(
+            f,
+            "registers: A={a} B={b} C={c} D={d}  flags: {}",
+            self.flags
+        )?;
+        write!(f, "data:")?;
+        for byte in self.data.iter() {
+            write!(f, " {byte}")?;
+        }
+        Ok(())
+    }
+}
+
+fn switch_names(space: MemorySpace) -> &'static str {
+    match space {
+        MemorySpace::Code => "SW15-SW0",
+        MemorySpace::Data => "SW7-SW0",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use i281_ir::{Instruction, Ir, Register, Variable};
+
+    use super::{
+        InputRequest, ScriptedSwitches, SimulationError, Simulator, SwitchValue, Switches,
+        TraceEvent,
+    };
+    use crate::{Diagnostic, Error, MemorySpace};
+
+    fn run(instructions: Vec<Instruction>, switches: &[u16]) -> Simulator<ScriptedSwitches> {
+        let ir = Ir {
+            variables: vec![Variable {
+                data_addr: 0,
+                data: vec![0; 4],
+            }],
+            instructions,
+        };
+        let switches = ScriptedSwitches::new(switches.iter().map(|&v| SwitchValue(v)));
+        let mut sim = Simulator::new(&ir, switches).unwrap();
+        sim.run(100).unwrap();
+        sim
+    }
+
+    #[test]
+    fn simulate1() {
+        // every word decodes to the instruction it was built from
+        for word in [
+            0x0000, 0x1105, 0x387f, 0x9e80, 0xbcff, 0xc500, 0xe0ff, 0xf3fe,
+        ] {
+            assert_eq!(Instruction::decode(word).build(), word);
+        }
+        assert!(matches!(
+            Instruction::decode(0xb7fd),
+            Instruction::StoreF {
+                ry: Register::D,
+                data_offset: -3,
+                rx: Register::B
+            }
+        ));
+    }
+
+    #[test]
+    fn simulate2() {
+        // data input only uses the low byte of the switches
+        let sim = run(
+            vec![
+                Instruction::InputD { data_addr: 1 },
+                Instruction::LoadI {
+                    rx: Register::A,
+                    value: 2,
+                },
+                Instruction::InputDF {
+                    rx: Register::A,
+                    data_offset: 1,
+                },
+            ],
+            &[0x12ff, 7],
+        );
+        assert_eq!(sim.data[..4], [0, -1, 0, 7]);
+        let inputs: Vec<_> = sim
+            .trace
+            .iter()
+            .filter_map(|event| match event {
+                TraceEvent::Input(input) => Some(input.to_string()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            inputs,
+            [
+                "pc 00: INPUTD SW7-SW0 = 0x12ff -> data[1] = -1 (SW15-SW8 = 0x12 ignored)",
+                "pc 02: INPUTDF SW7-SW0 = 0x0007 -> data[3] = 7",
+            ]
+        );
+    }
+
+    #[test]
+    fn simulate3() {
+        // code input stores all 16 switches and the new instruction runs
+        let load = Instruction::LoadI {
+            rx: Register::B,
+            value: 42,
+        };
+        let sim = run(
+            vec![
+                Instruction::InputC { code_addr: 1 },
+                Instruction::NoOp,
+                Instruction::Jump { pc_offset: -1 },
+            ],
+            &[load.build()],
+        );
+        assert_eq!(sim.register(Register::B), 42);
+        assert_eq!(sim.pc, 2);
+        assert_eq!(sim.switches().remaining(), 0);
+    }
+
+    #[test]
+    fn simulate4() {
+        // count down from 3 with a compare and branch
+        let sim = run(
+            vec![
+                Instruction::LoadI {
+                    rx: Register::A,
+                    value: 3,
+                },
+                Instruction::SubI {
+                    rx: Register::A,
+                    value: 1,
+                },
+                Instruction::AddI {
+                    rx: Register::C,
+                    value: 10,
+                },
+                Instruction::Cmp {
+                    rx: Register::A,
+                    ry: Register::D,
+                },
+                Instruction::Branch {
+                    cond: i281_ir::BranchCondition::Greater,
+                    pc_offset: -4,
+                },
+            ],
+            &[],
+        );
+        assert_eq!(sim.register(Register::C), 30);
+        assert_eq!(sim.steps, 13);
+        assert!(sim.flags.zero);
+    }
+
+    #[test]
+    fn simulate5() {
+        assert_eq!(
+            SwitchValue::parse_list("0x12, 7 -1 ; comment\n0b101,"),
+            Ok(vec![
+                SwitchValue(0x12),
+                SwitchValue(7),
+                SwitchValue(0xffff),
+                SwitchValue(5)
+            ])
+        );
+        assert!("65536".parse::<SwitchValue>().is_err());
+        assert!("x".parse::<SwitchValue>().is_err());
+
+        struct Never;
+        impl Switches for Never {
+            fn read(&mut self, request: &InputRequest) -> Option<SwitchValue> {
+                assert_eq!(request.space, MemorySpace::Code);
+                assert_eq!(request.switches(), "SW15-SW0");
+                None
+            }
+        }
+        let ir = Ir {
+            variables: Vec::new(),
+            instructions: vec![Instruction::InputC { code_addr: 0 }],
+        };
+        let mut sim = Simulator::new(&ir, Never).unwrap();
+        assert!(matches!(sim.run(10), Err(SimulationError::NoInput { .. })));
+    }
+
+    #[test]
+    fn simulate6() {
+        // a program filling all of code memory halts after its last instruction
+        let sim = run(vec![Instruction::NoOp; 32], &[]);
+        assert!(sim.is_halted());
+        assert_eq!((sim.pc, sim.steps), (32, 32));
+
+        // only offsets from a register can leave data memory in a validated ir
+        let ir = Ir {
+            variables: Vec::new(),
+            instructions: vec![Instruction::StoreF {
+                ry: Register::B,
+                data_offset: 16,
+                rx: Register::A,
+            }],
+        };
+        let mut sim = Simulator::new(&ir, ScriptedSwitches::default()).unwrap();
+        assert!(matches!(
+            sim.run(10),
+            Err(SimulationError::AddressOutOfRange { addr: 16, .. })
+        ));
+    }
+
+    #[test]
+    fn simulate7() {
+        // data that doesn't fit is not loaded, not even in part
+        let ir = Ir {
+            variables: vec![Variable {
+                data_addr: 10,
+                data: vec![1; 10],
+            }],
+            instructions: vec![Instruction::NoOp],
+        };
+        let Err(diagnostics) = Simulator::new(&ir, ScriptedSwitches::default()) else {
+            panic!("the data does not fit");
+        };
+        assert!(matches!(
+            diagnostics.as_slice(),
+            [Diagnostic::Error(Error::TooMuchUserData { found: 20, .. })]
+        ));
+    }
+}
@@ -0,0 +1,149 @@
+use i281_ir::{Instruction, Ir};
+
+use crate::{
+    diagnostics::{Diagnostic, Error},
+    MemorySpace, CODE_ADDR_MAX, DATA_ADDR_MAX,
+};
+
+/// Check an ir that did not come from the analyzer (e.g. one that was deserialized)
+///
+/// The analyzer only ever produces ir that passes these checks.
+pub fn validate_ir(ir: &Ir) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    if ir.instructions.len() > CODE_ADDR_MAX as usize {
+        diagnostics.push(
+            Error::TooManyInstructions {
+                found: ir.instructions.len(),
+                max: CODE_ADDR_MAX as usize,
+                span: None,
+            }
+            .into(),
+        );
+    }
+
+    // checked before anything builds the contents of data memory from the variables
+    let data_len = ir.data_len().unwrap_or(usize::MAX);
+    if data_len > DATA_ADDR_MAX as usize {
+        diagnostics.push(
+            Error::TooMuchUserData {
+                found: data_len,
+                max: DATA_ADDR_MAX as usize,
+                span: None,
+            }
+            .into(),
+        );
+    }
+
+    for (index, &ins) in ir.instructions.iter().enumerate() {
+        let in_bounds = match ins {
+            // the same ranges the analyzer checks addresses against
+            Instruction::InputC { code_addr } => MemorySpace::Code.contains(code_addr as i64),
+            Instruction::InputD { data_addr }
+            | Instruction::Load { data_addr, .. }
+            | Instruction::Store { data_addr, .. } => MemorySpace::Data.contains(data_addr as i64),
+            Instruction::Jump { pc_offset } | Instruction::Branch { pc_offset, .. } => {
+                // a jump right after the last instruction halts, like a label at the end of code
+                let target = index as isize + 1 + pc_offset as isize;
+                if !(0..=ir.instructions.len() as isize).contains(&target) {
+                    diagnostics.push(Error::IrJumpOOB { index, ins }.into());
+                }
+                true
+            }
+            _ => true,
+        };
+        if !in_bounds {
+            diagnostics.push(Error::IrAddressOOB { index, ins }.into());
+        }
+    }
+
+    diagnostics
+}
+
+#[cfg(test)]
+mod test {
+    use i281_ir::{BranchCondition, Instruction, Ir, Register, Variable};
+
+    use super::validate_ir;
+    use crate::{Diagnostic, Error};
+
+    #[test]
+    fn validate_ir1() {
+        let ir = Ir {
+            variables: vec![Variable {
+                data_addr: 0,
+                data: vec![1, 2],
+            }],
+            instructions: vec![
+                Instruction::Load {
+                    rx: Register::A,
+                    data_addr: 1,
+                },
+                Instruction::Branch {
+                    cond: BranchCondition::Equal,
+                    pc_offset: -2,
+                },
+                Instruction::Jump { pc_offset: 0 },
+                Instruction::Load {
+                    rx: Register::B,
+                    data_addr: 15,
+                },
+                Instruction::InputC { code_addr: 31 },
+            ],
+        };
+        assert!(validate_ir(&ir).is_empty());
+    }
+
+    #[test]
+    fn validate_ir2() {
+        let ir = Ir {
+            variables: vec![Variable {
+                data_addr: 0,
+                data: vec![0; 20],
+            }],
+            instructions: vec![
+                Instruction::Store {
+                    data_addr: 100,
+                    rx: Register::A,
+                },
+                Instruction::Jump { pc_offset: 5 },
+                Instruction::Load {
+                    rx: Register::B,
+                    data_addr: 16,
+                },
+                Instruction::InputC { code_addr: 32 },
+            ],
+        };
+        let diagnostics = validate_ir(&ir);
+        assert!(matches!(
+            diagnostics.as_slice(),
+            [
+                Diagnostic::Error(Error::TooMuchUserData { found: 20, .. }),
+                Diagnostic::Error(Error::IrAddressOOB { index: 0, .. }),
+                Diagnostic::Error(Error::IrJumpOOB { index: 1, .. }),
+                Diagnostic::Error(Error::IrAddressOOB { index: 2, .. }),
+                Diagnostic::Error(Error::IrAddressOOB { index: 3, .. }),
+            ]
+        ));
+    }
+
+    #[test]
+    fn validate_ir3() {
+        // the end of the variable doesn't fit in a usize, this is reported instead of overflowing
+        let ir = Ir {
+            variables: vec![Variable {
+                data_addr: usize::MAX,
+                data: vec![1],
+            }],
+            instructions: Vec::new(),
+        };
+        assert!(matches!(
+            validate_ir(&ir).as_slice(),
+            [Diagnostic::Error(Error::TooMuchUserData {
+                found: usize::MAX,
+                ..
+            })]
+        ));
+        assert!(ir.data_memory().is_none());
+    }
+}
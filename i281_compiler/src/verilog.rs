@@ -1,6 +1,6 @@
 use std::{borrow::Cow, io};
 
-use i281_ir::{Instruction, Ir, Variable};
+use i281_ir::{Instruction, Ir};
 
 use crate::{BLOCK_SIZE, CODE_ADDR_MAX, DATA_ADDR_MAX};
 
@@ -86,15 +86,22 @@ where
 {
     let insts = ir
         .instructions
-        .into_iter()
+        .iter()
+        .copied()
         .map(Instruction::build)
-        .chain(std::iter::repeat(0).take(CODE_ADDR_MAX as usize)) // fill with at least max amt
+        .chain(std::iter::repeat_n(0, CODE_ADDR_MAX as usize)) // fill with at least max amt
         .enumerate();
+    // gaps left by `.org` and `@` are zero like the rest of the unused memory
     let vars = ir
-        .variables
+        .data_memory()
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the variables do not fit in data memory",
+            )
+        })?
         .into_iter()
-        .flat_map(Variable::into_data)
-        .chain(std::iter::repeat(0).take(DATA_ADDR_MAX as usize)) // fill with at least max amt
+        .chain(std::iter::repeat_n(0, DATA_ADDR_MAX as usize)) // fill with at least max amt
         .enumerate();
 
     write_file(code_low.to, &code_low.name, 16, |out| {
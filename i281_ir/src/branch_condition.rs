@@ -17,3 +17,15 @@ impl From<BranchCondition> for u16 {
         Self::from(v as u8)
     }
 }
+
+/// Only the low 2 bits of the field are used
+impl From<u16> for BranchCondition {
+    fn from(v: u16) -> Self {
+        match v & 0b11 {
+            0b00 => Self::Equal,
+            0b01 => Self::NotEqual,
+            0b10 => Self::Greater,
+            _ => Self::GreaterEqual,
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{BranchCondition, InputMode, OpCode, Register, ShiftDirection};
+use crate::{OpCode, Register};
 
 pub struct InstructionBuilder(u16);
 
@@ -8,66 +8,75 @@ impl InstructionBuilder {
     }
 
     pub fn set_opcode(&mut self, opcode: OpCode) -> &mut Self {
-        self.set_a(u16::from(opcode));
-        self
+        self.set_a(u16::from(opcode))
     }
 
-    pub fn set_rx(&mut self, rx: Register) -> &mut Self {
-        self.set_b(u16::from(rx));
-        self
+    pub fn finish(&mut self) -> u16 {
+        self.0
     }
+}
+
+impl InstructionBuilder {
+    //                    A___~B_~C_~D_______
+    const ZERO_A: u16 = 0b0000_11_11_11111111;
+    const ZERO_B: u16 = 0b1111_00_11_11111111;
+    const ZERO_C: u16 = 0b1111_11_00_11111111;
+    const ZERO_D: u16 = 0b1111_11_11_00000000;
 
-    pub fn set_ry(&mut self, ry: Register) -> &mut Self {
-        self.set_c(u16::from(ry));
+    fn set_a(&mut self, val: u16) -> &mut Self {
+        self.0 = (self.0 & Self::ZERO_A) | ((val << 12) & !Self::ZERO_A);
         self
     }
 
-    pub fn set_branch(&mut self, branch: BranchCondition) -> &mut Self {
-        self.set_c(u16::from(branch));
+    pub fn set_b(&mut self, val: u16) -> &mut Self {
+        self.0 = (self.0 & Self::ZERO_B) | ((val << 10) & !Self::ZERO_B);
         self
     }
 
-    pub fn set_input(&mut self, input: InputMode) -> &mut Self {
-        self.set_c(u16::from(input));
+    pub fn set_c(&mut self, val: u16) -> &mut Self {
+        self.0 = (self.0 & Self::ZERO_C) | ((val << 8) & !Self::ZERO_C);
         self
     }
 
-    pub fn set_shift(&mut self, shift: ShiftDirection) -> &mut Self {
-        self.set_c(u16::from(shift));
+    pub fn set_d(&mut self, val: u16) -> &mut Self {
+        self.0 = (self.0 & Self::ZERO_D) | (val & !Self::ZERO_D);
         self
     }
+}
 
-    pub fn set_data(&mut self, data: i8) -> &mut Self {
-        let data: u8 = data as u8; // intentional overflow cast this will use twos complement
-        self.set_d(u16::from(data));
-        self
+/// Splits a machine code word back into its fields, the inverse of [`InstructionBuilder`]
+pub struct InstructionFields(u16);
+
+impl InstructionFields {
+    pub fn new(word: u16) -> Self {
+        Self(word)
     }
 
-    pub fn finish(&mut self) -> u16 {
-        self.0
+    pub fn opcode(&self) -> OpCode {
+        OpCode::from(self.0 >> 12)
     }
-}
 
-impl InstructionBuilder {
-    //                    A___~B_~C_~D_______
-    const ZERO_A: u16 = 0b0000_11_11_11111111;
-    const ZERO_B: u16 = 0b1111_00_11_11111111;
-    const ZERO_C: u16 = 0b1111_11_00_11111111;
-    const ZERO_D: u16 = 0b1111_11_11_00000000;
+    pub fn b(&self) -> u16 {
+        (self.0 >> 10) & 0b11
+    }
 
-    fn set_a(&mut self, val: u16) {
-        self.0 = (self.0 & Self::ZERO_A) | ((val << 12) & !Self::ZERO_A);
+    pub fn c(&self) -> u16 {
+        (self.0 >> 8) & 0b11
     }
 
-    fn set_b(&mut self, val: u16) {
-        self.0 = (self.0 & Self::ZERO_B) | ((val << 10) & !Self::ZERO_B);
+    pub fn d(&self) -> u16 {
+        self.0 & 0xff
     }
 
-    fn set_c(&mut self, val: u16) {
-        self.0 = (self.0 & Self::ZERO_C) | ((val << 8) & !Self::ZERO_C);
+    pub fn rx(&self) -> Register {
+        Register::from(self.b())
     }
 
-    fn set_d(&mut self, val: u16) {
-        self.0 = (self.0 & Self::ZERO_D) | (val & !Self::ZERO_D);
+    pub fn ry(&self) -> Register {
+        Register::from(self.c())
+    }
+
+    pub fn data(&self) -> i8 {
+        self.d() as u8 as i8
     }
 }
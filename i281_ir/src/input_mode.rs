@@ -13,3 +13,15 @@ impl From<InputMode> for u16 {
         Self::from(v as u8)
     }
 }
+
+/// Only the low 2 bits of the field are used
+impl From<u16> for InputMode {
+    fn from(v: u16) -> Self {
+        match v & 0b11 {
+            0b00 => Self::C,
+            0b01 => Self::CF,
+            0b10 => Self::D,
+            _ => Self::DF,
+        }
+    }
+}
@@ -1,25 +1,27 @@
-use crate::{
-    builder::InstructionBuilder, BranchCondition, InputMode, OpCode, Register, ShiftDirection,
-};
+use crate::{isa::InstructionInfo, BranchCondition, OpCode, Register, ShiftDirection};
 
+/// A machine code instruction, `V` is the value in the data field
+///
+/// The analyzer produces `Instruction<Value>` where values can still refer to symbols, see
+/// [`SymbolicIr`](crate::SymbolicIr), resolving them gives the `Instruction<i8>` that is encoded.
 #[derive(Clone, Copy, Debug)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 #[cfg_attr(feature = "serde", serde(tag = "opcode", rename_all = "UPPERCASE"))]
-pub enum Instruction {
+pub enum Instruction<V = i8> {
     NoOp,
     InputC {
-        code_addr: i8,
+        code_addr: V,
     },
     InputCF {
         rx: Register,
-        code_offset: i8,
+        code_offset: V,
     },
     InputD {
-        data_addr: i8,
+        data_addr: V,
     },
     InputDF {
         rx: Register,
-        data_offset: i8,
+        data_offset: V,
     },
     Move {
         rx: Register,
@@ -27,7 +29,7 @@ pub enum Instruction {
     },
     LoadI {
         rx: Register,
-        value: i8,
+        value: V,
     },
     Add {
         rx: Register,
@@ -35,7 +37,7 @@ pub enum Instruction {
     },
     AddI {
         rx: Register,
-        value: i8,
+        value: V,
     },
     Sub {
         rx: Register,
@@ -43,24 +45,24 @@ pub enum Instruction {
     },
     SubI {
         rx: Register,
-        value: i8,
+        value: V,
     },
     Load {
         rx: Register,
-        data_addr: i8,
+        data_addr: V,
     },
     LoadF {
         rx: Register,
         ry: Register,
-        data_offset: i8,
+        data_offset: V,
     },
     Store {
-        data_addr: i8,
+        data_addr: V,
         rx: Register,
     },
     StoreF {
         ry: Register,
-        data_offset: i8,
+        data_offset: V,
         rx: Register,
     },
     Shift {
@@ -72,84 +74,252 @@ pub enum Instruction {
         ry: Register,
     },
     Jump {
-        pc_offset: i8,
+        pc_offset: V,
     },
     Branch {
         cond: BranchCondition,
-        pc_offset: i8,
+        pc_offset: V,
     },
 }
 
 impl Instruction {
     pub fn build(self) -> u16 {
-        let mut builder = InstructionBuilder::new();
+        let (rx, ry, data) = self.fields();
+        self.info().layout.encode(rx, ry, data)
+    }
 
-        let opcode = self.opcode();
-        builder.set_opcode(opcode);
+    /// Decode a machine code word, every word is a valid instruction
+    ///
+    /// Unused fields are ignored so building the result may not give back the same word.
+    pub fn decode(word: u16) -> Self {
+        InstructionInfo::of_word(word).decode(word)
+    }
 
+    /// The values of the `RX`, `RY` and data fields, ones the instruction doesn't use are zero
+    fn fields(self) -> (Register, Register, i8) {
+        let (a, zero) = (Register::A, 0);
         match self {
-            Self::NoOp => &mut builder,
-            Self::InputC { code_addr } => builder.set_input(InputMode::C).set_data(code_addr),
-            Self::InputCF { rx, code_offset } => builder
-                .set_input(InputMode::CF)
-                .set_rx(rx)
-                .set_data(code_offset),
-            Self::InputD { data_addr } => builder.set_input(InputMode::D).set_data(data_addr),
-            Self::InputDF { rx, data_offset } => builder
-                .set_input(InputMode::DF)
-                .set_rx(rx)
-                .set_data(data_offset),
-            Self::Move { rx, ry } => builder.set_rx(rx).set_ry(ry),
-            Self::LoadI { rx, value } => builder.set_rx(rx).set_data(value),
-            Self::Add { rx, ry } => builder.set_rx(rx).set_ry(ry),
-            Self::AddI { rx, value } => builder.set_rx(rx).set_data(value),
-            Self::Sub { rx, ry } => builder.set_rx(rx).set_ry(ry),
-            Self::SubI { rx, value } => builder.set_rx(rx).set_data(value),
-            Self::Load { rx, data_addr } => builder.set_rx(rx).set_data(data_addr),
+            Self::NoOp => (a, a, zero),
+            Self::InputC { code_addr } => (a, a, code_addr),
+            Self::InputCF { rx, code_offset } => (rx, a, code_offset),
+            Self::InputD { data_addr } => (a, a, data_addr),
+            Self::InputDF { rx, data_offset } => (rx, a, data_offset),
+            Self::Move { rx, ry }
+            | Self::Add { rx, ry }
+            | Self::Sub { rx, ry }
+            | Self::Cmp { rx, ry } => (rx, ry, zero),
+            Self::LoadI { rx, value } | Self::AddI { rx, value } | Self::SubI { rx, value } => {
+                (rx, a, value)
+            }
+            Self::Load { rx, data_addr } | Self::Store { data_addr, rx } => (rx, a, data_addr),
             Self::LoadF {
                 rx,
                 ry,
                 data_offset,
-            } => builder.set_rx(rx).set_ry(ry).set_data(data_offset),
-            Self::Store { data_addr, rx } => builder.set_rx(rx).set_data(data_addr),
-            Self::StoreF {
+            }
+            | Self::StoreF {
                 ry,
                 data_offset,
                 rx,
-            } => builder.set_rx(rx).set_ry(ry).set_data(data_offset),
-            Self::Shift { dir: mode, rx } => builder.set_shift(mode).set_rx(rx),
-            Self::Cmp { rx, ry } => builder.set_rx(rx).set_ry(ry),
-            Self::Jump { pc_offset } => builder.set_data(pc_offset),
-            Self::Branch {
-                cond: mode,
-                pc_offset,
-            } => builder.set_branch(mode).set_data(pc_offset),
-        };
+            } => (rx, ry, data_offset),
+            Self::Shift { rx, .. } => (rx, a, zero),
+            Self::Jump { pc_offset } | Self::Branch { pc_offset, .. } => (a, a, pc_offset),
+        }
+    }
+}
+
+impl<V> Instruction<V> {
+    pub fn opcode(&self) -> OpCode {
+        self.info().layout.opcode
+    }
 
-        builder.finish()
+    /// Everything known about this kind of instruction
+    pub fn info(&self) -> &'static InstructionInfo {
+        InstructionInfo::lookup(self.mnemonic()).expect("every instruction is in the isa table")
     }
 
-    pub fn opcode(self) -> OpCode {
+    pub fn mnemonic(&self) -> &'static str {
         match self {
-            Self::NoOp => OpCode::NoOp,
-            Self::InputC { .. }
-            | Self::InputCF { .. }
-            | Self::InputD { .. }
-            | Self::InputDF { .. } => OpCode::Input,
-            Self::Move { .. } => OpCode::Move,
-            Self::LoadI { .. } => OpCode::LoadI,
-            Self::Add { .. } => OpCode::Add,
-            Self::AddI { .. } => OpCode::AddI,
-            Self::Sub { .. } => OpCode::Sub,
-            Self::SubI { .. } => OpCode::SubI,
-            Self::Load { .. } => OpCode::Load,
-            Self::LoadF { .. } => OpCode::LoadF,
-            Self::Store { .. } => OpCode::Store,
-            Self::StoreF { .. } => OpCode::StoreF,
-            Self::Shift { .. } => OpCode::Shift,
-            Self::Cmp { .. } => OpCode::Cmp,
-            Self::Jump { .. } => OpCode::Jump,
-            Self::Branch { .. } => OpCode::Branch,
+            Self::NoOp => "NOOP",
+            Self::InputC { .. } => "INPUTC",
+            Self::InputCF { .. } => "INPUTCF",
+            Self::InputD { .. } => "INPUTD",
+            Self::InputDF { .. } => "INPUTDF",
+            Self::Move { .. } => "MOVE",
+            Self::LoadI { .. } => "LOADI",
+            Self::Add { .. } => "ADD",
+            Self::AddI { .. } => "ADDI",
+            Self::Sub { .. } => "SUB",
+            Self::SubI { .. } => "SUBI",
+            Self::Load { .. } => "LOAD",
+            Self::LoadF { .. } => "LOADF",
+            Self::Store { .. } => "STORE",
+            Self::StoreF { .. } => "STOREF",
+            Self::Shift {
+                dir: ShiftDirection::Left,
+                ..
+            } => "SHIFTL",
+            Self::Shift {
+                dir: ShiftDirection::Right,
+                ..
+            } => "SHIFTR",
+            Self::Cmp { .. } => "CMP",
+            Self::Jump { .. } => "JUMP",
+            Self::Branch { cond, .. } => match cond {
+                BranchCondition::Equal => "BRE",
+                BranchCondition::NotEqual => "BRNE",
+                BranchCondition::Greater => "BRG",
+                BranchCondition::GreaterEqual => "BRGE",
+            },
         }
     }
+
+    /// The value in the data field if the instruction has one
+    pub fn value(&self) -> Option<&V> {
+        match self {
+            Self::InputC { code_addr: value }
+            | Self::InputCF {
+                code_offset: value, ..
+            }
+            | Self::InputD { data_addr: value }
+            | Self::InputDF {
+                data_offset: value, ..
+            }
+            | Self::LoadI { value, .. }
+            | Self::AddI { value, .. }
+            | Self::SubI { value, .. }
+            | Self::Load {
+                data_addr: value, ..
+            }
+            | Self::LoadF {
+                data_offset: value, ..
+            }
+            | Self::Store {
+                data_addr: value, ..
+            }
+            | Self::StoreF {
+                data_offset: value, ..
+            }
+            | Self::Jump { pc_offset: value }
+            | Self::Branch {
+                pc_offset: value, ..
+            } => Some(value),
+            Self::NoOp
+            | Self::Move { .. }
+            | Self::Add { .. }
+            | Self::Sub { .. }
+            | Self::Shift { .. }
+            | Self::Cmp { .. } => None,
+        }
+    }
+
+    /// The value in the data field if the instruction has one, for changing it in place
+    pub fn value_mut(&mut self) -> Option<&mut V> {
+        match self {
+            Self::InputC { code_addr: value }
+            | Self::InputCF {
+                code_offset: value, ..
+            }
+            | Self::InputD { data_addr: value }
+            | Self::InputDF {
+                data_offset: value, ..
+            }
+            | Self::LoadI { value, .. }
+            | Self::AddI { value, .. }
+            | Self::SubI { value, .. }
+            | Self::Load {
+                data_addr: value, ..
+            }
+            | Self::LoadF {
+                data_offset: value, ..
+            }
+            | Self::Store {
+                data_addr: value, ..
+            }
+            | Self::StoreF {
+                data_offset: value, ..
+            }
+            | Self::Jump { pc_offset: value }
+            | Self::Branch {
+                pc_offset: value, ..
+            } => Some(value),
+            Self::NoOp
+            | Self::Move { .. }
+            | Self::Add { .. }
+            | Self::Sub { .. }
+            | Self::Shift { .. }
+            | Self::Cmp { .. } => None,
+        }
+    }
+
+    /// Replace the value in the data field, `f` is only called if the instruction has one
+    pub fn try_map<W, E>(self, f: impl FnOnce(V) -> Result<W, E>) -> Result<Instruction<W>, E> {
+        Ok(match self {
+            Self::NoOp => Instruction::NoOp,
+            Self::InputC { code_addr } => Instruction::InputC {
+                code_addr: f(code_addr)?,
+            },
+            Self::InputCF { rx, code_offset } => Instruction::InputCF {
+                rx,
+                code_offset: f(code_offset)?,
+            },
+            Self::InputD { data_addr } => Instruction::InputD {
+                data_addr: f(data_addr)?,
+            },
+            Self::InputDF { rx, data_offset } => Instruction::InputDF {
+                rx,
+                data_offset: f(data_offset)?,
+            },
+            Self::Move { rx, ry } => Instruction::Move { rx, ry },
+            Self::LoadI { rx, value } => Instruction::LoadI {
+                rx,
+                value: f(value)?,
+            },
+            Self::Add { rx, ry } => Instruction::Add { rx, ry },
+            Self::AddI { rx, value } => Instruction::AddI {
+                rx,
+                value: f(value)?,
+            },
+            Self::Sub { rx, ry } => Instruction::Sub { rx, ry },
+            Self::SubI { rx, value } => Instruction::SubI {
+                rx,
+                value: f(value)?,
+            },
+            Self::Load { rx, data_addr } => Instruction::Load {
+                rx,
+                data_addr: f(data_addr)?,
+            },
+            Self::LoadF {
+                rx,
+                ry,
+                data_offset,
+            } => Instruction::LoadF {
+                rx,
+                ry,
+                data_offset: f(data_offset)?,
+            },
+            Self::Store { data_addr, rx } => Instruction::Store {
+                data_addr: f(data_addr)?,
+                rx,
+            },
+            Self::StoreF {
+                ry,
+                data_offset,
+                rx,
+            } => Instruction::StoreF {
+                ry,
+                data_offset: f(data_offset)?,
+                rx,
+            },
+            Self::Shift { dir, rx } => Instruction::Shift { dir, rx },
+            Self::Cmp { rx, ry } => Instruction::Cmp { rx, ry },
+            Self::Jump { pc_offset } => Instruction::Jump {
+                pc_offset: f(pc_offset)?,
+            },
+            Self::Branch { cond, pc_offset } => Instruction::Branch {
+                cond,
+                pc_offset: f(pc_offset)?,
+            },
+        })
+    }
 }
@@ -0,0 +1,527 @@
+use crate::{
+    BranchCondition, Instruction, Ir, Register, ResolveError, ShiftDirection, Symbol, SymbolicIr,
+    SymbolicLabel, SymbolicVariable, Value, CODE_MEMORY_SIZE, DATA_MEMORY_SIZE,
+};
+
+/// A place in the code created by [`IrBuilder::new_label`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Label(usize);
+
+/// A variable created by [`IrBuilder::var`], or an address within it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Var {
+    index: usize,
+    /// `None` if adding up the offsets overflowed
+    offset: Option<i16>,
+}
+
+impl Var {
+    /// The address `by` bytes after this one, e.g. an element of an array
+    pub fn offset(self, by: i16) -> Self {
+        Self {
+            offset: self.offset.and_then(|offset| offset.checked_add(by)),
+            ..self
+        }
+    }
+}
+
+#[derive(Clone, Debug, miette::Diagnostic, thiserror::Error)]
+pub enum BuildError {
+    #[error("label `{label}` is used but never bound")]
+    #[diagnostic(code(ir::unbound_label))]
+    UnboundLabel { label: usize },
+    #[error("label `{label}` is bound more than once")]
+    #[diagnostic(code(ir::label_bound_twice))]
+    LabelBoundTwice { label: usize },
+    #[error("label `{label}` was not created by this builder")]
+    #[diagnostic(code(ir::unknown_label))]
+    UnknownLabel { label: usize },
+    #[error("variable `{index}` was not created by this builder")]
+    #[diagnostic(code(ir::unknown_variable))]
+    UnknownVariable { index: usize },
+    #[error("the offset into variable `{name}` overflows")]
+    #[diagnostic(code(ir::offset_overflow))]
+    OffsetOverflow { name: String },
+    #[error("variable `{name}` is defined more than once")]
+    #[diagnostic(code(ir::duplicate_variable))]
+    DuplicateVariable { name: String },
+    #[error("too many instructions amount: `{found}` maximum allowed: `{max}`")]
+    #[diagnostic(code(ir::too_many_instructions))]
+    TooManyInstructions { found: usize, max: usize },
+    #[error("too much user data amount: `{found}` maximum allowed: `{max}`")]
+    #[diagnostic(code(ir::too_much_user_data))]
+    TooMuchUserData { found: usize, max: usize },
+    #[error("instruction `{index}` accesses an address that is out of bounds: `{ins:?}`")]
+    #[diagnostic(
+        code(ir::address_out_of_bounds),
+        help("every address must be within the memory it is accessing")
+    )]
+    AddressOutOfBounds { index: usize, ins: Instruction },
+    #[error("instruction `{index}` jumps outside of the program: `{ins:?}`")]
+    #[diagnostic(code(ir::jump_out_of_bounds))]
+    JumpOutOfBounds { index: usize, ins: Instruction },
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Resolve(#[from] ResolveError),
+}
+
+/// Build a program from rust without writing assembly
+///
+/// Labels and variables are handles, the offsets of jumps and the addresses of variables are
+/// filled in by [`IrBuilder::finish`] which also checks the program and every address it uses
+/// fit in memory.
+///
+/// ```
+/// use i281_ir::{BranchCondition, IrBuilder, Register};
+///
+/// let mut b = IrBuilder::new();
+/// let arr = b.var("arr", &[1, 2, 3]);
+/// let l = b.new_label();
+/// b.bind(l);
+/// b.load(Register::A, arr.offset(2));
+/// b.sub_i(Register::A, 1);
+/// b.branch(BranchCondition::NotEqual, l);
+/// let ir = b.finish().unwrap();
+/// assert_eq!(ir.instructions.len(), 3);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct IrBuilder {
+    variables: Vec<SymbolicVariable>,
+    /// Where every label is bound, if it is
+    labels: Vec<Option<usize>>,
+    instructions: Vec<Instruction<Value>>,
+    /// Reported by `finish` so building can go on without checking every call
+    error: Option<BuildError>,
+}
+
+impl IrBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a label that can be jumped to before it is bound
+    pub fn new_label(&mut self) -> Label {
+        self.labels.push(None);
+        Label(self.labels.len() - 1)
+    }
+
+    /// Place a label in front of the next instruction, or after the last one if no more follow
+    pub fn bind(&mut self, label: Label) -> &mut Self {
+        match self.labels.get(label.0) {
+            Some(None) => self.labels[label.0] = Some(self.instructions.len()),
+            Some(Some(_)) => self.fail(BuildError::LabelBoundTwice { label: label.0 }),
+            None => self.fail(BuildError::UnknownLabel { label: label.0 }),
+        }
+        self
+    }
+
+    /// Add a variable after the ones added before it
+    pub fn var(&mut self, name: &str, data: &[i8]) -> Var {
+        if self.variables.iter().any(|var| var.name == name) {
+            self.fail(BuildError::DuplicateVariable { name: name.into() });
+        }
+        let data_addr = self
+            .variables
+            .last()
+            .map_or(0, |var| var.data_addr + var.data.len());
+        self.variables.push(SymbolicVariable {
+            name: name.into(),
+            data_addr,
+            data: data
+                .iter()
+                .map(|&byte| Value::constant(byte.into()))
+                .collect(),
+            fixed: false,
+        });
+        Var {
+            index: self.variables.len() - 1,
+            offset: Some(0),
+        }
+    }
+
+    /// Add any instruction, operands that refer to labels have to use the names [`Self::finish`]
+    /// gives them, e.g. `L0` for the first label
+    pub fn push(&mut self, ins: Instruction<Value>) -> &mut Self {
+        self.instructions.push(ins);
+        self
+    }
+
+    pub fn noop(&mut self) -> &mut Self {
+        self.push(Instruction::NoOp)
+    }
+
+    pub fn input_c(&mut self, label: Label) -> &mut Self {
+        let code_addr = self.label_value(label);
+        self.push(Instruction::InputC { code_addr })
+    }
+
+    pub fn input_cf(&mut self, rx: Register, label: Label) -> &mut Self {
+        let code_offset = self.label_value(label);
+        self.push(Instruction::InputCF { rx, code_offset })
+    }
+
+    pub fn input_d(&mut self, var: Var) -> &mut Self {
+        let data_addr = self.var_value(var);
+        self.push(Instruction::InputD { data_addr })
+    }
+
+    pub fn input_df(&mut self, rx: Register, var: Var) -> &mut Self {
+        let data_offset = self.var_value(var);
+        self.push(Instruction::InputDF { rx, data_offset })
+    }
+
+    pub fn mov(&mut self, rx: Register, ry: Register) -> &mut Self {
+        self.push(Instruction::Move { rx, ry })
+    }
+
+    pub fn load_i(&mut self, rx: Register, value: i8) -> &mut Self {
+        self.push(Instruction::LoadI {
+            rx,
+            value: Value::constant(value.into()),
+        })
+    }
+
+    /// Load the address of a variable, e.g. to use it as a pointer
+    pub fn load_addr(&mut self, rx: Register, var: Var) -> &mut Self {
+        let value = self.var_value(var);
+        self.push(Instruction::LoadI { rx, value })
+    }
+
+    pub fn add(&mut self, rx: Register, ry: Register) -> &mut Self {
+        self.push(Instruction::Add { rx, ry })
+    }
+
+    pub fn add_i(&mut self, rx: Register, value: i8) -> &mut Self {
+        self.push(Instruction::AddI {
+            rx,
+            value: Value::constant(value.into()),
+        })
+    }
+
+    pub fn sub(&mut self, rx: Register, ry: Register) -> &mut Self {
+        self.push(Instruction::Sub { rx, ry })
+    }
+
+    pub fn sub_i(&mut self, rx: Register, value: i8) -> &mut Self {
+        self.push(Instruction::SubI {
+            rx,
+            value: Value::constant(value.into()),
+        })
+    }
+
+    pub fn load(&mut self, rx: Register, var: Var) -> &mut Self {
+        let data_addr = self.var_value(var);
+        self.push(Instruction::Load { rx, data_addr })
+    }
+
+    /// Load from `var` plus the value of `ry`, like `LOADF rx, [var+ry]`
+    pub fn load_f(&mut self, rx: Register, var: Var, ry: Register) -> &mut Self {
+        let data_offset = self.var_value(var);
+        self.push(Instruction::LoadF {
+            rx,
+            ry,
+            data_offset,
+        })
+    }
+
+    pub fn store(&mut self, var: Var, rx: Register) -> &mut Self {
+        let data_addr = self.var_value(var);
+        self.push(Instruction::Store { data_addr, rx })
+    }
+
+    /// Store into `var` plus the value of `ry`, like `STOREF [var+ry], rx`
+    pub fn store_f(&mut self, var: Var, ry: Register, rx: Register) -> &mut Self {
+        let data_offset = self.var_value(var);
+        self.push(Instruction::StoreF {
+            ry,
+            data_offset,
+            rx,
+        })
+    }
+
+    pub fn shift(&mut self, dir: ShiftDirection, rx: Register) -> &mut Self {
+        self.push(Instruction::Shift { dir, rx })
+    }
+
+    pub fn cmp(&mut self, rx: Register, ry: Register) -> &mut Self {
+        self.push(Instruction::Cmp { rx, ry })
+    }
+
+    pub fn jump(&mut self, label: Label) -> &mut Self {
+        let pc_offset = self.label_value(label);
+        self.push(Instruction::Jump { pc_offset })
+    }
+
+    pub fn branch(&mut self, cond: BranchCondition, label: Label) -> &mut Self {
+        let pc_offset = self.label_value(label);
+        self.push(Instruction::Branch { cond, pc_offset })
+    }
+
+    /// The program with its labels and variables still symbolic
+    pub fn symbolic(&self) -> SymbolicIr {
+        let labels = self
+            .labels
+            .iter()
+            .enumerate()
+            .filter_map(|(index, code_addr)| {
+                Some(SymbolicLabel {
+                    name: label_name(index),
+                    code_addr: (*code_addr)?,
+                })
+            })
+            .collect();
+        SymbolicIr {
+            variables: self.variables.clone(),
+            labels,
+            instructions: self.instructions.clone(),
+        }
+    }
+
+    /// Check the program and give every label and variable its address
+    pub fn finish(&self) -> Result<Ir, BuildError> {
+        if let Some(error) = &self.error {
+            return Err(error.clone());
+        }
+        if self.instructions.len() > CODE_MEMORY_SIZE {
+            return Err(BuildError::TooManyInstructions {
+                found: self.instructions.len(),
+                max: CODE_MEMORY_SIZE,
+            });
+        }
+        let data = self
+            .variables
+            .last()
+            .map_or(0, |var| var.data_addr + var.data.len());
+        if data > DATA_MEMORY_SIZE {
+            return Err(BuildError::TooMuchUserData {
+                found: data,
+                max: DATA_MEMORY_SIZE,
+            });
+        }
+
+        for ins in self.instructions.iter() {
+            let Some(Symbol::Label(name)) = ins.value().and_then(|v| v.symbol.as_ref()) else {
+                continue;
+            };
+            let Some(label) = (0..self.labels.len()).find(|&i| label_name(i) == *name) else {
+                continue; // pushed by hand, resolving reports it if it does not exist
+            };
+            if self.labels[label].is_none() {
+                return Err(BuildError::UnboundLabel { label });
+            }
+        }
+
+        let ir = self.symbolic().resolve()?;
+        let len = ir.instructions.len() as isize;
+        let within = |addr: i8, size: usize| usize::try_from(addr).is_ok_and(|addr| addr < size);
+        for (index, &ins) in ir.instructions.iter().enumerate() {
+            match ins {
+                Instruction::InputC { code_addr } if !within(code_addr, CODE_MEMORY_SIZE) => {
+                    return Err(BuildError::AddressOutOfBounds { index, ins })
+                }
+                Instruction::InputD { data_addr }
+                | Instruction::Load { data_addr, .. }
+                | Instruction::Store { data_addr, .. }
+                    if !within(data_addr, DATA_MEMORY_SIZE) =>
+                {
+                    return Err(BuildError::AddressOutOfBounds { index, ins })
+                }
+                // jumping right after the last instruction halts the program
+                Instruction::Jump { pc_offset } | Instruction::Branch { pc_offset, .. }
+                    if !(0..=len).contains(&(index as isize + 1 + pc_offset as isize)) =>
+                {
+                    return Err(BuildError::JumpOutOfBounds { index, ins })
+                }
+                _ => {}
+            }
+        }
+        Ok(ir)
+    }
+
+    fn fail(&mut self, error: BuildError) {
+        self.error.get_or_insert(error);
+    }
+
+    fn label_value(&mut self, label: Label) -> Value {
+        if label.0 >= self.labels.len() {
+            self.fail(BuildError::UnknownLabel { label: label.0 });
+        }
+        Value::symbol(Symbol::Label(label_name(label.0)), 0)
+    }
+
+    fn var_value(&mut self, var: Var) -> Value {
+        let Some(name) = self.variables.get(var.index).map(|v| v.name.clone()) else {
+            self.fail(BuildError::UnknownVariable { index: var.index });
+            return Value::constant(0);
+        };
+        match var.offset {
+            Some(offset) => Value::symbol(Symbol::Variable(name), offset),
+            None => {
+                self.fail(BuildError::OffsetOverflow { name });
+                Value::constant(0)
+            }
+        }
+    }
+}
+
+fn label_name(index: usize) -> String {
+    format!("L{index}")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BuildError, IrBuilder};
+    use crate::{BranchCondition, Instruction, Register, ResolveError, Value};
+
+    #[test]
+    fn ir_builder1() {
+        let mut b = IrBuilder::new();
+        let total = b.var("total", &[0]);
+        let arr = b.var("arr", &[1, 2, 3]);
+        let (top, done) = (b.new_label(), b.new_label());
+        b.load_i(Register::B, 2);
+        b.bind(top);
+        b.load_f(Register::A, arr, Register::B)
+            .load(Register::C, total)
+            .add(Register::C, Register::A)
+            .store(total, Register::C)
+            .sub_i(Register::B, 1)
+            .branch(BranchCondition::GreaterEqual, top)
+            .jump(done)
+            .noop();
+        b.bind(done);
+        b.store(arr.offset(2), Register::C);
+
+        let ir = b.finish().unwrap();
+        assert_eq!(ir.data_memory().unwrap(), [0, 1, 2, 3]);
+        assert!(matches!(
+            ir.instructions.as_slice(),
+            [
+                Instruction::LoadI { value: 2, .. },
+                Instruction::LoadF { data_offset: 1, .. },
+                Instruction::Load { data_addr: 0, .. },
+                Instruction::Add { .. },
+                Instruction::Store { data_addr: 0, .. },
+                Instruction::SubI { value: 1, .. },
+                Instruction::Branch { pc_offset: -6, .. },
+                Instruction::Jump { pc_offset: 1 },
+                Instruction::NoOp,
+                Instruction::Store { data_addr: 3, .. },
+            ]
+        ));
+    }
+
+    #[test]
+    fn ir_builder2() {
+        let mut b = IrBuilder::new();
+        let l = b.new_label();
+        b.jump(l);
+        assert!(matches!(
+            b.finish(),
+            Err(BuildError::UnboundLabel { label: 0 })
+        ));
+        b.bind(l);
+        let ir = b.finish().unwrap();
+        assert!(matches!(
+            ir.instructions[..],
+            [Instruction::Jump { pc_offset: 0 }]
+        ));
+        b.noop().bind(l);
+        assert!(matches!(
+            b.finish(),
+            Err(BuildError::LabelBoundTwice { label: 0 })
+        ));
+
+        let mut b = IrBuilder::new();
+        b.var("x", &[0; 10]);
+        b.var("x", &[0; 10]);
+        assert!(matches!(
+            b.finish(),
+            Err(BuildError::DuplicateVariable { .. })
+        ));
+
+        let mut b = IrBuilder::new();
+        b.var("x", &[0; 10]);
+        b.var("y", &[0; 10]);
+        assert!(matches!(
+            b.finish(),
+            Err(BuildError::TooMuchUserData { found: 20, max: 16 })
+        ));
+
+        let mut b = IrBuilder::new();
+        for _ in 0..33 {
+            b.noop();
+        }
+        assert!(matches!(
+            b.finish(),
+            Err(BuildError::TooManyInstructions { found: 33, .. })
+        ));
+
+        let mut b = IrBuilder::new();
+        let x = b.var("x", &[0]);
+        b.load_addr(Register::A, x.offset(300));
+        assert!(matches!(
+            b.finish(),
+            Err(BuildError::Resolve(ResolveError::OutOfRange {
+                resolved: 300,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn ir_builder3() {
+        let mut b = IrBuilder::new();
+        let x = b.var("x", &[0]);
+        b.noop().load(Register::A, x.offset(20));
+        assert!(matches!(
+            b.finish(),
+            Err(BuildError::AddressOutOfBounds { index: 1, .. })
+        ));
+
+        let mut b = IrBuilder::new();
+        b.push(Instruction::InputC {
+            code_addr: Value::constant(32),
+        });
+        assert!(matches!(
+            b.finish(),
+            Err(BuildError::AddressOutOfBounds { index: 0, .. })
+        ));
+
+        let mut b = IrBuilder::new();
+        b.push(Instruction::Jump {
+            pc_offset: Value::constant(-2),
+        });
+        assert!(matches!(
+            b.finish(),
+            Err(BuildError::JumpOutOfBounds { index: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn ir_builder4() {
+        let mut other = IrBuilder::new();
+        other.var("x", &[0]);
+        let y = other.var("y", &[0]);
+        let l = other.new_label();
+
+        let mut b = IrBuilder::new();
+        b.load(Register::A, y);
+        assert!(matches!(
+            b.finish(),
+            Err(BuildError::UnknownVariable { index: 1 })
+        ));
+
+        let mut b = IrBuilder::new();
+        b.bind(l);
+        assert!(matches!(
+            b.finish(),
+            Err(BuildError::UnknownLabel { label: 0 })
+        ));
+
+        let mut b = IrBuilder::new();
+        let x = b.var("x", &[0]);
+        b.load(Register::A, x.offset(i16::MAX).offset(1));
+        assert!(matches!(b.finish(), Err(BuildError::OffsetOverflow { .. })));
+    }
+}
@@ -0,0 +1,785 @@
+//! Everything known about each instruction of the i281 in one table
+//!
+//! The encoder, the decoder, hover docs and `docs/assembly_mapping_v3.7.md` are all derived from
+//! [`ISA`], so a change to an instruction only has to be made here.
+
+use std::fmt::{self, Write};
+
+use crate::{
+    builder::{InstructionBuilder, InstructionFields},
+    BranchCondition, InputMode, Instruction, OpCode, Register, ShiftDirection,
+};
+
+/// An operand of an instruction as it is written in assembly
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OperandKind {
+    /// The register in the `RX` field
+    Rx,
+    /// The register in the `RY` field
+    Ry,
+    /// A constant that fits in a byte
+    Immediate,
+    /// `[DADDRESS]`
+    DataAddress,
+    /// `[DADDRESS + RX]`, the register is added at run time
+    DataAddressRx,
+    /// `[DADDRESS + RY]`, the register is added at run time
+    DataAddressRy,
+    /// `[CADDRESS]`
+    CodeAddress,
+    /// `[CADDRESS + RX]`, the register is added at run time
+    CodeAddressRx,
+    /// A label, the assembler turns it into a `PCOFFSET`
+    Label,
+}
+
+impl OperandKind {
+    pub fn syntax(self) -> &'static str {
+        match self {
+            Self::Rx => "RX",
+            Self::Ry => "RY",
+            Self::Immediate => "IMMEDVAL",
+            Self::DataAddress => "[DADDRESS]",
+            Self::DataAddressRx => "[DADDRESS + RX]",
+            Self::DataAddressRy => "[DADDRESS + RY]",
+            Self::CodeAddress => "[CADDRESS]",
+            Self::CodeAddressRx => "[CADDRESS + RX]",
+            Self::Label => "LABEL",
+        }
+    }
+}
+
+/// What the low byte of an instruction holds
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DataField {
+    Immediate,
+    DataAddress,
+    CodeAddress,
+    /// Signed offset added to the pc after it was incremented
+    PcOffset,
+}
+
+impl DataField {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Immediate => "IMMEDVAL",
+            Self::DataAddress => "DADDRESS",
+            Self::CodeAddress => "CADDRESS",
+            Self::PcOffset => "PCOFFSET",
+        }
+    }
+}
+
+/// What a bit field of an instruction holds
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Field {
+    /// Ignored by the hardware, the assembler writes zeros
+    Unused,
+    Rx,
+    Ry,
+    /// The low `bits` of the field select between instructions sharing an opcode
+    Select {
+        bits: u8,
+        value: u8,
+    },
+    Data(DataField),
+}
+
+impl Field {
+    fn value(self, rx: Register, ry: Register, data: i8) -> u16 {
+        match self {
+            Self::Unused => 0,
+            Self::Rx => u16::from(rx),
+            Self::Ry => u16::from(ry),
+            Self::Select { value, .. } => u16::from(value),
+            Self::Data(_) => u16::from(data as u8), // intentional overflow cast this will use twos complement
+        }
+    }
+
+    fn matches(self, field: u16) -> bool {
+        match self {
+            Self::Select { bits, value } => field & ((1 << bits) - 1) == u16::from(value),
+            _ => true,
+        }
+    }
+
+    fn fmt(self, f: &mut fmt::Formatter<'_>, width: usize) -> fmt::Result {
+        match self {
+            Self::Unused => write!(f, "{}", "d".repeat(width)),
+            Self::Rx => write!(f, "RX"),
+            Self::Ry => write!(f, "RY"),
+            Self::Select { bits, value } => write!(
+                f,
+                "{}{value:0bits$b}",
+                "d".repeat(width - bits as usize),
+                bits = bits as usize
+            ),
+            Self::Data(data) => write!(f, "{}", data.name()),
+        }
+    }
+}
+
+/// The bit fields of an instruction, `opcode(4)|B(2)|C(2)|D(8)`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Layout {
+    pub opcode: OpCode,
+    pub b: Field,
+    pub c: Field,
+    pub d: Field,
+}
+
+impl Layout {
+    pub(crate) fn encode(&self, rx: Register, ry: Register, data: i8) -> u16 {
+        InstructionBuilder::new()
+            .set_opcode(self.opcode)
+            .set_b(self.b.value(rx, ry, data))
+            .set_c(self.c.value(rx, ry, data))
+            .set_d(self.d.value(rx, ry, data))
+            .finish()
+    }
+
+    /// If the opcode and the select bits of `word` are the ones of this layout
+    pub fn matches(&self, word: u16) -> bool {
+        let fields = InstructionFields::new(word);
+        fields.opcode() == self.opcode
+            && self.b.matches(fields.b())
+            && self.c.matches(fields.c())
+            && self.d.matches(fields.d())
+    }
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04b}_", u16::from(self.opcode))?;
+        self.b.fmt(f, 2)?;
+        write!(f, "_")?;
+        self.c.fmt(f, 2)?;
+        write!(f, "_")?;
+        self.d.fmt(f, 8)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Flag {
+    Carry,
+    Negative,
+    Overflow,
+    Zero,
+}
+
+impl Flag {
+    pub fn letter(self) -> char {
+        match self {
+            Self::Carry => 'C',
+            Self::Negative => 'N',
+            Self::Overflow => 'V',
+            Self::Zero => 'Z',
+        }
+    }
+}
+
+/// Another mnemonic the assembler accepts for the same machine code
+#[derive(Clone, Copy, Debug)]
+pub struct Alias {
+    pub mnemonic: &'static str,
+    pub name: &'static str,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct InstructionInfo {
+    pub mnemonic: &'static str,
+    pub aliases: &'static [Alias],
+    /// The full name, the letters the mnemonic is made of are in bold
+    pub name: &'static str,
+    pub operands: &'static [OperandKind],
+    pub layout: Layout,
+    pub semantics: &'static str,
+    pub flags_read: &'static [Flag],
+    pub flags_written: &'static [Flag],
+    /// Build the instruction from the values of its fields
+    decode: fn(Register, Register, i8) -> Instruction,
+}
+
+impl InstructionInfo {
+    /// Find an instruction by its mnemonic or one of its aliases, ignoring case
+    pub fn lookup(mnemonic: &str) -> Option<&'static Self> {
+        ISA.iter().find(|info| {
+            std::iter::once(info.mnemonic)
+                .chain(info.aliases.iter().map(|alias| alias.mnemonic))
+                .any(|name| name.eq_ignore_ascii_case(mnemonic))
+        })
+    }
+
+    /// The instruction a machine code word is, every word is one
+    pub fn of_word(word: u16) -> &'static Self {
+        ISA.iter()
+            .find(|info| info.layout.matches(word))
+            .expect("every word matches an instruction of the table")
+    }
+
+    pub(crate) fn decode(&self, word: u16) -> Instruction {
+        let fields = InstructionFields::new(word);
+        (self.decode)(fields.rx(), fields.ry(), fields.data())
+    }
+
+    /// How the instruction is written, e.g. `LOADF RX, [DADDRESS + RY]`
+    pub fn syntax(&self) -> String {
+        let operands: Vec<_> = self.operands.iter().map(|op| op.syntax()).collect();
+        match operands.is_empty() {
+            true => self.mnemonic.to_string(),
+            false => format!("{} {}", self.mnemonic, operands.join(", ")),
+        }
+    }
+
+    /// Markdown shown when hovering over the mnemonic in an editor
+    pub fn hover(&self) -> String {
+        let mut hover = format!(
+            "```asm\n{}\n```\n{}\n\n`{}`\n\n{}\n",
+            self.syntax(),
+            self.name,
+            self.layout,
+            self.semantics
+        );
+        if !self.aliases.is_empty() {
+            let aliases: Vec<_> = self
+                .aliases
+                .iter()
+                .map(|a| format!("`{}`", a.mnemonic))
+                .collect();
+            write!(hover, "\nAlso written as {}\n", aliases.join(", ")).unwrap();
+        }
+        if !self.flags_read.is_empty() {
+            write!(hover, "\nReads flags: {}\n", flags(self.flags_read)).unwrap();
+        }
+        if !self.flags_written.is_empty() {
+            write!(hover, "\nWrites flags: {}\n", flags(self.flags_written)).unwrap();
+        }
+        hover
+    }
+}
+
+fn flags(flags: &[Flag]) -> String {
+    flags.iter().map(|flag| flag.letter()).collect()
+}
+
+/// The markdown table of every mnemonic, aliases get a row of their own
+pub fn reference() -> String {
+    let header = [
+        "OpCode",
+        "Naming Scheme",
+        "Operands",
+        "Machine Code",
+        "Meaning",
+        "Flags Read",
+        "Flags Written",
+    ];
+    let mut rows = Vec::new();
+    for info in ISA {
+        let operands: Vec<_> = info.operands.iter().map(|op| op.syntax()).collect();
+        let row = |mnemonic: &str, name: &str, semantics: String| {
+            [
+                mnemonic.to_string(),
+                name.to_string(),
+                operands.join(", "),
+                info.layout.to_string(),
+                semantics,
+                flags(info.flags_read),
+                flags(info.flags_written),
+            ]
+        };
+        rows.push(row(info.mnemonic, info.name, info.semantics.to_string()));
+        for alias in info.aliases {
+            let semantics = format!("{} *identical to {}*", info.semantics, info.mnemonic);
+            rows.push(row(alias.mnemonic, alias.name, semantics));
+        }
+    }
+
+    let widths: Vec<_> = (0..header.len())
+        .map(|i| {
+            rows.iter()
+                .map(|row| row[i].len())
+                .chain([header[i].len()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let mut table = String::new();
+    let mut line = |cells: &mut dyn Iterator<Item = String>| {
+        let cells: Vec<_> = cells
+            .zip(&widths)
+            .map(|(cell, &width)| format!("{cell:width$}"))
+            .collect();
+        writeln!(table, "| {} |", cells.join(" | ")).unwrap();
+    };
+    line(&mut header.iter().map(|h| h.to_string()));
+    line(&mut widths.iter().map(|&width| "-".repeat(width)));
+    for row in rows {
+        line(&mut row.into_iter());
+    }
+    table
+}
+
+const fn layout(opcode: OpCode, b: Field, c: Field, d: Field) -> Layout {
+    Layout { opcode, b, c, d }
+}
+
+const fn select(bits: u8, value: u8) -> Field {
+    Field::Select { bits, value }
+}
+
+use DataField::{CodeAddress, DataAddress, Immediate, PcOffset};
+use Field::{Data, Rx, Ry, Unused};
+use Flag::{Carry, Negative, Overflow, Zero};
+
+const ALU_FLAGS: &[Flag] = &[Carry, Negative, Overflow, Zero];
+
+/// Every instruction in the order of its machine code
+pub static ISA: &[InstructionInfo] = &[
+    InstructionInfo {
+        mnemonic: "NOOP",
+        aliases: &[],
+        name: "**NO OP**eration",
+        operands: &[],
+        layout: layout(OpCode::NoOp, Unused, Unused, Unused),
+        semantics: "Do nothing",
+        flags_read: &[],
+        flags_written: &[],
+        decode: |_, _, _| Instruction::NoOp,
+    },
+    InstructionInfo {
+        mnemonic: "INPUTC",
+        aliases: &[],
+        name: "**INPUT** into **C**ode memory",
+        operands: &[OperandKind::CodeAddress],
+        layout: layout(
+            OpCode::Input,
+            Unused,
+            select(2, InputMode::C as u8),
+            Data(CodeAddress),
+        ),
+        semantics: "CODE at [CADDRESS] = INPUTVAL from switches SW15-SW0",
+        flags_read: &[],
+        flags_written: &[],
+        decode: |_, _, data| Instruction::InputC { code_addr: data },
+    },
+    InstructionInfo {
+        mnemonic: "INPUTCF",
+        aliases: &[],
+        name: "**INPUT** into **C**ode memory with o**F**fset",
+        operands: &[OperandKind::CodeAddressRx],
+        layout: layout(
+            OpCode::Input,
+            Rx,
+            select(2, InputMode::CF as u8),
+            Data(CodeAddress),
+        ),
+        semantics: "CODE at [CADDRESS + RX] = INPUTVAL from switches SW15-SW0",
+        flags_read: &[],
+        flags_written: &[],
+        decode: |rx, _, data| Instruction::InputCF {
+            rx,
+            code_offset: data,
+        },
+    },
+    InstructionInfo {
+        mnemonic: "INPUTD",
+        aliases: &[],
+        name: "**INPUT** into **D**ata memory",
+        operands: &[OperandKind::DataAddress],
+        layout: layout(
+            OpCode::Input,
+            Unused,
+            select(2, InputMode::D as u8),
+            Data(DataAddress),
+        ),
+        semantics: "DATA at [DADDRESS] = INPUTVAL from switches SW7-SW0",
+        flags_read: &[],
+        flags_written: &[],
+        decode: |_, _, data| Instruction::InputD { data_addr: data },
+    },
+    InstructionInfo {
+        mnemonic: "INPUTDF",
+        aliases: &[],
+        name: "**INPUT** into **D**ata memory with o**F**fset",
+        operands: &[OperandKind::DataAddressRx],
+        layout: layout(
+            OpCode::Input,
+            Rx,
+            select(2, InputMode::DF as u8),
+            Data(DataAddress),
+        ),
+        semantics: "DATA at [DADDRESS + RX] = INPUTVAL from switches SW7-SW0",
+        flags_read: &[],
+        flags_written: &[],
+        decode: |rx, _, data| Instruction::InputDF {
+            rx,
+            data_offset: data,
+        },
+    },
+    InstructionInfo {
+        mnemonic: "MOVE",
+        aliases: &[],
+        name: "**MOVE** (i.e. copy) the contents of one register into another",
+        operands: &[OperandKind::Rx, OperandKind::Ry],
+        layout: layout(OpCode::Move, Rx, Ry, Unused),
+        semantics: "RX = RY",
+        flags_read: &[],
+        flags_written: &[],
+        decode: |rx, ry, _| Instruction::Move { rx, ry },
+    },
+    InstructionInfo {
+        mnemonic: "LOADI",
+        // `LOADP` takes the address of a variable instead, the compiler turns it into an immediate
+        aliases: &[Alias {
+            mnemonic: "LOADP",
+            name: "**LOAD** **P**ointer address into register",
+        }],
+        name: "**LOAD** **I**mmediate value into register",
+        operands: &[OperandKind::Rx, OperandKind::Immediate],
+        layout: layout(OpCode::LoadI, Rx, Unused, Data(Immediate)),
+        semantics: "RX = IMMEDVAL",
+        flags_read: &[],
+        flags_written: &[],
+        decode: |rx, _, data| Instruction::LoadI { rx, value: data },
+    },
+    InstructionInfo {
+        mnemonic: "ADD",
+        aliases: &[],
+        name: "**ADD** two registers",
+        operands: &[OperandKind::Rx, OperandKind::Ry],
+        layout: layout(OpCode::Add, Rx, Ry, Unused),
+        semantics: "RX = RX + RY",
+        flags_read: &[],
+        flags_written: ALU_FLAGS,
+        decode: |rx, ry, _| Instruction::Add { rx, ry },
+    },
+    InstructionInfo {
+        mnemonic: "ADDI",
+        aliases: &[],
+        name: "**ADD** an **I**mmediate value to a register value",
+        operands: &[OperandKind::Rx, OperandKind::Immediate],
+        layout: layout(OpCode::AddI, Rx, Unused, Data(Immediate)),
+        semantics: "RX = RX + IMMEDVAL",
+        flags_read: &[],
+        flags_written: ALU_FLAGS,
+        decode: |rx, _, data| Instruction::AddI { rx, value: data },
+    },
+    InstructionInfo {
+        mnemonic: "SUB",
+        aliases: &[],
+        name: "**SUB**tract two registers",
+        operands: &[OperandKind::Rx, OperandKind::Ry],
+        layout: layout(OpCode::Sub, Rx, Ry, Unused),
+        semantics: "RX = RX - RY",
+        flags_read: &[],
+        flags_written: ALU_FLAGS,
+        decode: |rx, ry, _| Instruction::Sub { rx, ry },
+    },
+    InstructionInfo {
+        mnemonic: "SUBI",
+        aliases: &[],
+        name: "**SUB**tract an **I**mmediate value from a register value",
+        operands: &[OperandKind::Rx, OperandKind::Immediate],
+        layout: layout(OpCode::SubI, Rx, Unused, Data(Immediate)),
+        semantics: "RX = RX - IMMEDVAL",
+        flags_read: &[],
+        flags_written: ALU_FLAGS,
+        decode: |rx, _, data| Instruction::SubI { rx, value: data },
+    },
+    InstructionInfo {
+        mnemonic: "LOAD",
+        aliases: &[],
+        name: "**LOAD** from a data memory address into a register",
+        operands: &[OperandKind::Rx, OperandKind::DataAddress],
+        layout: layout(OpCode::Load, Rx, Unused, Data(DataAddress)),
+        semantics: "RX = [DADDRESS]",
+        flags_read: &[],
+        flags_written: &[],
+        decode: |rx, _, data| Instruction::Load {
+            rx,
+            data_addr: data,
+        },
+    },
+    InstructionInfo {
+        mnemonic: "LOADF",
+        aliases: &[],
+        name: "**LOAD** with an o**F**fset specified by another register",
+        operands: &[OperandKind::Rx, OperandKind::DataAddressRy],
+        layout: layout(OpCode::LoadF, Rx, Ry, Data(DataAddress)),
+        semantics: "RX = [DADDRESS + RY]",
+        flags_read: &[],
+        flags_written: &[],
+        decode: |rx, ry, data| Instruction::LoadF {
+            rx,
+            ry,
+            data_offset: data,
+        },
+    },
+    InstructionInfo {
+        mnemonic: "STORE",
+        aliases: &[],
+        name: "**STORE** a register into a data memory address",
+        operands: &[OperandKind::DataAddress, OperandKind::Rx],
+        layout: layout(OpCode::Store, Rx, Unused, Data(DataAddress)),
+        semantics: "[DADDRESS] = RX",
+        flags_read: &[],
+        flags_written: &[],
+        decode: |rx, _, data| Instruction::Store {
+            data_addr: data,
+            rx,
+        },
+    },
+    InstructionInfo {
+        mnemonic: "STOREF",
+        aliases: &[],
+        name: "**STORE** with an o**F**fset specified by another register",
+        operands: &[OperandKind::DataAddressRy, OperandKind::Rx],
+        layout: layout(OpCode::StoreF, Rx, Ry, Data(DataAddress)),
+        semantics: "[DADDRESS + RY] = RX",
+        flags_read: &[],
+        flags_written: &[],
+        decode: |rx, ry, data| Instruction::StoreF {
+            ry,
+            data_offset: data,
+            rx,
+        },
+    },
+    InstructionInfo {
+        mnemonic: "SHIFTL",
+        aliases: &[],
+        name: "**SHIFT** all bits in a register **L**eft",
+        operands: &[OperandKind::Rx],
+        layout: layout(
+            OpCode::Shift,
+            Rx,
+            select(1, ShiftDirection::Left as u8),
+            Unused,
+        ),
+        semantics: "RX = RX << 1, the bit shifted out goes to the overflow flag",
+        flags_read: &[],
+        flags_written: &[Negative, Overflow, Zero],
+        decode: |rx, _, _| Instruction::Shift {
+            dir: ShiftDirection::Left,
+            rx,
+        },
+    },
+    InstructionInfo {
+        mnemonic: "SHIFTR",
+        aliases: &[],
+        name: "**SHIFT** all bits in a register **R**ight",
+        operands: &[OperandKind::Rx],
+        layout: layout(
+            OpCode::Shift,
+            Rx,
+            select(1, ShiftDirection::Right as u8),
+            Unused,
+        ),
+        semantics:
+            "RX = RX >> 1 keeping the sign bit, the bit shifted out goes to the overflow flag",
+        flags_read: &[],
+        flags_written: &[Negative, Overflow, Zero],
+        decode: |rx, _, _| Instruction::Shift {
+            dir: ShiftDirection::Right,
+            rx,
+        },
+    },
+    InstructionInfo {
+        mnemonic: "CMP",
+        aliases: &[],
+        name: "**C**o**MP**are the values in two registers",
+        operands: &[OperandKind::Rx, OperandKind::Ry],
+        layout: layout(OpCode::Cmp, Rx, Ry, Unused),
+        semantics: "Set the flags from RX - RY without storing the result",
+        flags_read: &[],
+        flags_written: ALU_FLAGS,
+        decode: |rx, ry, _| Instruction::Cmp { rx, ry },
+    },
+    InstructionInfo {
+        mnemonic: "JUMP",
+        aliases: &[],
+        name: "**JUMP** unconditionally to a specified address",
+        operands: &[OperandKind::Label],
+        layout: layout(OpCode::Jump, Unused, Unused, Data(PcOffset)),
+        semantics: "PC = PC + 1 + PCOFFSET *PCOFFSET is signed 2's complement*",
+        flags_read: &[],
+        flags_written: &[],
+        decode: |_, _, data| Instruction::Jump { pc_offset: data },
+    },
+    InstructionInfo {
+        mnemonic: "BRE",
+        aliases: &[Alias {
+            mnemonic: "BRZ",
+            name: "**BR**anch if **Z**ero",
+        }],
+        name: "**BR**anch if **E**qual",
+        operands: &[OperandKind::Label],
+        layout: layout(
+            OpCode::Branch,
+            Unused,
+            select(2, BranchCondition::Equal as u8),
+            Data(PcOffset),
+        ),
+        semantics: "PC = PC + 1 + PCOFFSET if condition *Equal* is true",
+        flags_read: &[Zero],
+        flags_written: &[],
+        decode: |_, _, data| Instruction::Branch {
+            cond: BranchCondition::Equal,
+            pc_offset: data,
+        },
+    },
+    InstructionInfo {
+        mnemonic: "BRNE",
+        aliases: &[Alias {
+            mnemonic: "BRNZ",
+            name: "**BR**anch if **N**ot **Z**ero",
+        }],
+        name: "**BR**anch if **N**ot **E**qual",
+        operands: &[OperandKind::Label],
+        layout: layout(
+            OpCode::Branch,
+            Unused,
+            select(2, BranchCondition::NotEqual as u8),
+            Data(PcOffset),
+        ),
+        semantics: "PC = PC + 1 + PCOFFSET if condition *Not Equal* is true",
+        flags_read: &[Zero],
+        flags_written: &[],
+        decode: |_, _, data| Instruction::Branch {
+            cond: BranchCondition::NotEqual,
+            pc_offset: data,
+        },
+    },
+    InstructionInfo {
+        mnemonic: "BRG",
+        aliases: &[],
+        name: "**BR**anch if **G**reater",
+        operands: &[OperandKind::Label],
+        layout: layout(
+            OpCode::Branch,
+            Unused,
+            select(2, BranchCondition::Greater as u8),
+            Data(PcOffset),
+        ),
+        semantics: "PC = PC + 1 + PCOFFSET if condition *Greater than* is true",
+        flags_read: &[Negative, Overflow, Zero],
+        flags_written: &[],
+        decode: |_, _, data| Instruction::Branch {
+            cond: BranchCondition::Greater,
+            pc_offset: data,
+        },
+    },
+    InstructionInfo {
+        mnemonic: "BRGE",
+        aliases: &[],
+        name: "**BR**anch if **G**reater than or **E**qual",
+        operands: &[OperandKind::Label],
+        layout: layout(
+            OpCode::Branch,
+            Unused,
+            select(2, BranchCondition::GreaterEqual as u8),
+            Data(PcOffset),
+        ),
+        semantics: "PC = PC + 1 + PCOFFSET if condition *Greater than or equal* is true",
+        flags_read: &[Negative, Overflow],
+        flags_written: &[],
+        decode: |_, _, data| Instruction::Branch {
+            cond: BranchCondition::GreaterEqual,
+            pc_offset: data,
+        },
+    },
+];
+
+#[cfg(test)]
+mod test {
+    use super::{Field, InstructionInfo, ISA};
+    use crate::{Instruction, Register};
+
+    #[test]
+    fn isa1() {
+        // building an instruction puts every operand where the table says and nothing else
+        for info in ISA {
+            for (rx, ry, data) in [
+                (Register::A, Register::A, 0),
+                (Register::B, Register::C, -3),
+                (Register::D, Register::B, 0x55),
+            ] {
+                let ins = info.decode(info.layout.encode(rx, ry, data));
+                assert_eq!(ins.mnemonic(), info.mnemonic);
+                let word = ins.build();
+
+                let field = |field: Field, value: u16, expected: u16| match field {
+                    Field::Unused => assert_eq!(value, 0, "{} {word:016b}", info.mnemonic),
+                    Field::Select { value: select, .. } => {
+                        assert_eq!(value, u16::from(select), "{}", info.mnemonic)
+                    }
+                    _ => assert_eq!(value, expected, "{} {word:016b}", info.mnemonic),
+                };
+                assert_eq!(
+                    word >> 12,
+                    u16::from(info.layout.opcode),
+                    "{}",
+                    info.mnemonic
+                );
+                field(info.layout.b, (word >> 10) & 0b11, u16::from(rx));
+                field(info.layout.c, (word >> 8) & 0b11, u16::from(ry));
+                field(info.layout.d, word & 0xff, u16::from(data as u8));
+
+                assert!(info.layout.matches(word));
+                assert_eq!(InstructionInfo::of_word(word).mnemonic, info.mnemonic);
+                assert_eq!(Instruction::decode(word).build(), word);
+            }
+        }
+    }
+
+    #[test]
+    fn isa2() {
+        // the table and the parser know the same mnemonics
+        let names: Vec<_> = ISA
+            .iter()
+            .flat_map(|info| {
+                std::iter::once(info.mnemonic).chain(info.aliases.iter().map(|a| a.mnemonic))
+            })
+            .collect();
+        assert_eq!(names.len(), i281_ast::OpCode::ALL.len());
+        for name in i281_ast::OpCode::ALL {
+            assert!(InstructionInfo::lookup(name).is_some(), "{name} is missing");
+        }
+        assert_eq!(InstructionInfo::lookup("brz").unwrap().mnemonic, "BRE");
+
+        // every word decodes to exactly one instruction
+        for word in 0..=u16::MAX {
+            let matching = ISA.iter().filter(|info| info.layout.matches(word)).count();
+            assert_eq!(matching, 1, "{word:016b}");
+        }
+
+        let hover = InstructionInfo::lookup("LOADF").unwrap().hover();
+        assert!(hover.starts_with("```asm\nLOADF RX, [DADDRESS + RY]\n```\n"));
+        assert!(hover.contains("`1001_RX_RY_DADDRESS`"));
+        let hover = InstructionInfo::lookup("SHIFTR").unwrap().hover();
+        assert!(hover.contains("`1100_RX_d1_dddddddd`"));
+        assert!(hover.contains("Writes flags: NVZ"));
+    }
+
+    #[test]
+    fn isa3() {
+        // the table in the docs is generated, set `UPDATE_DOCS=1` to regenerate it
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../docs/assembly_mapping_v3.7.md"
+        );
+        let (start, end) = ("<!-- isa table start -->\n", "<!-- isa table end -->");
+        let docs = std::fs::read_to_string(path).unwrap();
+        let from = docs.find(start).expect("the start marker is in the docs") + start.len();
+        let to = docs.find(end).expect("the end marker is in the docs");
+        let table = super::reference();
+        if std::env::var_os("UPDATE_DOCS").is_some() {
+            std::fs::write(path, format!("{}{table}{}", &docs[..from], &docs[to..])).unwrap();
+        } else {
+            assert_eq!(
+                docs[from..to],
+                table,
+                "run `UPDATE_DOCS=1 cargo test -p i281_ir`"
+            );
+        }
+    }
+}
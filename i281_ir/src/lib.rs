@@ -1,26 +1,61 @@
 #![forbid(unsafe_code)]
 #![warn(missing_debug_implementations)]
+#![allow(clippy::unusual_byte_groupings)] // binary literals are grouped by instruction field
 
 mod branch_condition;
 mod builder;
 mod input_mode;
 mod instruction;
+mod ir_builder;
+pub mod isa;
+mod object;
 mod opcode;
 mod register;
 mod shift_direction;
+mod symbolic;
 mod variable;
 
 pub use branch_condition::BranchCondition;
 pub use input_mode::InputMode;
 pub use instruction::Instruction;
+pub use ir_builder::{BuildError, IrBuilder, Label, Var};
+pub use object::{link, LinkError, Object, Relocation, Site};
 pub use opcode::OpCode;
 pub use register::Register;
 pub use shift_direction::ShiftDirection;
+pub use symbolic::{ResolveError, Symbol, SymbolicIr, SymbolicLabel, SymbolicVariable, Value};
 pub use variable::Variable;
 
+/// The number of instructions that fit in code memory
+pub const CODE_MEMORY_SIZE: usize = 32;
+/// The number of bytes that fit in data memory
+pub const DATA_MEMORY_SIZE: usize = 16;
+
 #[derive(Clone, Debug)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 pub struct Ir {
     pub variables: Vec<Variable>,
     pub instructions: Vec<Instruction>,
 }
+
+impl Ir {
+    /// Where the last variable ends in data memory, `None` if the end does not fit in a `usize`
+    pub fn data_len(&self) -> Option<usize> {
+        self.variables.iter().try_fold(0, |len, var| {
+            Some(len.max(var.data_addr.checked_add(var.data.len())?))
+        })
+    }
+
+    /// The initial contents of data memory up to the end of the last variable
+    ///
+    /// Gaps between variables are filled with zeros, where variables overlap the one declared last
+    /// is used. Returns `None` if a variable does not fit in [`DATA_MEMORY_SIZE`] bytes.
+    pub fn data_memory(&self) -> Option<Vec<i8>> {
+        let len = self.data_len().filter(|len| *len <= DATA_MEMORY_SIZE)?;
+        let mut memory = vec![0; len];
+        for var in self.variables.iter() {
+            memory[var.data_addr..var.data_addr + var.data.len()].copy_from_slice(&var.data);
+        }
+        Some(memory)
+    }
+}
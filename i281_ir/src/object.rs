@@ -0,0 +1,479 @@
+use std::{collections::HashMap, ops::Range};
+
+use crate::{Symbol, SymbolicIr, SymbolicLabel, SymbolicVariable, Value};
+
+/// Local labels contain a `.` and numeric labels a `#`, neither can be used by another module
+fn is_private(name: &str) -> bool {
+    name.contains(['.', '#'])
+}
+
+/// Where a value that refers to a symbol is stored
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Site {
+    /// The data field of an instruction
+    Code { index: usize },
+    /// A byte of a variable, `variable` is its position in the variables of the ir
+    Data { variable: usize, index: usize },
+}
+
+impl std::fmt::Display for Site {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Code { index } => write!(f, "instruction {index}"),
+            Self::Data { variable, index } => write!(f, "byte {index} of variable {variable}"),
+        }
+    }
+}
+
+/// A value whose symbol has to be bound when the object is linked
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct Relocation {
+    pub site: Site,
+    pub symbol: Symbol,
+}
+
+/// A separately assembled module that still has to be linked with the others
+///
+/// Every variable and global label is exported, local and numeric labels are private to the
+/// module. A name the module uses without defining it is imported, it is bound by name to the
+/// module that exports it so the kind of symbol the module guessed for it does not matter.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct Object {
+    /// Private labels are qualified with this when linking, usually the name of the source file
+    pub name: String,
+    /// The code and data of the module placed as if it was the only one
+    pub ir: SymbolicIr,
+    pub exports: Vec<Symbol>,
+    pub imports: Vec<String>,
+    pub relocations: Vec<Relocation>,
+}
+
+#[derive(Clone, Debug, miette::Diagnostic, thiserror::Error)]
+pub enum LinkError {
+    #[error("`{module}` is linked more than once")]
+    #[diagnostic(code(link::duplicate_module))]
+    DuplicateModule { module: String },
+    #[error("`{name}` is defined by both `{first}` and `{second}`")]
+    #[diagnostic(
+        code(link::duplicate_symbol),
+        help("every variable and global label is exported, rename one of them or make it local")
+    )]
+    DuplicateSymbol {
+        name: String,
+        first: String,
+        second: String,
+    },
+    #[error("`{name}` is used by `{module}` but none of the modules define it")]
+    #[diagnostic(
+        code(link::missing_symbol),
+        help("link the module that defines `{name}` as well")
+    )]
+    MissingSymbol { name: String, module: String },
+    #[error("`{module}` relocates `{symbol}` at {site} but the value there is different")]
+    #[diagnostic(
+        code(link::bad_relocation),
+        help("the object file was changed after it was assembled, assemble it again")
+    )]
+    BadRelocation {
+        module: String,
+        symbol: Symbol,
+        site: Site,
+    },
+    #[error("`{name}` of `{module}` and `{other}` of `{other_module}` are both placed at data address `{data_addr}`")]
+    #[diagnostic(
+        code(link::overlapping_variables),
+        help("move one of the variables with `.org` or `@`, or remove its placement so the linker places it")
+    )]
+    OverlappingVariables {
+        name: String,
+        module: String,
+        other: String,
+        other_module: String,
+        data_addr: usize,
+    },
+}
+
+impl SymbolicIr {
+    fn value_mut(&mut self, site: Site) -> Option<&mut Value> {
+        match site {
+            Site::Code { index } => self.instructions.get_mut(index)?.value_mut(),
+            Site::Data { variable, index } => self.variables.get_mut(variable)?.data.get_mut(index),
+        }
+    }
+}
+
+impl Object {
+    /// Find the exports, imports and relocations of an ir
+    pub fn new(name: String, ir: SymbolicIr) -> Self {
+        let mut exports = Vec::new();
+        for var in ir.variables.iter() {
+            let symbol = Symbol::Variable(var.name.clone());
+            if !exports.contains(&symbol) {
+                exports.push(symbol);
+            }
+        }
+        for label in ir.labels.iter().filter(|label| !is_private(&label.name)) {
+            let symbol = Symbol::Label(label.name.clone());
+            if !exports.contains(&symbol) {
+                exports.push(symbol);
+            }
+        }
+
+        let code = ir
+            .instructions
+            .iter()
+            .enumerate()
+            .filter_map(|(index, ins)| Some((Site::Code { index }, ins.value()?)));
+        let data = ir.variables.iter().enumerate().flat_map(|(variable, var)| {
+            var.data
+                .iter()
+                .enumerate()
+                .map(move |(index, value)| (Site::Data { variable, index }, value))
+        });
+        let relocations: Vec<_> = code
+            .chain(data)
+            .filter_map(|(site, value)| {
+                Some(Relocation {
+                    site,
+                    symbol: value.symbol.clone()?,
+                })
+            })
+            .collect();
+
+        let mut imports = Vec::new();
+        for relocation in relocations.iter() {
+            let name = relocation.symbol.name();
+            if ir.address(&relocation.symbol).is_none() && !imports.iter().any(|i| i == name) {
+                imports.push(name.to_owned());
+            }
+        }
+
+        Self {
+            name,
+            ir,
+            exports,
+            imports,
+            relocations,
+        }
+    }
+
+    /// The name a symbol of this module has once it is linked
+    fn linked_name(&self, name: &str) -> String {
+        match is_private(name) {
+            true => format!("{}:{name}", self.name),
+            false => name.to_owned(),
+        }
+    }
+}
+
+/// Place the objects one after another and bind every import to the module that exports it
+///
+/// The code of the first object is placed first so that is where the program starts. Variables
+/// placed with `.org` or `@` keep their address, the others are placed one after another in the
+/// order of the modules and skip over the fixed ones. The result still has to be resolved, which
+/// is where a program that does not fit in memory is found.
+pub fn link(objects: &[Object]) -> Result<SymbolicIr, Vec<LinkError>> {
+    let mut errors = Vec::new();
+
+    // the module and symbol of every exported name
+    let mut exports: HashMap<&str, (&str, &Symbol)> = HashMap::new();
+    for (i, object) in objects.iter().enumerate() {
+        if objects[..i].iter().any(|other| other.name == object.name) {
+            errors.push(LinkError::DuplicateModule {
+                module: object.name.clone(),
+            });
+            continue;
+        }
+        for symbol in object.exports.iter() {
+            match exports.get(symbol.name()) {
+                // a variable and a label of the same module can share a name, the variable wins
+                Some((module, _)) if *module == object.name => {}
+                Some((module, _)) => errors.push(LinkError::DuplicateSymbol {
+                    name: symbol.name().to_owned(),
+                    first: module.to_string(),
+                    second: object.name.clone(),
+                }),
+                None => {
+                    exports.insert(symbol.name(), (&object.name, symbol));
+                }
+            }
+        }
+    }
+    for object in objects.iter() {
+        for name in object.imports.iter() {
+            if !exports.contains_key(name.as_str()) {
+                errors.push(LinkError::MissingSymbol {
+                    name: name.clone(),
+                    module: object.name.clone(),
+                });
+            }
+        }
+    }
+
+    // the address range, module and name of every fixed variable
+    let mut fixed: Vec<(Range<usize>, &str, &str)> = Vec::new();
+    for object in objects.iter() {
+        for var in object.ir.variables.iter().filter(|var| var.fixed) {
+            let range = var.data_addr..var.data_addr + var.data.len();
+            let overlapping = fixed.iter().find(|(other, module, _)| {
+                *module != object.name && other.start < range.end && range.start < other.end
+            });
+            if let Some((other, module, name)) = overlapping {
+                errors.push(LinkError::OverlappingVariables {
+                    name: var.name.clone(),
+                    module: object.name.clone(),
+                    other: name.to_string(),
+                    other_module: module.to_string(),
+                    data_addr: range.start.max(other.start),
+                });
+            }
+            fixed.push((range, &object.name, &var.name));
+        }
+    }
+
+    let mut data_addr = 0;
+    let mut linked = SymbolicIr {
+        variables: Vec::new(),
+        labels: Vec::new(),
+        instructions: Vec::new(),
+    };
+    for object in objects.iter() {
+        let code_base = linked.instructions.len();
+
+        let mut ir = object.ir.clone();
+        for relocation in object.relocations.iter() {
+            let Some(value) = ir
+                .value_mut(relocation.site)
+                .filter(|value| value.symbol.as_ref() == Some(&relocation.symbol))
+            else {
+                errors.push(LinkError::BadRelocation {
+                    module: object.name.clone(),
+                    symbol: relocation.symbol.clone(),
+                    site: relocation.site,
+                });
+                continue;
+            };
+            let symbol = &relocation.symbol;
+            value.symbol = match object.ir.address(symbol) {
+                Some(_) => Some(match symbol {
+                    Symbol::Label(name) => Symbol::Label(object.linked_name(name)),
+                    Symbol::Variable(name) => Symbol::Variable(object.linked_name(name)),
+                }),
+                // missing symbols were already reported
+                None => exports
+                    .get(symbol.name())
+                    .map(|(_, export)| (*export).clone()),
+            };
+        }
+
+        linked
+            .labels
+            .extend(ir.labels.into_iter().map(|label| SymbolicLabel {
+                name: object.linked_name(&label.name),
+                code_addr: label.code_addr + code_base,
+            }));
+        for var in ir.variables {
+            if var.fixed {
+                linked.variables.push(var);
+                continue;
+            }
+            let size = var.data.len();
+            while let Some((range, ..)) = fixed
+                .iter()
+                .find(|(range, ..)| range.start < data_addr + size && data_addr < range.end)
+            {
+                data_addr = range.end;
+            }
+            linked.variables.push(SymbolicVariable { data_addr, ..var });
+            data_addr += size;
+        }
+        linked.instructions.extend(ir.instructions);
+    }
+
+    match errors.is_empty() {
+        true => Ok(linked),
+        false => Err(errors),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{link, LinkError, Object, Relocation, Site};
+    use crate::{
+        Instruction, Register, Symbol, SymbolicIr, SymbolicLabel, SymbolicVariable, Value,
+    };
+
+    fn label(name: &str) -> Value {
+        Value::symbol(Symbol::Label(name.into()), 0)
+    }
+
+    fn var(name: &str, offset: i16) -> Value {
+        Value::symbol(Symbol::Variable(name.into()), offset)
+    }
+
+    /// Reads two bytes with a subroutine from the other module
+    fn main() -> Object {
+        let ir = SymbolicIr {
+            variables: vec![SymbolicVariable {
+                name: "input".into(),
+                data_addr: 0,
+                data: vec![Value::constant(0), Value::constant(0)],
+                fixed: false,
+            }],
+            labels: vec![SymbolicLabel {
+                name: "1#0".into(),
+                code_addr: 1,
+            }],
+            instructions: vec![
+                Instruction::InputD {
+                    data_addr: var("input", 1),
+                },
+                Instruction::Load {
+                    rx: Register::A,
+                    data_addr: var("total", 0),
+                },
+                Instruction::Jump {
+                    pc_offset: label("1#0"),
+                },
+            ],
+        };
+        Object::new("main".into(), ir)
+    }
+
+    /// Adds up the input of the other module
+    fn sum() -> Object {
+        let ir = SymbolicIr {
+            variables: vec![SymbolicVariable {
+                name: "total".into(),
+                data_addr: 0,
+                data: vec![var("input", 0)],
+                fixed: false,
+            }],
+            labels: vec![
+                SymbolicLabel {
+                    name: "sum".into(),
+                    code_addr: 0,
+                },
+                SymbolicLabel {
+                    name: "1#0".into(),
+                    code_addr: 1,
+                },
+            ],
+            instructions: vec![
+                Instruction::Load {
+                    rx: Register::B,
+                    data_addr: var("input", 1),
+                },
+                Instruction::Branch {
+                    cond: crate::BranchCondition::Equal,
+                    pc_offset: label("1#0"),
+                },
+            ],
+        };
+        Object::new("sum".into(), ir)
+    }
+
+    #[test]
+    fn object1() {
+        let main = main();
+        assert_eq!(main.exports, [Symbol::Variable("input".into())]);
+        assert_eq!(main.imports, ["total"]);
+        assert_eq!(
+            main.relocations,
+            [
+                Relocation {
+                    site: Site::Code { index: 0 },
+                    symbol: Symbol::Variable("input".into()),
+                },
+                Relocation {
+                    site: Site::Code { index: 1 },
+                    symbol: Symbol::Variable("total".into()),
+                },
+                Relocation {
+                    site: Site::Code { index: 2 },
+                    symbol: Symbol::Label("1#0".into()),
+                },
+            ]
+        );
+
+        let sum = sum();
+        assert_eq!(
+            sum.exports,
+            [
+                Symbol::Variable("total".into()),
+                Symbol::Label("sum".into())
+            ]
+        );
+        assert_eq!(sum.imports, ["input"]);
+        assert_eq!(
+            sum.relocations[0].site,
+            Site::Code { index: 0 },
+            "code comes before data"
+        );
+        assert_eq!(
+            sum.relocations[2].site,
+            Site::Data {
+                variable: 0,
+                index: 0
+            }
+        );
+    }
+
+    #[test]
+    fn object2() {
+        let linked = link(&[main(), sum()]).unwrap();
+        let names: Vec<_> = linked.labels.iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(names, ["main:1#0", "sum", "sum:1#0"]);
+
+        let ir = linked.resolve().unwrap();
+        // the variables of `sum` come after the two bytes of `main`
+        assert_eq!(ir.data_memory().unwrap(), [0, 0, 0]);
+        assert!(matches!(
+            ir.instructions.as_slice(),
+            [
+                Instruction::InputD { data_addr: 1 },
+                Instruction::Load { data_addr: 2, .. },
+                Instruction::Jump { pc_offset: -2 },
+                Instruction::Load { data_addr: 1, .. },
+                Instruction::Branch { pc_offset: -1, .. },
+            ]
+        ));
+    }
+
+    #[test]
+    fn object3() {
+        let errors = link(&[main()]).unwrap_err();
+        assert!(matches!(
+            errors.as_slice(),
+            [LinkError::MissingSymbol { name, module }] if name == "total" && module == "main"
+        ));
+
+        let mut other = sum();
+        other.name = "other".into();
+        let errors = link(&[main(), sum(), sum(), other]).unwrap_err();
+        assert!(matches!(
+            errors.as_slice(),
+            [
+                LinkError::DuplicateModule { module },
+                LinkError::DuplicateSymbol { name: total, first, second },
+                LinkError::DuplicateSymbol { name: sum, .. },
+            ] if module == "sum" && total == "total" && first == "sum" && second == "other"
+                && sum == "sum"
+        ));
+
+        let mut main = main();
+        main.relocations[0].site = Site::Code { index: 5 };
+        let errors = link(&[main, sum()]).unwrap_err();
+        assert!(matches!(
+            errors.as_slice(),
+            [LinkError::BadRelocation {
+                site: Site::Code { index: 5 },
+                ..
+            }]
+        ));
+    }
+}
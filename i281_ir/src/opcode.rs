@@ -1,3 +1,5 @@
+use crate::isa::InstructionInfo;
+
 #[repr(u8)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
@@ -20,34 +22,13 @@ pub enum OpCode {
     Branch = 0b1111,
 }
 
+/// Aliases and pseudo instructions map to the opcode of the instruction they are assembled to
 impl From<i281_ast::OpCode> for OpCode {
     fn from(code: i281_ast::OpCode) -> Self {
-        match code {
-            i281_ast::OpCode::NoOp(_) => Self::NoOp,
-            i281_ast::OpCode::InputC(_)
-            | i281_ast::OpCode::InputCF(_)
-            | i281_ast::OpCode::InputD(_)
-            | i281_ast::OpCode::InputDF(_) => Self::Input,
-            i281_ast::OpCode::Move(_) => Self::Move,
-            i281_ast::OpCode::LoadI(_) | i281_ast::OpCode::LoadP(_) => Self::LoadI,
-            i281_ast::OpCode::Add(_) => Self::Add,
-            i281_ast::OpCode::AddI(_) => Self::AddI,
-            i281_ast::OpCode::Sub(_) => Self::Sub,
-            i281_ast::OpCode::SubI(_) => Self::SubI,
-            i281_ast::OpCode::Load(_) => Self::Load,
-            i281_ast::OpCode::LoadF(_) => Self::LoadF,
-            i281_ast::OpCode::Store(_) => Self::Store,
-            i281_ast::OpCode::StoreF(_) => Self::StoreF,
-            i281_ast::OpCode::ShiftL(_) | i281_ast::OpCode::ShiftR(_) => Self::Shift,
-            i281_ast::OpCode::Cmp(_) => Self::Cmp,
-            i281_ast::OpCode::Jump(_) => Self::Jump,
-            i281_ast::OpCode::BrE(_)
-            | i281_ast::OpCode::BrZ(_)
-            | i281_ast::OpCode::BrNE(_)
-            | i281_ast::OpCode::BrNZ(_)
-            | i281_ast::OpCode::BrG(_)
-            | i281_ast::OpCode::BrGE(_) => Self::Branch,
-        }
+        InstructionInfo::lookup(&code.to_string())
+            .expect("every opcode of the parser is in the isa table")
+            .layout
+            .opcode
     }
 }
 
@@ -56,3 +37,27 @@ impl From<OpCode> for u16 {
         Self::from(v as u8)
     }
 }
+
+/// Only the low 4 bits of the field are used
+impl From<u16> for OpCode {
+    fn from(v: u16) -> Self {
+        match v & 0b1111 {
+            0b0000 => Self::NoOp,
+            0b0001 => Self::Input,
+            0b0010 => Self::Move,
+            0b0011 => Self::LoadI,
+            0b0100 => Self::Add,
+            0b0101 => Self::AddI,
+            0b0110 => Self::Sub,
+            0b0111 => Self::SubI,
+            0b1000 => Self::Load,
+            0b1001 => Self::LoadF,
+            0b1010 => Self::Store,
+            0b1011 => Self::StoreF,
+            0b1100 => Self::Shift,
+            0b1101 => Self::Cmp,
+            0b1110 => Self::Jump,
+            _ => Self::Branch,
+        }
+    }
+}
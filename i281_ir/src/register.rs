@@ -30,3 +30,15 @@ impl From<Register> for u16 {
         Self::from(v as u8)
     }
 }
+
+/// Only the low 2 bits of the field are used
+impl From<u16> for Register {
+    fn from(v: u16) -> Self {
+        match v & 0b11 {
+            0b00 => Self::A,
+            0b01 => Self::B,
+            0b10 => Self::C,
+            _ => Self::D,
+        }
+    }
+}
@@ -11,3 +11,13 @@ impl From<ShiftDirection> for u16 {
         Self::from(v as u8)
     }
 }
+
+/// Only the low bit of the field is used
+impl From<u16> for ShiftDirection {
+    fn from(v: u16) -> Self {
+        match v & 0b1 {
+            0b0 => Self::Left,
+            _ => Self::Right,
+        }
+    }
+}
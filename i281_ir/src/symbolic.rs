@@ -0,0 +1,377 @@
+use std::fmt;
+
+use crate::{
+    isa::{DataField, Field},
+    Instruction, Ir, Variable,
+};
+
+/// A name that is only given an address when the program is resolved
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Symbol {
+    /// The code address of a label
+    Label(String),
+    /// The data address of a variable
+    Variable(String),
+}
+
+impl Symbol {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Label(name) | Self::Variable(name) => name,
+        }
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// An operand that is either a constant or the address of a symbol plus a constant
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(from = "serde_repr::Value", into = "serde_repr::Value")
+)]
+pub struct Value {
+    pub symbol: Option<Symbol>,
+    pub offset: i16,
+}
+
+impl Value {
+    pub fn constant(offset: i16) -> Self {
+        Self {
+            symbol: None,
+            offset,
+        }
+    }
+
+    pub fn symbol(symbol: Symbol, offset: i16) -> Self {
+        Self {
+            symbol: Some(symbol),
+            offset,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.symbol, self.offset) {
+            (None, offset) => write!(f, "{offset}"),
+            (Some(symbol), 0) => write!(f, "{symbol}"),
+            (Some(symbol), offset) if offset < 0 => write!(f, "{symbol} - {}", -offset),
+            (Some(symbol), offset) => write!(f, "{symbol} + {offset}"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct SymbolicVariable {
+    pub name: String,
+    /// Where the first byte is placed in data memory
+    pub data_addr: usize,
+    pub data: Vec<Value>,
+    /// Placed with `.org` or `@`, linking keeps it at this address
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "std::ops::Not::not")
+    )]
+    pub fixed: bool,
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct SymbolicLabel {
+    /// Local labels are qualified with their scope and numeric labels are numbered, e.g. `1#0`
+    pub name: String,
+    /// The index of the instruction the label is in front of
+    pub code_addr: usize,
+}
+
+/// An [`Ir`] where operands still refer to the labels and variables they were written with
+///
+/// The target of a jump or a branch is an absolute code address here, it is only turned into an
+/// offset from the pc when the program is resolved. That way instructions can be moved and
+/// variables relocated by only changing the labels and variables.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct SymbolicIr {
+    pub variables: Vec<SymbolicVariable>,
+    pub labels: Vec<SymbolicLabel>,
+    pub instructions: Vec<Instruction<Value>>,
+}
+
+#[derive(Clone, Debug, miette::Diagnostic, thiserror::Error)]
+pub enum ResolveError {
+    #[error("`{symbol}` is not defined")]
+    #[diagnostic(
+        code(ir::undefined_symbol),
+        help("every symbol has to be one of the labels or variables of the ir")
+    )]
+    UndefinedSymbol { symbol: Symbol },
+    #[error("`{value}` is `{resolved}` which does not fit in a byte")]
+    #[diagnostic(code(ir::value_out_of_range))]
+    OutOfRange { value: Value, resolved: i64 },
+    #[error(
+        "instruction `{index}` jumps to `{target}` which is `{offset}` away, too far for a byte"
+    )]
+    #[diagnostic(code(ir::jump_too_far))]
+    JumpTooFar {
+        index: usize,
+        target: Value,
+        offset: i64,
+    },
+}
+
+impl SymbolicIr {
+    /// The address of a symbol, the first definition of a name is used
+    pub fn address(&self, symbol: &Symbol) -> Option<usize> {
+        match symbol {
+            Symbol::Label(name) => self
+                .labels
+                .iter()
+                .find(|label| &label.name == name)
+                .map(|label| label.code_addr),
+            Symbol::Variable(name) => self
+                .variables
+                .iter()
+                .find(|var| &var.name == name)
+                .map(|var| var.data_addr),
+        }
+    }
+
+    fn resolve_value(&self, value: &Value) -> Result<i64, ResolveError> {
+        let base = match &value.symbol {
+            Some(symbol) => self
+                .address(symbol)
+                .ok_or_else(|| ResolveError::UndefinedSymbol {
+                    symbol: symbol.clone(),
+                })?,
+            None => 0,
+        };
+        Ok(base as i64 + value.offset as i64)
+    }
+
+    /// Like the folded expressions of the analyzer a value can be anything that fits in a byte,
+    /// values above 127 are stored in two's complement
+    fn resolve_byte(&self, value: &Value) -> Result<i8, ResolveError> {
+        let resolved = self.resolve_value(value)?;
+        match (i8::MIN as i64..=u8::MAX as i64).contains(&resolved) {
+            true => Ok(resolved as i8),
+            false => Err(ResolveError::OutOfRange {
+                value: value.clone(),
+                resolved,
+            }),
+        }
+    }
+
+    /// Give every symbol its address, producing the ir the backends use
+    pub fn resolve(&self) -> Result<Ir, ResolveError> {
+        let variables = self
+            .variables
+            .iter()
+            .map(|var| {
+                let data = var
+                    .data
+                    .iter()
+                    .map(|value| self.resolve_byte(value))
+                    .collect::<Result<_, _>>()?;
+                Ok(Variable {
+                    data_addr: var.data_addr,
+                    data,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        let instructions = self
+            .instructions
+            .iter()
+            .enumerate()
+            .map(|(index, ins)| {
+                let relative = ins.info().layout.d == Field::Data(DataField::PcOffset);
+                ins.clone().try_map(|value| match relative {
+                    true => {
+                        let offset = self.resolve_value(&value)? - index as i64 - 1;
+                        i8::try_from(offset).map_err(|_| ResolveError::JumpTooFar {
+                            index,
+                            target: value,
+                            offset,
+                        })
+                    }
+                    false => self.resolve_byte(&value),
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Ir {
+            variables,
+            instructions,
+        })
+    }
+}
+
+/// Values are written as a plain number when they are constant so the json stays readable
+#[cfg(feature = "serde")]
+mod serde_repr {
+    use super::Symbol;
+
+    #[derive(serde::Deserialize, serde::Serialize)]
+    #[serde(untagged)]
+    pub(super) enum Value {
+        Constant(i16),
+        Label {
+            label: String,
+            #[serde(default, skip_serializing_if = "is_zero")]
+            offset: i16,
+        },
+        Variable {
+            variable: String,
+            #[serde(default, skip_serializing_if = "is_zero")]
+            offset: i16,
+        },
+    }
+
+    fn is_zero(offset: &i16) -> bool {
+        *offset == 0
+    }
+
+    impl From<Value> for super::Value {
+        fn from(value: Value) -> Self {
+            match value {
+                Value::Constant(offset) => Self::constant(offset),
+                Value::Label { label, offset } => Self::symbol(Symbol::Label(label), offset),
+                Value::Variable { variable, offset } => {
+                    Self::symbol(Symbol::Variable(variable), offset)
+                }
+            }
+        }
+    }
+
+    impl From<super::Value> for Value {
+        fn from(value: super::Value) -> Self {
+            match value.symbol {
+                None => Self::Constant(value.offset),
+                Some(Symbol::Label(label)) => Self::Label {
+                    label,
+                    offset: value.offset,
+                },
+                Some(Symbol::Variable(variable)) => Self::Variable {
+                    variable,
+                    offset: value.offset,
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ResolveError, Symbol, SymbolicIr, SymbolicLabel, SymbolicVariable, Value};
+    use crate::{Instruction, Register};
+
+    fn program() -> SymbolicIr {
+        let label = |name: &str| Value::symbol(Symbol::Label(name.into()), 0);
+        let var = |name: &str, offset| Value::symbol(Symbol::Variable(name.into()), offset);
+        SymbolicIr {
+            variables: vec![
+                SymbolicVariable {
+                    name: "arr".into(),
+                    data_addr: 0,
+                    data: vec![Value::constant(1), Value::constant(255)],
+                    fixed: false,
+                },
+                SymbolicVariable {
+                    name: "end".into(),
+                    data_addr: 2,
+                    data: vec![var("arr", 2)],
+                    fixed: false,
+                },
+            ],
+            labels: vec![SymbolicLabel {
+                name: "loop".into(),
+                code_addr: 1,
+            }],
+            instructions: vec![
+                Instruction::LoadI {
+                    rx: Register::A,
+                    value: var("end", 0),
+                },
+                Instruction::Load {
+                    rx: Register::B,
+                    data_addr: var("arr", 1),
+                },
+                Instruction::Jump {
+                    pc_offset: label("loop"),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn symbolic1() {
+        let ir = program().resolve().unwrap();
+        assert_eq!(ir.data_memory().unwrap(), [1, -1, 2]);
+        assert!(matches!(
+            ir.instructions.as_slice(),
+            [
+                Instruction::LoadI { value: 2, .. },
+                Instruction::Load { data_addr: 1, .. },
+                Instruction::Jump { pc_offset: -2 },
+            ]
+        ));
+
+        // moving code and data only needs the symbols to be changed
+        let mut moved = program();
+        moved.variables[0].data_addr = 5;
+        moved.instructions.insert(0, Instruction::NoOp);
+        moved.labels[0].code_addr += 1;
+        let ir = moved.resolve().unwrap();
+        assert_eq!(ir.variables[1].data, [7]);
+        assert!(matches!(
+            ir.instructions.as_slice(),
+            [
+                Instruction::NoOp,
+                Instruction::LoadI { value: 2, .. },
+                Instruction::Load { data_addr: 6, .. },
+                Instruction::Jump { pc_offset: -2 },
+            ]
+        ));
+    }
+
+    #[test]
+    fn symbolic2() {
+        let mut ir = program();
+        ir.labels.clear();
+        assert!(matches!(
+            ir.resolve(),
+            Err(ResolveError::UndefinedSymbol { symbol: Symbol::Label(name) }) if name == "loop"
+        ));
+
+        let mut ir = program();
+        ir.variables[1].data[0].offset = 300;
+        assert!(matches!(
+            ir.resolve(),
+            Err(ResolveError::OutOfRange { resolved: 300, .. })
+        ));
+
+        let mut ir = program();
+        ir.labels[0].code_addr = 200;
+        assert!(matches!(
+            ir.resolve(),
+            Err(ResolveError::JumpTooFar {
+                index: 2,
+                offset: 197,
+                ..
+            })
+        ));
+
+        let value = Value::symbol(Symbol::Variable("arr".into()), -1);
+        assert_eq!(value.to_string(), "arr - 1");
+        assert_eq!(Value::constant(-1).to_string(), "-1");
+    }
+}
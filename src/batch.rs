@@ -0,0 +1,354 @@
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+
+use i281_compiler::{
+    CompileOptions, DiagnosticInfo, EmitKind, LintLevels, CODE_ADDR_MAX, DATA_ADDR_MAX,
+};
+use miette::IntoDiagnostic;
+use rayon::prelude::*;
+use serde_json::json;
+use walkdir::WalkDir;
+
+#[derive(clap::Args, Debug)]
+pub struct BatchArgs {
+    /// The directory that is searched recursively for `.asm` files
+    dir: PathBuf,
+    /// Each file is compiled into a directory under this one that mirrors its path
+    #[arg(long, short, default_value_os_t = PathBuf::from("./i281build/"))]
+    out_dir: PathBuf,
+    /// Where the json report is written, defaults to `report.json` in the output directory
+    #[arg(long)]
+    report: Option<PathBuf>,
+    /// How many files are compiled at the same time, defaults to the number of cpus
+    #[arg(long, short)]
+    jobs: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Status {
+    Ok,
+    Warnings,
+    Errors,
+}
+
+impl Status {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Ok => "ok",
+            Self::Warnings => "warnings",
+            Self::Errors => "errors",
+        }
+    }
+}
+
+/// The result of compiling a single file in the batch
+#[derive(Debug)]
+struct FileReport {
+    /// The path of the file relative to the batch directory
+    file: PathBuf,
+    status: Status,
+    warnings: usize,
+    errors: usize,
+    /// Instructions used, `None` if the file could not be parsed
+    instructions: Option<usize>,
+    /// Bytes of data memory used, `None` if the file could not be parsed
+    data: Option<usize>,
+    diagnostics: Vec<DiagnosticInfo>,
+}
+
+/// Compile every assembly file in a directory and summarize the results
+///
+/// Returns an error if any of the files failed to compile.
+pub fn run(args: &BatchArgs, lints: &LintLevels) -> miette::Result<()> {
+    let files = find_files(&args.dir)?;
+    if files.is_empty() {
+        miette::bail!("no `.asm` files were found in `{}`", args.dir.display());
+    }
+
+    let options = CompileOptions {
+        emit: vec![EmitKind::Ast, EmitKind::Verilog],
+        lints: lints.clone(),
+        ..Default::default()
+    };
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(args.jobs.unwrap_or(0))
+        .build()
+        .into_diagnostic()?;
+    let reports = pool.install(|| compile_all(args, &files, &options));
+
+    print_summary(&reports);
+
+    let report_path = args
+        .report
+        .clone()
+        .unwrap_or_else(|| args.out_dir.join("report.json"));
+    if let Some(parent) = report_path.parent() {
+        std::fs::create_dir_all(parent).into_diagnostic()?;
+    }
+    let report = serde_json::to_string_pretty(&json_report(&reports)).into_diagnostic()?;
+    std::fs::write(&report_path, report + "\n").into_diagnostic()?;
+    println!("report written to {}", report_path.display());
+
+    let failed = reports
+        .iter()
+        .filter(|r| r.status == Status::Errors)
+        .count();
+    if failed > 0 {
+        miette::bail!("{failed} of {} files failed to compile", reports.len());
+    }
+    Ok(())
+}
+
+/// Every `.asm` file under `dir` relative to it, sorted so the summary is stable
+fn find_files(dir: &Path) -> miette::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in WalkDir::new(dir) {
+        let entry = entry.into_diagnostic()?;
+        if entry.file_type().is_file() && entry.path().extension().is_some_and(|e| e == "asm") {
+            let relative = entry.path().strip_prefix(dir).unwrap_or(entry.path());
+            files.push(relative.to_path_buf());
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Compile every file, a file that makes the compiler panic is reported as failed
+/// instead of taking the rest of the batch down with it
+fn compile_all(args: &BatchArgs, files: &[PathBuf], options: &CompileOptions) -> Vec<FileReport> {
+    files
+        .par_iter()
+        .map(|file| {
+            std::panic::catch_unwind(AssertUnwindSafe(|| compile_file(args, file, options)))
+                .unwrap_or_else(|payload| {
+                    let message = payload
+                        .downcast_ref::<&str>()
+                        .map(|s| s.to_string())
+                        .or_else(|| payload.downcast_ref::<String>().cloned())
+                        .unwrap_or_else(|| "unknown error".to_owned());
+                    FileReport {
+                        file: file.clone(),
+                        status: Status::Errors,
+                        warnings: 0,
+                        errors: 1,
+                        instructions: None,
+                        data: None,
+                        diagnostics: vec![io_error(&format!("the compiler panicked: {message}"))],
+                    }
+                })
+        })
+        .collect()
+}
+
+fn compile_file(args: &BatchArgs, file: &Path, options: &CompileOptions) -> FileReport {
+    let mut report = FileReport {
+        file: file.to_path_buf(),
+        status: Status::Errors,
+        warnings: 0,
+        errors: 0,
+        instructions: None,
+        data: None,
+        diagnostics: Vec::new(),
+    };
+
+    let source = match std::fs::read_to_string(args.dir.join(file)) {
+        Ok(source) => source,
+        Err(err) => {
+            report.errors = 1;
+            report.diagnostics.push(io_error(&err.to_string()));
+            return report;
+        }
+    };
+
+    let output = i281_compiler::compile(&source, options);
+    if let Some(ast) = &output.ast {
+        report.instructions = Some(ast.code.instructions.len());
+        report.data = Some(
+            ast.data
+                .iter()
+                .flat_map(|data| data.variables.iter())
+                .map(|var| var.value.size_of())
+                .sum(),
+        );
+    }
+    report.errors = output.diagnostics.iter().filter(|d| d.is_error()).count();
+    report.warnings = output.diagnostics.len() - report.errors;
+    report.diagnostics = output
+        .diagnostics
+        .iter()
+        .map(|d| DiagnosticInfo::new(d, &source))
+        .collect();
+
+    if !output.has_errors() {
+        let out_dir = args.out_dir.join(file.with_extension(""));
+        let written = std::fs::create_dir_all(&out_dir)
+            .into_diagnostic()
+            .and_then(|()| {
+                output.verilog.iter().try_for_each(|module| {
+                    let path = out_dir.join(format!("{}.v", module.name));
+                    crate::write_if_changed(&path, module.source.as_bytes())
+                })
+            });
+        if let Err(err) = written {
+            report.errors += 1;
+            report.diagnostics.push(io_error(&err.to_string()));
+        }
+    }
+
+    report.status = match (report.errors, report.warnings) {
+        (0, 0) => Status::Ok,
+        (0, _) => Status::Warnings,
+        _ => Status::Errors,
+    };
+    report
+}
+
+fn io_error(message: &str) -> DiagnosticInfo {
+    DiagnosticInfo {
+        severity: i281_compiler::Severity::Error,
+        code: None,
+        message: message.to_owned(),
+        spans: Vec::new(),
+        help: None,
+    }
+}
+
+fn usage(used: Option<usize>, max: i8) -> String {
+    match used {
+        Some(used) => format!("{used}/{max}"),
+        None => "-".to_owned(),
+    }
+}
+
+fn print_summary(reports: &[FileReport]) {
+    let width = reports
+        .iter()
+        .map(|r| r.file.display().to_string().len())
+        .max()
+        .unwrap_or(0)
+        .max("file".len());
+
+    println!(
+        "{:width$}  {:8}  {:>8}  {:>6}  {:>5}  {:>5}",
+        "file", "status", "warnings", "errors", "code", "data"
+    );
+    for r in reports {
+        println!(
+            "{:width$}  {:8}  {:>8}  {:>6}  {:>5}  {:>5}",
+            r.file.display().to_string(),
+            r.status.as_str(),
+            r.warnings,
+            r.errors,
+            usage(r.instructions, CODE_ADDR_MAX),
+            usage(r.data, DATA_ADDR_MAX),
+        );
+    }
+
+    let count = |status| reports.iter().filter(|r| r.status == status).count();
+    println!(
+        "\n{} files: {} ok, {} with warnings, {} failed",
+        reports.len(),
+        count(Status::Ok),
+        count(Status::Warnings),
+        count(Status::Errors)
+    );
+}
+
+fn json_report(reports: &[FileReport]) -> serde_json::Value {
+    let files: Vec<_> = reports
+        .iter()
+        .map(|r| {
+            json!({
+                "file": r.file.display().to_string(),
+                "status": r.status.as_str(),
+                "warnings": r.warnings,
+                "errors": r.errors,
+                "instructions": { "used": r.instructions, "max": CODE_ADDR_MAX },
+                "data": { "used": r.data, "max": DATA_ADDR_MAX },
+                "diagnostics": r.diagnostics,
+            })
+        })
+        .collect();
+    let failed = reports
+        .iter()
+        .filter(|r| r.status == Status::Errors)
+        .count();
+
+    json!({
+        "total": reports.len(),
+        "failed": failed,
+        "files": files,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use i281_compiler::{CompileOptions, EmitKind};
+
+    use super::{compile_all, compile_file, find_files, BatchArgs, Status};
+
+    #[test]
+    fn batch1() {
+        let out_dir = std::env::temp_dir().join(format!("i281batch{}", std::process::id()));
+        let args = BatchArgs {
+            dir: PathBuf::from("examples"),
+            out_dir: out_dir.clone(),
+            report: None,
+            jobs: None,
+        };
+
+        let files = find_files(&args.dir).unwrap();
+        assert!(!files.is_empty());
+        let report = compile_file(&args, &files[0], &CompileOptions::default());
+        assert_ne!(report.status, Status::Errors, "{report:?}");
+        assert!(out_dir
+            .join(files[0].with_extension(""))
+            .join("User_Data.v")
+            .exists());
+
+        let missing = compile_file(&args, "missing.asm".as_ref(), &CompileOptions::default());
+        assert_eq!(missing.status, Status::Errors);
+        assert!(missing.instructions.is_none());
+
+        std::fs::remove_dir_all(out_dir).unwrap();
+    }
+
+    #[test]
+    fn batch2() {
+        let dir = std::env::temp_dir().join(format!("i281batch2-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("add.asm"),
+            ".code\n    LOADI A, 1\n    ADDI A, 2\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("pointer.asm"),
+            ".data\nx BYTE 1, 2\n.code\n    LOADP A, {x + 1}\n    LOAD B, [x]\n",
+        )
+        .unwrap();
+        let args = BatchArgs {
+            dir: dir.clone(),
+            out_dir: dir.join("out"),
+            report: None,
+            jobs: None,
+        };
+
+        let files = find_files(&args.dir).unwrap();
+        let options = CompileOptions {
+            emit: vec![EmitKind::Ast, EmitKind::Verilog],
+            ..Default::default()
+        };
+        let reports = compile_all(&args, &files, &options);
+        assert_eq!(reports.len(), 2);
+        for report in &reports {
+            assert_eq!(report.status, Status::Ok, "{report:?}");
+        }
+        assert_eq!(reports[1].file, PathBuf::from("pointer.asm"));
+        assert_eq!(reports[1].instructions, Some(2));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,506 @@
+use std::path::PathBuf;
+
+use i281_ast::{keyword, keyword::Keyword, OpCode, Oper, Register};
+use serde_json::json;
+
+use crate::write_if_changed;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum GrammarFormat {
+    /// A TextMate grammar for VS Code, Sublime Text and other editors
+    Textmate,
+    /// A tree-sitter `grammar.js` and the highlight queries for it
+    TreeSitter,
+    /// A vim syntax file
+    Vim,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct GrammarArgs {
+    #[arg(long, short, value_enum)]
+    format: GrammarFormat,
+    /// Write the grammar files into this directory instead of printing them, tree-sitter needs
+    /// one as its grammar and highlight queries are separate files
+    #[arg(long, short)]
+    out_dir: Option<PathBuf>,
+}
+
+/// Binary, hexadecimal and decimal numbers with the optional suffix, the same as the parser
+const HEX: &str = "0[xX][0-9a-fA-F_]+([ui]8)?";
+const BIN: &str = "0[bB][01_]+([ui]8)?";
+const DEC: &str = "[0-9][0-9_]*([ui]8)?";
+const IDENT: &str = "[A-Za-z_][A-Za-z0-9_]*";
+/// A numeric label used as a jump target, e.g. `1b` or `1f`
+const LABEL_REF: &str = "[0-9]+[bf]";
+
+/// The words of the language taken from the tables the parser uses
+struct Words {
+    /// Longest first so an alternation never stops at a shorter opcode, e.g. `LOAD` in `LOADI`
+    opcodes: Vec<&'static str>,
+    registers: Vec<&'static str>,
+    /// Keywords written after a `.` like `.data`
+    directives: Vec<&'static str>,
+    /// Keywords used within declarations like `BYTE`
+    keywords: Vec<&'static str>,
+    /// Operators and their precedence
+    operators: Vec<(String, u8)>,
+}
+
+impl Words {
+    fn new() -> Self {
+        let mut opcodes = OpCode::ALL.to_vec();
+        opcodes.sort_by_key(|op| std::cmp::Reverse(op.len()));
+        let dotted = [
+            keyword::Data.to_string(),
+            keyword::Code.to_string(),
+            keyword::Org.to_string(),
+        ];
+        let (directives, keywords) = Keyword::ALL
+            .iter()
+            .partition(|kw| dotted.iter().any(|d| d == *kw));
+        Self {
+            opcodes,
+            registers: Register::ALL.to_vec(),
+            directives,
+            keywords,
+            operators: Oper::ALL
+                .iter()
+                .map(|op| (op.to_string(), op.precedence()))
+                .collect(),
+        }
+    }
+
+    /// A regex that matches any operator
+    fn operator_regex(&self) -> String {
+        let mut operators: Vec<_> = self.operators.iter().map(|(op, _)| op.as_str()).collect();
+        operators.sort_by_key(|op| std::cmp::Reverse(op.len()));
+        operators
+            .iter()
+            .map(|op| escape(op))
+            .collect::<Vec<_>>()
+            .join("|")
+    }
+}
+
+/// Escape the characters that are special in a regex
+fn escape(s: &str) -> String {
+    s.chars()
+        .flat_map(|c| match c {
+            '\\' | '^' | '$' | '.' | '|' | '?' | '*' | '+' | '(' | ')' | '[' | ']' | '{' | '}'
+            | '/' => vec!['\\', c],
+            c => vec![c],
+        })
+        .collect()
+}
+
+/// A regex that matches the word in any case, tree-sitter has no case insensitive flag
+fn any_case(word: &str) -> String {
+    word.chars()
+        .map(|c| match c.is_ascii_alphabetic() {
+            true => format!("[{}{}]", c.to_ascii_lowercase(), c.to_ascii_uppercase()),
+            false => escape(&c.to_string()),
+        })
+        .collect()
+}
+
+pub fn run(args: &GrammarArgs) -> miette::Result<()> {
+    let words = Words::new();
+    let files = match args.format {
+        GrammarFormat::Textmate => vec![("i281.tmLanguage.json", textmate(&words))],
+        GrammarFormat::TreeSitter => vec![
+            ("grammar.js", tree_sitter(&words)),
+            ("queries/highlights.scm", highlights(&words)),
+        ],
+        GrammarFormat::Vim => vec![("syntax/i281.vim", vim(&words))],
+    };
+    let Some(out_dir) = &args.out_dir else {
+        let [(_, contents)] = files.as_slice() else {
+            let names: Vec<_> = files.iter().map(|(name, _)| *name).collect();
+            return Err(miette::miette!(
+                help = "give a directory to write them into with `--out-dir`",
+                "the grammar is made of more than one file: {}",
+                names.join(", ")
+            ));
+        };
+        print!("{contents}");
+        return Ok(());
+    };
+    for (name, contents) in files {
+        let path = out_dir.join(name);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|err| miette::miette!("{err}"))?;
+        }
+        write_if_changed(&path, contents.as_bytes())?;
+    }
+    Ok(())
+}
+
+fn textmate(words: &Words) -> String {
+    let alternation = |words: &[&str]| {
+        words
+            .iter()
+            .map(|w| escape(w))
+            .collect::<Vec<_>>()
+            .join("|")
+    };
+    let grammar = json!({
+        "$schema": "https://raw.githubusercontent.com/martinring/tmlanguage/master/tmlanguage.json",
+        "name": "i281 Assembly",
+        "scopeName": "source.i281",
+        "fileTypes": ["asm"],
+        "patterns": [
+            { "include": "#comment" },
+            { "include": "#directive" },
+            { "include": "#label" },
+            { "include": "#address" },
+            { "include": "#expression" },
+        ],
+        "repository": {
+            "comment": {
+                "name": "comment.line.semicolon.i281",
+                "match": ";.*$",
+            },
+            "directive": {
+                "name": "keyword.control.directive.i281",
+                "match": format!("(?i)\\.(?:{})\\b", alternation(&words.directives)),
+            },
+            "label": {
+                "match": format!("(\\.?{IDENT}|[0-9]+)\\s*(:)"),
+                "captures": {
+                    "1": { "name": "entity.name.label.i281" },
+                    "2": { "name": "punctuation.separator.label.i281" },
+                },
+            },
+            "address": {
+                "name": "meta.address.i281",
+                "begin": "\\[",
+                "end": "\\]",
+                "beginCaptures": { "0": { "name": "punctuation.definition.address.begin.i281" } },
+                "endCaptures": { "0": { "name": "punctuation.definition.address.end.i281" } },
+                "patterns": [{ "include": "#expression" }],
+            },
+            "expression": {
+                "patterns": [
+                    {
+                        "name": "keyword.other.opcode.i281",
+                        "match": format!("(?i)\\b(?:{})\\b", alternation(&words.opcodes)),
+                    },
+                    {
+                        "name": "keyword.other.i281",
+                        "match": format!("(?i)\\b(?:{})\\b", alternation(&words.keywords)),
+                    },
+                    {
+                        "name": "variable.language.register.i281",
+                        "match": format!("(?i)\\b(?:{})\\b", alternation(&words.registers)),
+                    },
+                    {
+                        "name": "entity.name.label.reference.i281",
+                        "match": format!("\\b{LABEL_REF}\\b"),
+                    },
+                    { "name": "constant.numeric.hex.i281", "match": format!("\\b{HEX}\\b") },
+                    { "name": "constant.numeric.binary.i281", "match": format!("\\b{BIN}\\b") },
+                    { "name": "constant.numeric.decimal.i281", "match": format!("\\b{DEC}\\b") },
+                    {
+                        "name": "constant.language.uninitialized.i281",
+                        "match": "\\?",
+                    },
+                    {
+                        "name": "keyword.operator.arithmetic.i281",
+                        "match": words.operator_regex(),
+                    },
+                    { "name": "punctuation.separator.comma.i281", "match": "," },
+                ],
+            },
+        },
+    });
+    let mut out = serde_json::to_string_pretty(&grammar).expect("the grammar is valid json");
+    out.push('\n');
+    out
+}
+
+fn tree_sitter(words: &Words) -> String {
+    let choice = |words: &[&str]| {
+        words
+            .iter()
+            .map(|w| format!("/{}/", any_case(w)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let directives: Vec<_> = words.directives.iter().map(|d| format!(".{d}")).collect();
+    let directives: Vec<_> = directives.iter().map(String::as_str).collect();
+    let operators = words
+        .operators
+        .iter()
+        .map(|(op, prec)| format!("['{op}', {prec}]"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        r#"// Generated by `compile281 gen-grammar --format tree-sitter`, do not edit by hand
+
+// operators and their precedence, a higher precedence binds tighter
+const OPERATORS = [{operators}];
+
+const sep1 = (rule, separator) => seq(rule, repeat(seq(separator, rule)));
+
+module.exports = grammar({{
+  name: 'i281',
+
+  extras: $ => [/[ \t\r]/, $.comment],
+
+  rules: {{
+    source_file: $ => seq(repeat(seq(optional($._line), '\n')), optional($._line)),
+
+    _line: $ => choice($.directive, $.variable, $.statement),
+
+    directive: $ => seq(field('name', $.directive_name), optional($._expression)),
+
+    // the same length as a local label so the precedence decides
+    directive_name: $ => token(prec(2, choice({directives}))),
+
+    variable: $ => seq(
+      field('name', $.identifier),
+      $.keyword,
+      sep1($._value, ','),
+      optional(seq('@', field('address', $._expression))),
+    ),
+
+    _value: $ => choice($._expression, $.uninitialized, $.dup),
+
+    uninitialized: $ => '?',
+
+    dup: $ => seq(field('count', $.number), $.keyword, '(', sep1($._value, ','), ')'),
+
+    statement: $ => choice(seq(repeat1($.label), optional($.instruction)), $.instruction),
+
+    label: $ => seq(field('name', choice($.identifier, $.local_identifier, $.number)), ':'),
+
+    instruction: $ => seq(field('opcode', $.opcode), optional(sep1($._operand, ','))),
+
+    _operand: $ => choice($.address, $._expression),
+
+    address: $ => seq('[', $._expression, ']'),
+
+    _expression: $ => choice(
+      $.number,
+      $.register,
+      $.identifier,
+      $.local_identifier,
+      $.qualified_identifier,
+      $.label_reference,
+      $.unary_expression,
+      $.binary_expression,
+      $.parenthesized_expression,
+    ),
+
+    unary_expression: $ => prec(10, seq('-', $._expression)),
+
+    binary_expression: $ => choice(...OPERATORS.map(([operator, precedence]) =>
+      prec.left(precedence, seq(
+        field('left', $._expression),
+        field('operator', operator),
+        field('right', $._expression),
+      )),
+    )),
+
+    parenthesized_expression: $ => seq('(', $._expression, ')'),
+
+    opcode: $ => token(prec(1, choice({opcodes}))),
+
+    register: $ => token(prec(1, choice({registers}))),
+
+    keyword: $ => token(prec(1, choice({keywords}))),
+
+    number: $ => token(choice(/{HEX}/, /{BIN}/, /{DEC}/)),
+
+    label_reference: $ => token(prec(1, /{LABEL_REF}/)),
+
+    identifier: $ => /{IDENT}/,
+
+    local_identifier: $ => /\.{IDENT}/,
+
+    qualified_identifier: $ => /{IDENT}\.{IDENT}/,
+
+    comment: $ => /;[^\n]*/,
+  }},
+}});
+"#,
+        directives = choice(&directives),
+        opcodes = choice(&words.opcodes),
+        registers = choice(&words.registers),
+        keywords = choice(&words.keywords),
+    )
+}
+
+fn highlights(words: &Words) -> String {
+    let operators = words
+        .operators
+        .iter()
+        .map(|(op, _)| format!("\"{op}\""))
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!(
+        r#"; Generated by `compile281 gen-grammar --format tree-sitter`, do not edit by hand
+
+(comment) @comment
+(directive_name) @keyword.directive
+(opcode) @keyword
+(keyword) @keyword
+(register) @variable.builtin
+(number) @number
+(uninitialized) @constant.builtin
+(label name: (_) @label)
+(label_reference) @label
+(local_identifier) @label
+(qualified_identifier) @label
+(variable name: (identifier) @variable)
+["[" "]" "(" ")"] @punctuation.bracket
+["," ":" "@"] @punctuation.delimiter
+[{operators}] @operator
+"#
+    )
+}
+
+fn vim(words: &Words) -> String {
+    let directives = words.directives.join("\\|");
+    // vim regexes use `\%(` for groups, `\+` for one or more and `\=` for optional
+    let vim_regex = |regex: &str| {
+        regex
+            .chars()
+            .map(|c| match c {
+                '(' => "\\%(".to_owned(),
+                ')' => "\\)".to_owned(),
+                '+' => "\\+".to_owned(),
+                '?' => "\\=".to_owned(),
+                c => c.to_string(),
+            })
+            .collect::<String>()
+    };
+    format!(
+        r#"" Vim syntax file
+" Language: i281 assembly
+" Generated by `compile281 gen-grammar --format vim`, do not edit by hand
+
+if exists("b:current_syntax")
+  finish
+endif
+
+syntax case ignore
+
+syntax keyword i281Opcode {opcodes}
+syntax keyword i281Register {registers}
+syntax keyword i281Keyword {keywords}
+syntax match i281Directive "\.\%({directives}\)\>"
+syntax match i281Number "\<{hex}\>"
+syntax match i281Number "\<{bin}\>"
+syntax match i281Number "\<{dec}\>"
+syntax match i281LabelRef "\<{label_ref}\>"
+syntax match i281Label "\%(\.\={ident}\|[0-9]\+\)\ze\s*:"
+syntax match i281Operator "{operators}"
+syntax match i281Uninitialized "?"
+syntax match i281Address "[][]"
+syntax match i281Comment ";.*$" contains=@Spell
+
+hi def link i281Opcode Statement
+hi def link i281Register Identifier
+hi def link i281Keyword Type
+hi def link i281Directive PreProc
+hi def link i281Number Number
+hi def link i281LabelRef Label
+hi def link i281Label Label
+hi def link i281Operator Operator
+hi def link i281Uninitialized Constant
+hi def link i281Address Delimiter
+hi def link i281Comment Comment
+
+let b:current_syntax = "i281"
+"#,
+        opcodes = words.opcodes.join(" "),
+        registers = words.registers.join(" "),
+        keywords = words.keywords.join(" "),
+        hex = vim_regex(HEX),
+        bin = vim_regex(BIN),
+        dec = vim_regex(DEC),
+        label_ref = vim_regex(LABEL_REF),
+        ident = vim_regex(IDENT),
+        operators = words
+            .operators
+            .iter()
+            .map(|(op, _)| op.replace('*', "\\*"))
+            .collect::<Vec<_>>()
+            .join("\\|"),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use i281_ast::{keyword::Keyword, OpCode, Register};
+    use regex_lite::Regex;
+
+    use super::{any_case, highlights, textmate, tree_sitter, vim, Words};
+
+    #[test]
+    fn grammar1() {
+        let words = Words::new();
+        let textmate = textmate(&words);
+        serde_json::from_str::<serde_json::Value>(&textmate).unwrap();
+        let tree_sitter = tree_sitter(&words);
+        let vim = vim(&words);
+        for op in OpCode::ALL {
+            assert!(textmate.contains(op), "{op} is missing from textmate");
+            assert!(
+                tree_sitter.contains(&any_case(op)),
+                "{op} is missing from tree-sitter"
+            );
+            assert!(vim.contains(op), "{op} is missing from vim");
+        }
+        for word in Register::ALL.iter().chain(Keyword::ALL) {
+            assert!(
+                textmate.contains(word) && vim.contains(word),
+                "{word} is missing"
+            );
+        }
+        assert!(highlights(&words).contains("\"<<\""));
+    }
+
+    #[test]
+    fn grammar2() {
+        let words = Words::new();
+        assert_eq!(words.directives, ["code", "data", "org"]);
+        assert_eq!(words.keywords, ["BYTE", "DUP"]);
+        // `LOADI` has to be tried before `LOAD` or it would never match in full
+        let position = |op| words.opcodes.iter().position(|o| *o == op).unwrap();
+        assert!(position("LOADI") < position("LOAD"));
+        assert_eq!(any_case("Br1"), "[bB][rR]1");
+    }
+
+    #[test]
+    fn grammar3() {
+        // the generated patterns match the literals and labels the parser accepts
+        let grammar: serde_json::Value = serde_json::from_str(&textmate(&Words::new())).unwrap();
+        let repository = &grammar["repository"];
+        let pattern = |name: &str| {
+            let patterns = repository["expression"]["patterns"].as_array().unwrap();
+            let found = patterns.iter().find(|p| p["name"] == name).unwrap();
+            Regex::new(found["match"].as_str().unwrap()).unwrap()
+        };
+        let matches = |regex: &Regex, sample: &str| {
+            regex
+                .find(sample)
+                .is_some_and(|found| found.as_str() == sample)
+        };
+
+        assert!(matches(&pattern("constant.numeric.hex.i281"), "0x_80u8"));
+        assert!(matches(
+            &pattern("constant.numeric.binary.i281"),
+            "0b1010_0101"
+        ));
+        assert!(matches(&pattern("constant.numeric.decimal.i281"), "200u8"));
+        assert!(matches(&pattern("entity.name.label.reference.i281"), "1b"));
+        assert!(!matches(&pattern("constant.numeric.decimal.i281"), "1b"));
+
+        let label = Regex::new(repository["label"]["match"].as_str().unwrap()).unwrap();
+        let captures = label.captures(".loop:").unwrap();
+        assert_eq!((&captures[1], &captures[2]), (".loop", ":"));
+        assert_eq!(&label.captures("1:  JUMP 1b").unwrap()[1], "1");
+    }
+}
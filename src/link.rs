@@ -0,0 +1,51 @@
+use std::path::PathBuf;
+
+use i281_compiler::{CompileOptions, Diagnostic, Error, Failure, LintLevels};
+use i281_ir::Object;
+use miette::IntoDiagnostic;
+
+use crate::write_verilog;
+
+#[derive(clap::Args, Debug)]
+pub struct LinkArgs {
+    /// Object files produced by `--emit object`, the program starts in the first one
+    #[arg(required = true)]
+    objects: Vec<PathBuf>,
+    #[arg(long, short, default_value_os_t = PathBuf::from("./i281build/"))]
+    out_dir: PathBuf,
+}
+
+/// Link separately assembled modules into a single program and write its verilog
+pub fn run(args: &LinkArgs, lints: &LintLevels) -> miette::Result<()> {
+    let objects = args
+        .objects
+        .iter()
+        .map(|path| {
+            let input = std::fs::read_to_string(path).into_diagnostic()?;
+            serde_json::from_str::<Object>(&input)
+                .map_err(|err| miette::miette!("`{}` is not an object file: {err}", path.display()))
+        })
+        .collect::<miette::Result<Vec<_>>>()?;
+
+    let mut err = std::io::stderr().lock();
+    let ir = match i281_ir::link(&objects) {
+        Ok(ir) => ir,
+        Err(errors) => {
+            let diagnostics: Vec<Diagnostic> =
+                errors.into_iter().map(|e| Error::from(e).into()).collect();
+            i281_compiler::render_diagnostics(&mut err, None, &diagnostics)?;
+            return Err(Failure::EncounteredError.into());
+        }
+    };
+
+    let options = CompileOptions {
+        lints: lints.clone(),
+        ..Default::default()
+    };
+    let output = i281_compiler::compile_symbolic_ir(ir, &options);
+    i281_compiler::render_diagnostics(&mut err, None, &output.diagnostics)?;
+    if output.has_errors() {
+        return Err(Failure::EncounteredError.into());
+    }
+    write_verilog(&args.out_dir, &output.verilog)
+}
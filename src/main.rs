@@ -1,83 +1,269 @@
 #![forbid(unsafe_code)]
 
-use std::{ffi::OsString, fs::OpenOptions, path::PathBuf};
+use std::{
+    ffi::{OsStr, OsString},
+    path::{Path, PathBuf},
+};
 
-use clap::Parser;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser};
 
-use i281_ast::Root;
-use i281_compiler::VerilogOutput;
+use i281_compiler::{CompileOptions, Failure, Level, LintLevels, LintSelector, VerilogModule};
 use miette::IntoDiagnostic;
 
+mod batch;
+mod grammar;
+mod link;
+mod message_format;
+mod simulate;
+mod watch;
+
+use message_format::MessageFormat;
+
 #[derive(clap::ValueEnum, Clone, Copy, Debug, Default)]
 enum EmitKind {
     Ast,
     Ir,
     #[default]
     Verilog,
+    Listing,
+    /// How much code and data memory is used and by what
+    Report,
+    /// How many cycles the program takes to run in the worst case
+    Cycles,
+    /// An object file to combine with other modules using `link`
+    Object,
+}
+
+impl From<EmitKind> for i281_compiler::EmitKind {
+    fn from(kind: EmitKind) -> Self {
+        match kind {
+            EmitKind::Ast => Self::Ast,
+            EmitKind::Ir => Self::Ir,
+            EmitKind::Verilog => Self::Verilog,
+            EmitKind::Listing => Self::Listing,
+            EmitKind::Report => Self::Report,
+            EmitKind::Cycles => Self::Cycles,
+            EmitKind::Object => Self::Object,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum InputFormat {
+    #[default]
+    Asm,
+    /// An ast in the json format produced by `--emit ast`
+    AstJson,
+    /// An ir in the json format produced by `--emit ir`
+    IrJson,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Compile every `.asm` file in a directory and print a summary of the results
+    Batch(batch::BatchArgs),
+    /// Compile a program and run it on a simulated cpu
+    Run(simulate::RunArgs),
+    /// Generate syntax highlighting for an editor from the opcodes and keywords of the parser
+    GenGrammar(grammar::GrammarArgs),
+    /// Link object files produced by `--emit object` into a single program
+    Link(link::LinkArgs),
 }
 
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, subcommand_negates_reqs = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
     #[arg(long, value_enum, default_value_t)]
     emit: EmitKind,
+    #[arg(long, value_enum, default_value_t)]
+    input_format: InputFormat,
+    /// How diagnostics are reported
+    #[arg(long, value_enum, default_value_t)]
+    message_format: MessageFormat,
     #[arg(long, short, default_value_os_t = PathBuf::from("./i281build/"))]
     out_dir: PathBuf,
-    filename: OsString,
+    /// Recompile whenever the input file changes
+    #[arg(long, short)]
+    watch: bool,
+    /// Don't report a lint, `warnings` selects every lint
+    #[arg(long, short = 'A', value_name = "LINT")]
+    allow: Vec<LintSelector>,
+    /// Report a lint as a warning, `warnings` selects every lint
+    #[arg(long, short = 'W', value_name = "LINT")]
+    warn: Vec<LintSelector>,
+    /// Report a lint as an error, `warnings` selects every lint
+    #[arg(long, short = 'D', value_name = "LINT")]
+    deny: Vec<LintSelector>,
+    /// The lint levels in the order they were given on the command line
+    #[arg(skip)]
+    lints: LintLevels,
+    #[arg(required = true)]
+    filename: Option<OsString>,
+}
+
+impl Args {
+    fn parse_with_lints() -> Self {
+        let matches = Self::command().get_matches();
+        let mut args = Self::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+        args.lints = lint_levels(&matches);
+        args
+    }
+
+    fn filename(&self) -> &OsStr {
+        self.filename
+            .as_deref()
+            .expect("a filename is required when there is no subcommand")
+    }
+}
+
+/// Collect the `-A`, `-W` and `-D` flags so that later flags override earlier ones
+fn lint_levels(matches: &ArgMatches) -> LintLevels {
+    let mut settings = Vec::new();
+    for (id, level) in [
+        ("allow", Level::Allow),
+        ("warn", Level::Warn),
+        ("deny", Level::Deny),
+    ] {
+        let (Some(indices), Some(values)) =
+            (matches.indices_of(id), matches.get_many::<LintSelector>(id))
+        else {
+            continue;
+        };
+        settings.extend(
+            indices
+                .zip(values)
+                .map(|(i, &selector)| (i, selector, level)),
+        );
+    }
+    settings.sort_by_key(|&(i, ..)| i);
+
+    let mut levels = LintLevels::default();
+    for (_, selector, level) in settings {
+        levels.set(selector, level);
+    }
+    levels
 }
 
 fn main() -> miette::Result<()> {
-    let args = Args::parse();
+    let args = Args::parse_with_lints();
 
-    let input = std::fs::read_to_string(args.filename).into_diagnostic()?;
-    let input = input.as_str();
+    match &args.command {
+        Some(Command::Batch(batch)) => return batch::run(batch, &args.lints),
+        Some(Command::Run(run)) => return simulate::run(run, &args.lints),
+        Some(Command::GenGrammar(grammar)) => return grammar::run(grammar),
+        Some(Command::Link(link)) => return link::run(link, &args.lints),
+        None => {}
+    }
+    if args.watch {
+        return watch::run(&args);
+    }
+    compile(&args)
+}
 
-    let ast = Root::parse(input).map_err(|e| dbg!(e))?;
-    match args.emit {
-        EmitKind::Ast => {
-            let output = std::io::stdout().lock();
-            serde_json::to_writer(output, &ast).into_diagnostic()?;
+fn compile(args: &Args) -> miette::Result<()> {
+    let input = std::fs::read_to_string(args.filename()).into_diagnostic()?;
+    let stem = Path::new(args.filename())
+        .file_stem()
+        .map_or("main".into(), OsStr::to_string_lossy);
+
+    let options = CompileOptions {
+        emit: vec![args.emit.into()],
+        lints: args.lints.clone(),
+        module_name: stem.to_string(),
+        ..Default::default()
+    };
+    let output = match args.input_format {
+        InputFormat::Asm => i281_compiler::compile(&input, &options),
+        InputFormat::AstJson => {
+            let ast = serde_json::from_str(&input).into_diagnostic()?;
+            i281_compiler::compile_ast(ast, &options)
         }
-        EmitKind::Ir => {
-            let output = std::io::stdout().lock();
-            let mut err = std::io::stderr().lock();
-            let ir = i281_compiler::analyze(&mut err, ast)?;
-            serde_json::to_writer(output, &ir).into_diagnostic()?;
+        InputFormat::IrJson => {
+            if matches!(
+                args.emit,
+                EmitKind::Ast | EmitKind::Listing | EmitKind::Report | EmitKind::Object
+            ) {
+                miette::bail!(
+                    "`--emit {:?}` requires an ast, it can not be produced from an ir",
+                    args.emit
+                );
+            }
+            let ir = serde_json::from_str(&input).into_diagnostic()?;
+            i281_compiler::compile_symbolic_ir(ir, &options)
         }
-        EmitKind::Verilog => {
-            let mut output = std::io::stderr().lock();
-            let ir = i281_compiler::analyze(&mut output, ast)?;
+    };
 
+    let filename = args.filename().to_string_lossy();
+    message_format::emit(args.message_format, &filename, &input, &output.diagnostics)?;
+    // the report is printed even if there were errors as it shows why a program does not fit
+    if let Some(report) = &output.report {
+        print!("{report}");
+    }
+    // the ast only needs the program to parse so it is printed even if checking it failed
+    if let Some(ast) = &output.ast {
+        let output_to = std::io::stdout().lock();
+        serde_json::to_writer(output_to, ast).into_diagnostic()?;
+    }
+    if output.has_errors() {
+        return Err(Failure::EncounteredError.into());
+    }
+
+    match args.emit {
+        EmitKind::Ast | EmitKind::Report => {} // already printed
+        EmitKind::Ir => {
+            let output_to = std::io::stdout().lock();
+            serde_json::to_writer(output_to, &output.symbolic_ir).into_diagnostic()?;
+        }
+        EmitKind::Listing => {
+            if let Some(listing) = output.listing {
+                print!("{listing}");
+            }
+        }
+        EmitKind::Cycles => {
+            if let Some(cycles) = output.cycles {
+                print!("{cycles}");
+            }
+        }
+        EmitKind::Verilog => write_verilog(&args.out_dir, &output.verilog)?,
+        EmitKind::Object => {
             if !args.out_dir.exists() {
                 std::fs::create_dir_all(&args.out_dir).into_diagnostic()?;
             }
 
-            let opts = OpenOptions::new()
-                .write(true)
-                .create(true)
-                .append(false)
-                .to_owned();
-
-            let code_low = &mut opts
-                .open(args.out_dir.join("User_Code_Low.v"))
-                .into_diagnostic()?;
-            let code_low = VerilogOutput::new("User_Code_Low", code_low);
-            let code_high = &mut opts
-                .open(args.out_dir.join("User_Code_High.v"))
-                .into_diagnostic()?;
-            let code_high = VerilogOutput::new("User_Code_High", code_high);
-            let data = &mut opts
-                .open(args.out_dir.join("User_Data.v"))
-                .into_diagnostic()?;
-            let data = VerilogOutput::new("User_Data", data);
-
-            i281_compiler::compile_verilog((code_low, code_high, data), ir).into_diagnostic()?;
+            let object = serde_json::to_vec(&output.object).into_diagnostic()?;
+            write_if_changed(&args.out_dir.join(format!("{stem}.o")), &object)?;
         }
     }
 
     Ok(())
 }
 
+/// Write every verilog module into the output directory, creating it if needed
+pub(crate) fn write_verilog(out_dir: &Path, modules: &[VerilogModule]) -> miette::Result<()> {
+    if !out_dir.exists() {
+        std::fs::create_dir_all(out_dir).into_diagnostic()?;
+    }
+
+    for module in modules.iter() {
+        let path = out_dir.join(format!("{}.v", module.name));
+        write_if_changed(&path, module.source.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Write `contents` to `path` unless the file already holds exactly those bytes
+///
+/// This keeps the modification time of unchanged outputs stable so tools watching
+/// the build directory (e.g. Quartus) don't see spurious changes.
+pub(crate) fn write_if_changed(path: &Path, contents: &[u8]) -> miette::Result<()> {
+    if std::fs::read(path).is_ok_and(|old| old == contents) {
+        return Ok(());
+    }
+    std::fs::write(path, contents).into_diagnostic()
+}
+
 #[cfg(test)]
 mod tests {
     use miette::IntoDiagnostic;
@@ -85,6 +271,32 @@ mod tests {
     use walkdir::WalkDir;
 
     use i281_ast::{ParseError, Root};
+    use i281_compiler::{CompileOptions, EmitKind};
+
+    #[test]
+    fn test_write_if_changed() -> miette::Result<()> {
+        use std::time::{Duration, SystemTime};
+
+        let path = env::temp_dir().join(format!("i281_write_if_changed_{}.v", std::process::id()));
+        std::fs::write(&path, "module").into_diagnostic()?;
+        let old = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let file = std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .into_diagnostic()?;
+        file.set_modified(old).into_diagnostic()?;
+        drop(file);
+        let modified = || std::fs::metadata(&path).and_then(|m| m.modified()).unwrap();
+
+        super::write_if_changed(&path, b"module")?;
+        assert_eq!(modified(), old);
+
+        super::write_if_changed(&path, b"changed")?;
+        assert_ne!(modified(), old);
+        assert_eq!(std::fs::read(&path).into_diagnostic()?, b"changed");
+
+        std::fs::remove_file(&path).into_diagnostic()
+    }
 
     #[test]
     fn test_examples() -> miette::Result<()> {
@@ -106,4 +318,43 @@ mod tests {
         }
         Ok(())
     }
+
+    #[test]
+    fn test_json_input() -> miette::Result<()> {
+        let current_dir = env::current_dir().into_diagnostic()?;
+        let options = CompileOptions {
+            emit: vec![EmitKind::Ast, EmitKind::Ir, EmitKind::Verilog],
+            ..Default::default()
+        };
+
+        for entry in WalkDir::new(current_dir)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|f| f.file_name().to_string_lossy().ends_with("asm"))
+        {
+            let input = std::fs::read_to_string(entry.path()).into_diagnostic()?;
+            let expected = i281_compiler::compile(&input, &options);
+
+            let ast = serde_json::to_string(&expected.ast).into_diagnostic()?;
+            let ast = serde_json::from_str(&ast).into_diagnostic()?;
+            let from_ast = i281_compiler::compile_ast(ast, &options);
+            assert_eq!(
+                from_ast.verilog,
+                expected.verilog,
+                "{}",
+                entry.path().display()
+            );
+
+            let ir = serde_json::to_string(&expected.symbolic_ir).into_diagnostic()?;
+            let ir = serde_json::from_str(&ir).into_diagnostic()?;
+            let from_ir = i281_compiler::compile_symbolic_ir(ir, &options);
+            assert_eq!(
+                from_ir.verilog,
+                expected.verilog,
+                "{}",
+                entry.path().display()
+            );
+        }
+        Ok(())
+    }
 }
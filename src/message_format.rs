@@ -0,0 +1,209 @@
+use std::io::Write;
+
+use i281_compiler::{Diagnostic, DiagnosticInfo, Severity};
+use miette::IntoDiagnostic;
+use serde_json::json;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MessageFormat {
+    /// Rendered diagnostics with source snippets on stderr
+    #[default]
+    Human,
+    /// One line per diagnostic on stderr in the style of gcc: `file:line:col: error: message`
+    Short,
+    /// One json object per diagnostic per line on stderr
+    Json,
+    /// A single SARIF 2.1.0 log on stderr
+    Sarif,
+}
+
+/// Output the diagnostics produced while compiling `file` in the requested format
+///
+/// Every format goes to stderr so it never mixes with `--emit` output on stdout
+pub fn emit(
+    format: MessageFormat,
+    file: &str,
+    source: &str,
+    diagnostics: &[Diagnostic],
+) -> miette::Result<()> {
+    let mut err = std::io::stderr().lock();
+    write(&mut err, format, file, source, diagnostics)
+}
+
+fn write<W: Write>(
+    out: &mut W,
+    format: MessageFormat,
+    file: &str,
+    source: &str,
+    diagnostics: &[Diagnostic],
+) -> miette::Result<()> {
+    let infos = || diagnostics.iter().map(|d| DiagnosticInfo::new(d, source));
+    match format {
+        MessageFormat::Human => i281_compiler::render_diagnostics(out, Some(source), diagnostics),
+        MessageFormat::Short => {
+            for info in infos() {
+                write_short(out, file, &info).into_diagnostic()?;
+            }
+            Ok(())
+        }
+        MessageFormat::Json => {
+            for info in infos() {
+                let mut value = serde_json::to_value(&info).into_diagnostic()?;
+                value["file"] = json!(file);
+                serde_json::to_writer(&mut *out, &value).into_diagnostic()?;
+                writeln!(out).into_diagnostic()?;
+            }
+            Ok(())
+        }
+        MessageFormat::Sarif => {
+            let log = sarif(file, source, &infos().collect::<Vec<_>>());
+            serde_json::to_writer_pretty(&mut *out, &log).into_diagnostic()?;
+            writeln!(out).into_diagnostic()
+        }
+    }
+}
+
+fn write_short<W: Write>(out: &mut W, file: &str, info: &DiagnosticInfo) -> std::io::Result<()> {
+    let location = |span: Option<&i281_compiler::SpanInfo>| match span {
+        Some(span) => format!("{file}:{}:{}", span.line, span.column),
+        None => file.to_owned(),
+    };
+
+    write!(
+        out,
+        "{}: {}: {}",
+        location(info.primary_span()),
+        info.severity,
+        info.message
+    )?;
+    match &info.code {
+        Some(code) => writeln!(out, " [{code}]")?,
+        None => writeln!(out)?,
+    }
+    for span in info.spans.iter().filter(|s| !s.primary) {
+        let label = span.label.as_deref().unwrap_or("related to this");
+        writeln!(out, "{}: note: {label}", location(Some(span)))?;
+    }
+    if let Some(help) = &info.help {
+        writeln!(out, "{}: help: {help}", location(info.primary_span()))?;
+    }
+    Ok(())
+}
+
+fn sarif(file: &str, source: &str, infos: &[DiagnosticInfo]) -> serde_json::Value {
+    // SARIF counts columns and offsets in UTF-16 code units, spans are in bytes
+    let utf16 = |s: &str| s.encode_utf16().count();
+    let column = |offset: usize| {
+        let line_start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+        utf16(&source[line_start..offset]) + 1
+    };
+    let region = |span: &i281_compiler::SpanInfo| {
+        let end = span.offset + span.len;
+        json!({
+            "startLine": span.line,
+            "startColumn": column(span.offset),
+            "endLine": span.end_line,
+            "endColumn": column(end),
+            "charOffset": utf16(&source[..span.offset]),
+            "charLength": utf16(&source[span.offset..end]),
+        })
+    };
+    let location = |span: &i281_compiler::SpanInfo| {
+        json!({
+            "physicalLocation": {
+                "artifactLocation": { "uri": file },
+                "region": region(span),
+            },
+            "message": { "text": span.label.clone().unwrap_or_default() },
+        })
+    };
+
+    let mut rules: Vec<&str> = infos.iter().filter_map(|i| i.code.as_deref()).collect();
+    rules.sort_unstable();
+    rules.dedup();
+
+    let results: Vec<_> = infos
+        .iter()
+        .map(|info| {
+            let level = match info.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+                Severity::Advice => "note",
+            };
+            let mut result = json!({
+                "level": level,
+                "message": { "text": info.message },
+                "locations": info.primary_span().map(location).into_iter().collect::<Vec<_>>(),
+                "relatedLocations": info
+                    .spans
+                    .iter()
+                    .filter(|s| !s.primary)
+                    .map(location)
+                    .collect::<Vec<_>>(),
+            });
+            if let Some(code) = &info.code {
+                result["ruleId"] = json!(code);
+            }
+            if let Some(help) = &info.help {
+                result["properties"] = json!({ "help": help });
+            }
+            result
+        })
+        .collect();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_BIN_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules.iter().map(|id| json!({ "id": id })).collect::<Vec<_>>(),
+                },
+            },
+            "columnKind": "utf16CodeUnits",
+            "artifacts": [{ "location": { "uri": file } }],
+            "results": results,
+        }],
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::MessageFormat;
+
+    // the non-ascii comment moves byte offsets away from SARIF's UTF-16 offsets
+    const SOURCE: &str = ".code\n; héllo 😀\nl:  NOOP\nl:  JUMP nowhere\n";
+
+    /// Compare the output of `format` against `tests/golden/<name>`, set `UPDATE_GOLDEN=1` to regenerate it
+    fn golden(format: MessageFormat, name: &str) {
+        let output = i281_compiler::compile(SOURCE, &Default::default());
+        let mut out = Vec::new();
+        super::write(&mut out, format, "golden.asm", SOURCE, &output.diagnostics).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        let path = format!("{}/tests/golden/{name}", env!("CARGO_MANIFEST_DIR"));
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::write(&path, out).unwrap();
+        } else {
+            let expected = std::fs::read_to_string(&path).unwrap();
+            assert_eq!(out, expected, "run `UPDATE_GOLDEN=1 cargo test`");
+        }
+    }
+
+    #[test]
+    fn format1() {
+        golden(MessageFormat::Short, "short.txt");
+    }
+
+    #[test]
+    fn format2() {
+        golden(MessageFormat::Json, "diagnostics.jsonl");
+    }
+
+    #[test]
+    fn format3() {
+        golden(MessageFormat::Sarif, "diagnostics.sarif");
+    }
+}
@@ -0,0 +1,113 @@
+use std::{
+    io::{BufRead, Write},
+    path::PathBuf,
+};
+
+use i281_compiler::{
+    CompileOptions, Diagnostic, EmitKind, Failure, InputRequest, LintLevels, ScriptedSwitches,
+    Simulator, SwitchValue, Switches, DEFAULT_MAX_STEPS,
+};
+use miette::IntoDiagnostic;
+
+use crate::message_format::{self, MessageFormat};
+
+#[derive(clap::Args, Debug)]
+pub struct RunArgs {
+    /// The assembly file to run
+    filename: PathBuf,
+    /// Values set on the switches for each input instruction in order, e.g. `0x12,7,-1`
+    #[arg(
+        long,
+        value_name = "VALUES",
+        value_delimiter = ',',
+        allow_negative_numbers = true,
+        conflicts_with = "interactive"
+    )]
+    switches: Vec<SwitchValue>,
+    /// Read the switch values from a file, separated by commas or whitespace
+    #[arg(long, value_name = "PATH", conflicts_with = "interactive")]
+    switches_file: Option<PathBuf>,
+    /// Ask for the switch values on stdin whenever an input instruction runs
+    #[arg(long, short)]
+    interactive: bool,
+    /// Print every instruction that runs and every input event
+    #[arg(long, short)]
+    trace: bool,
+    /// Stop with an error if the program has not halted after this many instructions
+    #[arg(long, default_value_t = DEFAULT_MAX_STEPS)]
+    max_steps: usize,
+}
+
+/// Prompts for each input on stdin, an empty line or the end of input stops the program
+#[derive(Debug)]
+struct Prompt;
+
+impl Switches for Prompt {
+    fn read(&mut self, request: &InputRequest) -> Option<SwitchValue> {
+        let stdin = std::io::stdin();
+        loop {
+            eprint!("{request}: ");
+            std::io::stderr().flush().ok()?;
+            let mut line = String::new();
+            stdin.lock().read_line(&mut line).ok()?;
+            if line.trim().is_empty() {
+                return None;
+            }
+            match line.parse() {
+                Ok(value) => return Some(value),
+                Err(err) => eprintln!("{err}"),
+            }
+        }
+    }
+}
+
+/// Compile a program and run it, printing the final state of the cpu
+pub fn run(args: &RunArgs, lints: &LintLevels) -> miette::Result<()> {
+    let input = std::fs::read_to_string(&args.filename).into_diagnostic()?;
+    let options = CompileOptions {
+        emit: vec![EmitKind::Ir],
+        lints: lints.clone(),
+        ..Default::default()
+    };
+    let output = i281_compiler::compile(&input, &options);
+    let filename = args.filename.to_string_lossy();
+    message_format::emit(MessageFormat::Human, &filename, &input, &output.diagnostics)?;
+    if output.has_errors() {
+        return Err(Failure::EncounteredError.into());
+    }
+    let ir = output
+        .ir
+        .expect("the ir is emitted when there are no errors");
+
+    // the compiler only emits ir that loads, this is reported like a compile error otherwise
+    let report = |diagnostics: Vec<Diagnostic>| -> miette::Report {
+        match message_format::emit(MessageFormat::Human, &filename, &input, &diagnostics) {
+            Ok(()) => Failure::EncounteredError.into(),
+            Err(err) => err,
+        }
+    };
+
+    if args.interactive {
+        return simulate(args, Simulator::new(&ir, Prompt).map_err(report)?);
+    }
+    let mut values = args.switches.clone();
+    if let Some(path) = &args.switches_file {
+        let contents = std::fs::read_to_string(path).into_diagnostic()?;
+        values.extend(SwitchValue::parse_list(&contents).map_err(|err| miette::miette!("{err}"))?);
+    }
+    let sim = Simulator::new(&ir, ScriptedSwitches::new(values)).map_err(report)?;
+    simulate(args, sim)
+}
+
+fn simulate<S: Switches>(args: &RunArgs, mut sim: Simulator<S>) -> miette::Result<()> {
+    let result = sim.run(args.max_steps);
+    // the trace is printed even if the program failed as it shows how it got there
+    if args.trace {
+        for event in sim.trace.iter() {
+            println!("{event}");
+        }
+    }
+    result?;
+    println!("{sim}");
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use std::{
+    path::PathBuf,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use crate::Args;
+
+/// How often the watched files are checked for modifications
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Clears the terminal and moves the cursor to the top left corner
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[1;1H";
+
+/// Run the compiler every time one of the input files changes
+///
+/// This never returns, stop it with Ctrl-C. A file that is temporarily missing (editors
+/// commonly remove and recreate a file when saving) is treated as a change, not an error.
+pub fn run(args: &Args) -> miette::Result<()> {
+    let files = watched_files(args);
+    let mut last_seen = modified_times(&files);
+
+    loop {
+        eprint!("{CLEAR_SCREEN}");
+        match crate::compile(args) {
+            Ok(()) => eprintln!("[watch] compiled successfully"),
+            Err(report) => eprintln!("{report:?}"),
+        }
+        eprintln!("[watch] waiting for changes to {}", display_files(&files));
+
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            let current = modified_times(&files);
+            if current != last_seen {
+                last_seen = current;
+                break;
+            }
+        }
+    }
+}
+
+/// All files that contribute to the compiled output
+///
+/// The assembly language has no include directive so this is only ever the input file
+fn watched_files(args: &Args) -> Vec<PathBuf> {
+    vec![PathBuf::from(args.filename())]
+}
+
+fn modified_times(files: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    files
+        .iter()
+        .map(|file| std::fs::metadata(file).and_then(|m| m.modified()).ok())
+        .collect()
+}
+
+fn display_files(files: &[PathBuf]) -> String {
+    files
+        .iter()
+        .map(|f| f.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}